@@ -0,0 +1,317 @@
+//! 无界面命令行模式
+//! 供服务器或容器中运行反代服务，以及在终端里管理账号
+
+use crate::commands::proxy::ProxyServiceInstance;
+use crate::modules::{self, logger};
+
+const USAGE: &str = "用法: antigravity-tools <命令>
+
+命令:
+  --headless [--port <端口>] [--bind <地址>]   无界面启动反代服务（同 proxy start）
+  proxy start [--port <端口>] [--bind <地址>]  启动反代服务，收到 SIGTERM / Ctrl+C 后退出
+  accounts list                              列出账号
+  accounts add <refresh_token>               使用 refresh_token 添加账号
+  accounts remove <账号 ID 或邮箱>            删除账号
+  quota refresh [--group <分组>]              刷新账号配额
+  help                                       显示本帮助
+
+全局选项:
+  --data-dir <目录>                          指定数据目录（也可通过环境变量 ANTIGRAVITY_TOOLS_DATA_DIR 指定）
+
+不带以上命令时启动图形界面。";
+
+/// 命令行子命令
+#[derive(Debug, Clone, PartialEq)]
+enum CliCommand {
+    ProxyStart { port: Option<u16>, bind: Option<String> },
+    AccountsList,
+    AccountsAdd { refresh_token: String },
+    AccountsRemove { account: String },
+    QuotaRefresh { group: Option<String> },
+    Help,
+}
+
+/// 解析命令行参数（不含程序名）
+///
+/// # 返回
+/// 不是命令行模式时返回 None，由调用方启动图形界面
+fn parse_args(args: &[String]) -> Option<Result<CliCommand, String>> {
+    let first = args.first()?.as_str();
+    let rest = &args[1..];
+
+    let command = match first {
+        "--headless" => parse_proxy_options(rest),
+        "proxy" => match rest.first().map(String::as_str) {
+            Some("start") => parse_proxy_options(&rest[1..]),
+            _ => Err("用法: proxy start [--port <端口>] [--bind <地址>]".to_string()),
+        },
+        "accounts" => match (rest.first().map(String::as_str), rest.get(1)) {
+            (Some("list"), None) => Ok(CliCommand::AccountsList),
+            (Some("add"), Some(token)) => Ok(CliCommand::AccountsAdd { refresh_token: token.clone() }),
+            (Some("remove"), Some(account)) => Ok(CliCommand::AccountsRemove { account: account.clone() }),
+            _ => Err("用法: accounts list | accounts add <refresh_token> | accounts remove <账号 ID 或邮箱>".to_string()),
+        },
+        "quota" => match rest.first().map(String::as_str) {
+            Some("refresh") => option_value(&rest[1..], "--group")
+                .map(|group| CliCommand::QuotaRefresh { group }),
+            _ => Err("用法: quota refresh [--group <分组>]".to_string()),
+        },
+        "help" | "--help" | "-h" => Ok(CliCommand::Help),
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn parse_proxy_options(args: &[String]) -> Result<CliCommand, String> {
+    let port = option_value(args, "--port")?
+        .map(|p| p.parse::<u16>().map_err(|_| format!("无效的端口: {}", p)))
+        .transpose()?;
+    let bind = option_value(args, "--bind")?;
+    Ok(CliCommand::ProxyStart { port, bind })
+}
+
+/// 取出 `--name value` 形式的选项，并从参数列表中移除
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let value = option_value(args, name)?;
+    if let Some(i) = args.iter().position(|a| a == name) {
+        args.drain(i..i + 2);
+    }
+    Ok(value)
+}
+
+/// 读取 `--name value` 形式的选项
+fn option_value(args: &[String], name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|a| a == name) {
+        Some(i) => args
+            .get(i + 1)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| format!("{} 缺少参数值", name)),
+        None => Ok(None),
+    }
+}
+
+/// Windows 发布版使用 GUI 子系统 (见 main.rs)，进程没有控制台，println!/eprintln! 的输出会被丢弃；
+/// 命令行模式下附加到启动它的终端，使输出可见
+#[cfg(windows)]
+fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    // 不是从终端启动 (或调试版已有控制台) 时附加失败，忽略即可
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
+
+/// 按命令行参数执行无界面命令
+///
+/// # 返回
+/// 不是命令行模式时返回 None；否则返回进程退出码
+pub fn try_run() -> Option<i32> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // --data-dir 对图形界面同样生效，需在访问数据目录之前处理
+    match take_option(&mut args, "--data-dir") {
+        Ok(Some(dir)) => {
+            if let Err(e) = modules::account::set_data_dir_override(dir.into()) {
+                attach_parent_console();
+                eprintln!("{}", e);
+                return Some(2);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => {
+            attach_parent_console();
+            eprintln!("{}\n\n{}", e, USAGE);
+            return Some(2);
+        }
+    }
+
+    let parsed = parse_args(&args)?;
+    attach_parent_console();
+    let command = match parsed {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return Some(2);
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("创建异步运行时失败: {}", e);
+            return Some(1);
+        }
+    };
+
+    match runtime.block_on(run_command(command)) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
+
+async fn run_command(command: CliCommand) -> Result<(), String> {
+    match command {
+        CliCommand::ProxyStart { port, bind } => proxy_start(port, bind).await,
+        CliCommand::AccountsList => accounts_list(),
+        CliCommand::AccountsAdd { refresh_token } => {
+            let account = crate::commands::add_account(String::new(), refresh_token).await?;
+            println!("已添加账号: {} ({})", account.email, account.id);
+            Ok(())
+        }
+        CliCommand::AccountsRemove { account } => accounts_remove(&account),
+        CliCommand::QuotaRefresh { group } => {
+            let stats = modules::quota_refresh::refresh_quotas(None, group.as_deref()).await?;
+            println!("配额刷新完成: {} 成功, {} 失败", stats.success, stats.failed);
+            for detail in &stats.details {
+                println!("  {}", detail);
+            }
+            Ok(())
+        }
+        CliCommand::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn accounts_list() -> Result<(), String> {
+    let current_id = modules::get_current_account_id()?;
+    for account in modules::list_accounts()? {
+        let mut flags = Vec::new();
+        if current_id.as_deref() == Some(account.id.as_str()) {
+            flags.push("当前".to_string());
+        }
+        if account.disabled {
+            flags.push("已禁用".to_string());
+        }
+        if !account.proxy_enabled {
+            flags.push("不参与反代".to_string());
+        }
+        if !account.groups.is_empty() {
+            flags.push(format!("分组: {}", account.groups.join(",")));
+        }
+        println!("{}\t{}\t{}", account.id, account.email, flags.join(" | "));
+    }
+    Ok(())
+}
+
+fn accounts_remove(target: &str) -> Result<(), String> {
+    let account = modules::list_accounts()?
+        .into_iter()
+        .find(|a| a.id == target || a.email == target)
+        .ok_or_else(|| format!("账号不存在: {}", target))?;
+    modules::delete_account(&account.id)?;
+    println!("已删除账号: {} ({})", account.email, account.id);
+    Ok(())
+}
+
+/// 无界面启动反代服务，直到收到退出信号
+async fn proxy_start(port: Option<u16>, bind: Option<String>) -> Result<(), String> {
+    logger::init_logger();
+
+    if let Err(e) = modules::account::migrate_plaintext_tokens() {
+        logger::log_error(&format!("迁移明文 Token 失败: {}", e));
+    }
+
+    let mut config = modules::config::load_app_config()?.proxy;
+    if let Some(port) = port {
+        config.port = port;
+    }
+    if let Some(bind) = bind {
+        config.bind_address = bind;
+    }
+
+    let (instance, active_accounts) = ProxyServiceInstance::start(config.clone(), None).await?;
+    logger::log_info(&format!(
+        "反代服务已启动: http://{}:{} ({} 个账号参与轮换)",
+        config.bind_address, config.port, active_accounts
+    ));
+
+    wait_for_shutdown().await;
+    logger::log_info("收到退出信号，正在停止反代服务...");
+    instance.stop().await;
+    logger::log_info("反代服务已停止");
+    Ok(())
+}
+
+/// 等待 Ctrl+C 或 SIGTERM
+async fn wait_for_shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                logger::log_warn(&format!("注册 SIGTERM 处理失败: {}", e));
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_falls_back_to_gui() {
+        assert!(parse_args(&[]).is_none());
+        assert!(parse_args(&args(&["--minimized"])).is_none());
+    }
+
+    #[test]
+    fn test_parse_args_subcommands() {
+        assert_eq!(
+            parse_args(&args(&["--headless", "--port", "9000", "--bind", "0.0.0.0"])),
+            Some(Ok(CliCommand::ProxyStart { port: Some(9000), bind: Some("0.0.0.0".to_string()) }))
+        );
+        assert_eq!(
+            parse_args(&args(&["proxy", "start"])),
+            Some(Ok(CliCommand::ProxyStart { port: None, bind: None }))
+        );
+        assert_eq!(
+            parse_args(&args(&["accounts", "remove", "a@example.com"])),
+            Some(Ok(CliCommand::AccountsRemove { account: "a@example.com".to_string() }))
+        );
+        assert_eq!(
+            parse_args(&args(&["quota", "refresh", "--group", "team-a"])),
+            Some(Ok(CliCommand::QuotaRefresh { group: Some("team-a".to_string()) }))
+        );
+        assert!(matches!(parse_args(&args(&["proxy", "start", "--port", "x"])), Some(Err(_))));
+        assert!(matches!(parse_args(&args(&["accounts", "add"])), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_take_option_removes_global_flag() {
+        let mut list = args(&["--data-dir", "/tmp/profile", "accounts", "list"]);
+        assert_eq!(take_option(&mut list, "--data-dir").unwrap().as_deref(), Some("/tmp/profile"));
+        assert_eq!(list, args(&["accounts", "list"]));
+        assert!(take_option(&mut args(&["--data-dir"]), "--data-dir").is_err());
+    }
+}
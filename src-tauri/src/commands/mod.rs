@@ -1,6 +1,6 @@
-use crate::models::{Account, TokenData, QuotaData, AppConfig};
+use crate::models::{Account, TokenData, QuotaData, AppConfig, SwitchMode};
 use crate::modules;
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
 // 导出 proxy 命令
 pub mod proxy;
@@ -51,9 +51,50 @@ pub async fn delete_account(account_id: String) -> Result<(), String> {
     Ok(())
 }
 
-/// 切换账号
+/// 启用或禁用账号
 #[tauri::command]
-pub async fn switch_account(app: tauri::AppHandle, account_id: String) -> Result<(), String> {
+pub async fn set_account_disabled(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    disabled: bool,
+) -> Result<Account, String> {
+    let account = modules::account::set_account_disabled(&account_id, disabled)?;
+    crate::commands::proxy::sync_account_to_proxy(&proxy_state, &account_id).await?;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(account)
+}
+
+/// 设置账号是否加入反代账号池
+#[tauri::command]
+pub async fn set_account_proxy_enabled(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    enabled: bool,
+) -> Result<Account, String> {
+    let account = modules::account::set_account_proxy_enabled(&account_id, enabled)?;
+    crate::commands::proxy::sync_account_to_proxy(&proxy_state, &account_id).await?;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(account)
+}
+
+/// 设置账号分组
+#[tauri::command]
+pub async fn set_account_groups(account_id: String, groups: Vec<String>) -> Result<Account, String> {
+    // 反代服务会通过账号变更通知自动热更新分组
+    modules::account::set_account_groups(&account_id, groups)
+}
+
+/// 列出所有账号分组
+#[tauri::command]
+pub async fn list_account_groups() -> Result<Vec<String>, String> {
+    modules::account::list_account_groups()
+}
+
+/// 切换账号，返回实际使用的切换方式
+#[tauri::command]
+pub async fn switch_account(app: tauri::AppHandle, account_id: String) -> Result<SwitchMode, String> {
     let res = modules::switch_account(&account_id).await;
     if res.is_ok() {
         crate::modules::tray::update_tray_menus(&app);
@@ -88,7 +129,15 @@ pub async fn fetch_account_quota(app: tauri::AppHandle, account_id: String) -> c
     let mut account = modules::load_account(&account_id).map_err(crate::error::AppError::Account)?;
     
     // 使用带重试的查询 (Shared logic)
-    let quota = modules::account::fetch_quota_with_retry(&mut account).await?;
+    let quota = match modules::account::fetch_quota_with_retry(&mut account).await {
+        Ok(quota) => quota,
+        Err(e) => {
+            if account.disabled {
+                modules::account::emit_needs_reauth(&app, &account.id, &account.email, &e.to_string());
+            }
+            return Err(e);
+        }
+    };
     
     // 4. 更新账号配额
     modules::update_account_quota(&account_id, quota.clone()).map_err(crate::error::AppError::Account)?;
@@ -98,60 +147,44 @@ pub async fn fetch_account_quota(app: tauri::AppHandle, account_id: String) -> c
     Ok(quota)
 }
 
-#[derive(serde::Serialize)]
-pub struct RefreshStats {
-    total: usize,
-    success: usize,
-    failed: usize,
-    details: Vec<String>,
+/// 刷新所有账号配额
+/// 指定 group 时只刷新该分组内的账号
+#[tauri::command]
+pub async fn refresh_all_quotas(
+    app: tauri::AppHandle,
+    group: Option<String>,
+) -> Result<modules::quota_refresh::RefreshStats, String> {
+    modules::quota_refresh::refresh_quotas(Some(&app), group.as_deref()).await
 }
 
-/// 刷新所有账号配额
+/// 查询账号的配额历史
 #[tauri::command]
-pub async fn refresh_all_quotas() -> Result<RefreshStats, String> {
-    modules::logger::log_info("开始批量刷新所有账号配额");
-    let accounts = modules::list_accounts()?;
-    
-    let mut success = 0;
-    let mut failed = 0;
-    let mut details = Vec::new();
+pub async fn get_quota_history(
+    account_id: String,
+    model: Option<String>,
+    since: Option<i64>,
+) -> Result<Vec<modules::quota_history::QuotaHistoryPoint>, String> {
+    modules::quota_history::get_history(&account_id, model.as_deref(), since)
+}
 
-    // 串行处理以确保持久化安全 (SQLite)
-    for mut account in accounts {
-        if let Some(ref q) = account.quota {
-            if q.is_forbidden {
-                modules::logger::log_info(&format!("  - Skipping {} (Forbidden)", account.email));
-                continue;
-            }
-        }
-        
-        modules::logger::log_info(&format!("  - Processing {}", account.email));
-        
-        match modules::account::fetch_quota_with_retry(&mut account).await {
-            Ok(quota) => {
-                 // 保存配额
-                 if let Err(e) = modules::update_account_quota(&account.id, quota) {
-                     failed += 1;
-                     let msg = format!("Account {}: Save quota failed - {}", account.email, e);
-                     details.push(msg.clone());
-                     modules::logger::log_error(&msg);
-                 } else {
-                     success += 1;
-                     modules::logger::log_info("    ✅ Success");
-                 }
-            },
-            Err(e) => {
-                failed += 1;
-                // e might be AppError, assume it implements Display
-                let msg = format!("Account {}: Fetch quota failed - {}", account.email, e);
-                details.push(msg.clone());
-                modules::logger::log_error(&msg);
-            }
-        }
-    }
-    
-    modules::logger::log_info(&format!("批量刷新完成: {} 成功, {} 失败", success, failed));
-    Ok(RefreshStats { total: success + failed, success, failed, details })
+/// 预测配额耗尽时间，不指定账号时返回全部账号
+#[tauri::command]
+pub async fn get_quota_forecasts(
+    account_id: Option<String>,
+) -> Result<Vec<modules::quota_history::QuotaForecast>, String> {
+    modules::quota_history::get_forecasts(account_id.as_deref())
+}
+
+/// 列出 Antigravity 数据库备份，最新的在前
+#[tauri::command]
+pub async fn list_db_backups() -> Result<Vec<modules::db_backup::DbBackupInfo>, String> {
+    modules::db_backup::list_backups()
+}
+
+/// 将 Antigravity 数据库恢复到指定备份
+#[tauri::command]
+pub async fn restore_db_backup(backup_id: String) -> Result<(), String> {
+    modules::db_backup::restore_backup(&backup_id)
 }
 
 /// 加载配置
@@ -172,6 +205,13 @@ pub async fn save_config(
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
+    // 日志级别与格式立即生效
+    modules::logger::apply_config(&config.log);
+
+    // 按新配置重启配额定时刷新与自动切换账号
+    app.state::<modules::quota_refresh::QuotaSchedulerState>().apply_config(&app, &config);
+    app.state::<modules::auto_switch::AutoSwitchState>().apply_config(&app, &config);
+
     // 热更新正在运行的服务
     let instance_lock = proxy_state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
@@ -179,6 +219,14 @@ pub async fn save_config(
         instance.axum_server.update_mapping(config.proxy.anthropic_mapping.clone()).await;
         // 更新上游代理
         instance.axum_server.update_proxy(config.proxy.upstream_proxy.clone()).await;
+        // 更新 API 密钥
+        instance.axum_server.update_api_key(config.proxy.api_key.clone()).await;
+        // 更新 API 密钥分组限制
+        instance.axum_server.update_api_key_groups(config.proxy.api_key_groups.clone()).await;
+        // 更新管理接口密钥
+        instance.axum_server.update_admin_key(config.proxy.admin_key.clone()).await;
+        // 更新重试策略
+        instance.axum_server.update_retry_policy(config.proxy.retry.clone()).await;
         tracing::info!("已同步热更新反代服务配置");
     }
     
@@ -236,6 +284,61 @@ pub async fn start_oauth_login(app_handle: tauri::AppHandle) -> Result<Account,
     modules::upsert_account(user_info.email.clone(), user_info.get_display_name(), token_data)
 }
 
+/// 重新授权已失效的账号
+/// 运行 OAuth 流程，并就地更新原账号的 Token（保留 ID、配额等信息）
+#[tauri::command]
+pub async fn reauthorize_account(
+    app_handle: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+) -> Result<Account, String> {
+    let account = modules::load_account(&account_id)?;
+    modules::logger::log_info(&format!("开始重新授权账号: {}", account.email));
+
+    let token_res = modules::oauth_server::start_oauth_flow(app_handle.clone()).await?;
+    let refresh_token = token_res.refresh_token.ok_or_else(|| {
+        "未获取到 Refresh Token，请在 https://myaccount.google.com/permissions 撤销授权后重试".to_string()
+    })?;
+
+    // 必须使用同一个 Google 账号授权
+    let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
+    if user_info.email != account.email {
+        return Err(format!(
+            "授权的账号 ({}) 与待重新授权的账号 ({}) 不一致",
+            user_info.email, account.email
+        ));
+    }
+
+    // OAuth 流程可能持续数分钟，在文件锁内基于最新内容更新，避免覆盖期间写入的配额、禁用状态等修改
+    let display_name = user_info.get_display_name();
+    let account = modules::update_account(&account_id, |account| {
+        account.token = TokenData::new(
+            token_res.access_token,
+            refresh_token,
+            token_res.expires_in,
+            Some(user_info.email.clone()),
+            account.token.project_id.clone(),
+            account.token.session_id.clone(),
+        );
+        if display_name.is_some() {
+            account.name = display_name;
+        }
+        account.enable();
+    })?;
+    modules::logger::log_info(&format!("账号重新授权成功: {}", account.email));
+
+    // 反代服务运行中时重新加载，使账号回到轮换
+    let instance_lock = proxy_state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        if let Err(e) = instance.token_manager.load_accounts().await {
+            modules::logger::log_warn(&format!("重新加载反代账号失败: {}", e));
+        }
+    }
+
+    crate::modules::tray::update_tray_menus(&app_handle);
+    Ok(account)
+}
+
 #[tauri::command]
 pub async fn cancel_oauth_login() -> Result<(), String> {
     modules::oauth_server::cancel_oauth_flow();
@@ -255,6 +358,36 @@ pub async fn import_from_db() -> Result<Account, String> {
     modules::migration::import_from_db().await
 }
 
+/// 批量导入 refresh_token (纯文本 / CSV / JSON)
+#[tauri::command]
+pub async fn import_refresh_tokens(
+    app: tauri::AppHandle,
+    content: String,
+) -> Result<modules::bulk_import::BulkImportReport, String> {
+    let report = modules::bulk_import::import_refresh_tokens(&content).await?;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(report)
+}
+
+/// 导出口令加密的账号包
+#[tauri::command]
+pub async fn export_account_bundle(path: String, passphrase: String) -> Result<usize, String> {
+    modules::bundle::export_bundle(std::path::Path::new(&path), &passphrase)
+}
+
+/// 导入口令加密的账号包
+#[tauri::command]
+pub async fn import_account_bundle(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: String,
+    conflict: modules::bundle::ConflictStrategy,
+) -> Result<modules::bundle::BundleImportReport, String> {
+    let report = modules::bundle::import_bundle(std::path::Path::new(&path), &passphrase, conflict)?;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(report)
+}
+
 /// 保存文本文件 (绕过前端 Scope 限制)
 #[tauri::command]
 pub async fn save_text_file(path: String, content: String) -> Result<(), String> {
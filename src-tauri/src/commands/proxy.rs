@@ -2,6 +2,10 @@ use tauri::State;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
+use tauri::Emitter;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use crate::proxy::events::{self, PROXY_EVENT};
 use crate::proxy::{ProxyConfig, TokenManager};
 
 /// 反代服务状态
@@ -13,12 +17,25 @@ pub struct ProxyStatus {
     pub active_accounts: usize,
 }
 
-/// 反代服务统计
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct ProxyStats {
-    pub total_requests: u64,
-    pub success_count: u64,
-    pub error_count: u64,
+pub use crate::proxy::stats::ProxyStats;
+
+/// 反代请求事件转发状态
+pub struct ProxyEventForwarder {
+    cancel_token: std::sync::Mutex<Option<CancellationToken>>,
+}
+
+impl ProxyEventForwarder {
+    pub fn new() -> Self {
+        Self {
+            cancel_token: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl Default for ProxyEventForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// 反代服务全局状态
@@ -34,6 +51,58 @@ pub struct ProxyServiceInstance {
     pub server_handle: tokio::task::JoinHandle<()>,
 }
 
+impl ProxyServiceInstance {
+    /// 加载账号并启动 Axum 服务器
+    ///
+    /// # 参数
+    /// - `config`: 反代配置
+    /// - `app_handle`: 用于向前端发送事件，无界面模式下为 None
+    ///
+    /// # 返回
+    /// 服务实例与参与轮换的账号数量
+    pub async fn start(
+        config: ProxyConfig,
+        app_handle: Option<tauri::AppHandle>,
+    ) -> Result<(Self, usize), String> {
+        // 1. 初始化 Token 管理器
+        let accounts_dir = crate::modules::account::get_data_dir()?;
+        let mut token_manager = TokenManager::new(accounts_dir);
+        if let Some(app_handle) = app_handle {
+            token_manager = token_manager.with_app_handle(app_handle);
+        }
+        let token_manager = Arc::new(token_manager);
+        
+        // 2. 加载账号
+        let active_accounts = token_manager.load_accounts().await
+            .map_err(|e| format!("加载账号失败: {}", e))?;
+        
+        if active_accounts == 0 {
+            return Err("没有可用账号，请先添加账号".to_string());
+        }
+        
+        // 3. 启动 Axum 服务器
+        let (axum_server, server_handle) = crate::proxy::AxumServer::start(&config, token_manager.clone())
+            .await
+        .map_err(|e| format!("启动 Axum 服务器失败: {}", e))?;
+        
+        Ok((
+            Self {
+                config,
+                token_manager,
+                axum_server,
+                server_handle,
+            },
+            active_accounts,
+        ))
+    }
+    
+    /// 停止服务器并等待任务结束
+    pub async fn stop(self) {
+        self.axum_server.stop();
+        self.server_handle.await.ok();
+    }
+}
+
 impl ProxyServiceState {
     pub fn new() -> Self {
         Self {
@@ -47,7 +116,7 @@ impl ProxyServiceState {
 pub async fn start_proxy_service(
     config: ProxyConfig,
     state: State<'_, ProxyServiceState>,
-    _app_handle: tauri::AppHandle,
+    app_handle: tauri::AppHandle,
 ) -> Result<ProxyStatus, String> {
     let mut instance_lock = state.instance.write().await;
     
@@ -56,40 +125,7 @@ pub async fn start_proxy_service(
         return Err("服务已在运行中".to_string());
     }
     
-    // 2. 初始化 Token 管理器
-    let app_data_dir = crate::modules::account::get_data_dir()?;
-    let accounts_dir = app_data_dir.clone();
-    
-    let token_manager = Arc::new(TokenManager::new(accounts_dir));
-    
-    // 3. 加载账号
-    let active_accounts = token_manager.load_accounts().await
-        .map_err(|e| format!("加载账号失败: {}", e))?;
-    
-    if active_accounts == 0 {
-        return Err("没有可用账号，请先添加账号".to_string());
-    }
-    
-    // 启动 Axum 服务器
-    let (axum_server, server_handle) = 
-        match crate::proxy::AxumServer::start(
-            config.port,
-            token_manager.clone(),
-            config.anthropic_mapping.clone(),
-            config.request_timeout,
-            config.upstream_proxy.clone(),
-        ).await {
-            Ok((server, handle)) => (server, handle),
-            Err(e) => return Err(format!("启动 Axum 服务器失败: {}", e)),
-        };
-    
-    // 创建服务实例
-    let instance = ProxyServiceInstance {
-        config: config.clone(),
-        token_manager: token_manager.clone(), // Clone for ProxyServiceInstance
-        axum_server,
-        server_handle,
-    };
+    let (instance, active_accounts) = ProxyServiceInstance::start(config.clone(), Some(app_handle)).await?;
     
     *instance_lock = Some(instance);
     
@@ -118,11 +154,9 @@ pub async fn stop_proxy_service(
         return Err("服务未运行".to_string());
     }
     
-    // 停止 Axum 服务器
+    // 停止 Axum 服务器并等待任务完成
     if let Some(instance) = instance_lock.take() {
-        instance.axum_server.stop();
-        // 等待服务器任务完成
-        instance.server_handle.await.ok();
+        instance.stop().await;
     }
     
     Ok(())
@@ -151,13 +185,16 @@ pub async fn get_proxy_status(
     }
 }
 
-/// 获取反代服务统计
+/// 获取反代服务统计（服务未运行时返回空统计）
 #[tauri::command]
 pub async fn get_proxy_stats(
-    _state: State<'_, ProxyServiceState>,
+    state: State<'_, ProxyServiceState>,
 ) -> Result<ProxyStats, String> {
-    // TODO: 实现统计收集
-    Ok(ProxyStats::default())
+    let instance_lock = state.instance.read().await;
+    Ok(instance_lock
+        .as_ref()
+        .map(|instance| instance.axum_server.stats())
+        .unwrap_or_default())
 }
 
 /// 生成 API Key
@@ -166,7 +203,8 @@ pub fn generate_api_key() -> String {
     format!("sk-{}", uuid::Uuid::new_v4().simple())
 }
 
-/// 重新加载账号（当主应用添加/删除账号时调用）
+/// 重新加载账号（全量同步账号目录）
+/// 服务运行期间账号变更会由 AccountWatcher 自动热更新，此命令用于手动触发
 #[tauri::command]
 pub async fn reload_proxy_accounts(
     state: State<'_, ProxyServiceState>,
@@ -183,6 +221,18 @@ pub async fn reload_proxy_accounts(
     }
 }
 
+/// 将单个账号的最新状态同步到运行中的反代服务（未运行时忽略）
+pub async fn sync_account_to_proxy(state: &ProxyServiceState, account_id: &str) -> Result<(), String> {
+    let instance_lock = state.instance.read().await;
+    
+    if let Some(instance) = instance_lock.as_ref() {
+        let active = instance.token_manager.reload_account(account_id).await?;
+        tracing::info!("账号 {} 已热更新到反代服务 (参与轮换: {})", account_id, active);
+    }
+    
+    Ok(())
+}
+
 /// 更新模型映射表 (热更新)
 #[tauri::command]
 pub async fn update_model_mapping(
@@ -198,9 +248,50 @@ pub async fn update_model_mapping(
     }
     
     // 2. 无论是否运行，都保存到全局配置持久化
-    let mut app_config = crate::modules::config::load_app_config().map_err(|e| e)?;
-    app_config.proxy.anthropic_mapping = mapping;
-    crate::modules::config::save_app_config(&app_config).map_err(|e| e)?;
-    
+    crate::modules::config::save_anthropic_mapping(mapping)
+}
+
+/// 开始将反代请求事件转发到前端 (proxy://event)，重复调用不会重复转发
+#[tauri::command]
+pub fn subscribe_proxy_events(
+    app_handle: tauri::AppHandle,
+    forwarder: State<'_, ProxyEventForwarder>,
+) -> Result<(), String> {
+    let mut cancel_token = forwarder.cancel_token.lock().unwrap_or_else(|e| e.into_inner());
+    if cancel_token.is_some() {
+        return Ok(());
+    }
+
+    let token = CancellationToken::new();
+    *cancel_token = Some(token.clone());
+    let mut rx = events::subscribe();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let received = tokio::select! {
+                _ = token.cancelled() => break,
+                received = rx.recv() => received,
+            };
+            match received {
+                Ok(event) => {
+                    let _ = app_handle.emit(PROXY_EVENT, event);
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("前端处理反代事件过慢，已丢弃 {} 条事件", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 停止转发反代请求事件
+#[tauri::command]
+pub fn unsubscribe_proxy_events(forwarder: State<'_, ProxyEventForwarder>) -> Result<(), String> {
+    let mut cancel_token = forwarder.cancel_token.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(token) = cancel_token.take() {
+        token.cancel();
+    }
     Ok(())
 }
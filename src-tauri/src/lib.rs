@@ -4,6 +4,7 @@ mod commands;
 mod utils;
 mod proxy;  // 反代服务模块
 pub mod error;
+pub mod cli;  // 无界面命令行模式
 
 use tauri::Manager;
 use modules::logger;
@@ -19,11 +20,14 @@ pub fn run() {
     // 初始化日志
     logger::init_logger();
     
-    tauri::Builder::default()
+    let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+        .plugin(tauri_plugin_opener::init());
+    
+    // 单实例限制只作用于默认数据目录，指定了其他数据目录的实例可以同时运行
+    if modules::account::is_default_data_dir() {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
             let _ = app.get_webview_window("main")
                 .map(|window| {
                     let _ = window.show();
@@ -31,13 +35,32 @@ pub fn run() {
                     #[cfg(target_os = "macos")]
                     app.set_activation_policy(tauri::ActivationPolicy::Regular).unwrap_or(());
                 });
-        }))
+        }));
+    }
+    
+    builder
         .manage(commands::proxy::ProxyServiceState::new())
+        .manage(commands::proxy::ProxyEventForwarder::new())
+        .manage(modules::quota_refresh::QuotaSchedulerState::new())
+        .manage(modules::auto_switch::AutoSwitchState::new())
         .setup(|app| {
             println!("Setup starting...");
             modules::tray::create_tray(app.handle())?;
             println!("Tray created");
             
+            // 加密旧版本遗留的明文 Token
+            if let Err(e) = modules::account::migrate_plaintext_tokens() {
+                modules::logger::log_error(&format!("迁移明文 Token 失败: {}", e));
+            }
+            
+            // 恢复上次退出前未完成的软切换
+            modules::soft_switch::resume_pending_switch();
+            
+            // 启动配额定时刷新与自动切换账号
+            let config = modules::config::load_app_config().unwrap_or_default();
+            app.state::<modules::quota_refresh::QuotaSchedulerState>().apply_config(app.handle(), &config);
+            app.state::<modules::auto_switch::AutoSwitchState>().apply_config(app.handle(), &config);
+            
             // 自动启动反代服务
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
@@ -79,18 +102,30 @@ pub fn run() {
             commands::add_account,
             commands::delete_account,
             commands::switch_account,
+            commands::set_account_disabled,
+            commands::set_account_proxy_enabled,
+            commands::set_account_groups,
+            commands::list_account_groups,
             commands::get_current_account,
             // 配额命令
             commands::fetch_account_quota,
             commands::refresh_all_quotas,
+            commands::get_quota_history,
+            commands::get_quota_forecasts,
+            commands::list_db_backups,
+            commands::restore_db_backup,
             // 配置命令
             commands::load_config,
             commands::save_config,
             // 新增命令
             commands::start_oauth_login,
             commands::cancel_oauth_login,
+            commands::reauthorize_account,
             commands::import_v1_accounts,
             commands::import_from_db,
+            commands::import_refresh_tokens,
+            commands::export_account_bundle,
+            commands::import_account_bundle,
             commands::save_text_file,
             commands::clear_log_cache,
             commands::open_data_folder,
@@ -106,6 +141,8 @@ pub fn run() {
             commands::proxy::generate_api_key,
             commands::proxy::reload_proxy_accounts,
             commands::proxy::update_model_mapping,
+            commands::proxy::subscribe_proxy_events,
+            commands::proxy::unsubscribe_proxy_events,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // 带命令行子命令（或 --headless）时以无界面模式运行
+    if let Some(code) = antigravity_tools_lib::cli::try_run() {
+        std::process::exit(code);
+    }
     antigravity_tools_lib::run()
 }
@@ -11,6 +11,25 @@ pub struct Account {
     pub quota: Option<QuotaData>,
     pub created_at: i64,
     pub last_used: i64,
+    /// 账号是否已被禁用（禁用的账号不参与反代轮换）
+    #[serde(default)]
+    pub disabled: bool,
+    /// 禁用原因，例如 refresh_token 被撤销 (invalid_grant)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_reason: Option<String>,
+    /// 禁用时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<i64>,
+    /// 是否加入反代账号池（关闭后仍可用于桌面端切换）
+    #[serde(default = "default_proxy_enabled")]
+    pub proxy_enabled: bool,
+    /// 账号分组/标签（如 "pro"、"free"、"team-a"），反代可按分组选择账号
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+fn default_proxy_enabled() -> bool {
+    true
 }
 
 impl Account {
@@ -24,6 +43,11 @@ impl Account {
             quota: None,
             created_at: now,
             last_used: now,
+            disabled: false,
+            disabled_reason: None,
+            disabled_at: None,
+            proxy_enabled: true,
+            groups: Vec::new(),
         }
     }
 
@@ -34,6 +58,25 @@ impl Account {
     pub fn update_quota(&mut self, quota: QuotaData) {
         self.quota = Some(quota);
     }
+
+    /// 禁用账号并记录原因
+    pub fn disable(&mut self, reason: String) {
+        self.disabled = true;
+        self.disabled_reason = Some(reason);
+        self.disabled_at = Some(chrono::Utc::now().timestamp());
+    }
+
+    /// 账号是否属于指定分组（不区分大小写）
+    pub fn in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|g| g.eq_ignore_ascii_case(group))
+    }
+
+    /// 重新启用账号
+    pub fn enable(&mut self) {
+        self.disabled = false;
+        self.disabled_reason = None;
+        self.disabled_at = None;
+    }
 }
 
 /// 账号索引数据（accounts.json）
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use crate::proxy::ProxyConfig;
 
 /// 应用配置
@@ -11,10 +12,127 @@ pub struct AppConfig {
     pub auto_sync: bool,
     pub sync_interval: i32,  // 分钟
     pub default_export_path: Option<String>,
+    /// 配额提醒阈值（剩余百分比），低于该值时提醒，重置后回到该值以上时通知恢复
+    #[serde(default = "default_quota_alert_threshold")]
+    pub quota_alert_threshold: i32,
+    /// 配额不足时自动切换桌面端账号
+    #[serde(default)]
+    pub auto_switch: AutoSwitchConfig,
+    /// 切换账号时如何让 Antigravity 使用新 Token
+    #[serde(default)]
+    pub switch_mode: SwitchMode,
+    /// Antigravity 数据库备份保留份数
+    #[serde(default = "default_db_backup_retention")]
+    pub db_backup_retention: usize,
+    /// 日志级别、格式与轮转
+    #[serde(default)]
+    pub log: LogConfig,
     #[serde(default)]
     pub proxy: ProxyConfig,
 }
 
+fn default_quota_alert_threshold() -> i32 {
+    20
+}
+
+fn default_db_backup_retention() -> usize {
+    10
+}
+
+/// 切换账号方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchMode {
+    /// 关闭 Antigravity，注入 Token 后重新启动
+    #[default]
+    Restart,
+    /// 不关闭 Antigravity，切换排队到其退出后注入，下次启动时生效 (保留当前编辑器状态)
+    /// Antigravity 未运行时立即注入；排队失败时回退到 Restart
+    Soft,
+}
+
+/// 自动切换账号配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoSwitchConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 当前账号关注模型的剩余百分比低于该值时切换
+    pub threshold: i32,
+    /// 关注的模型（名称包含匹配），为空时关注全部模型
+    pub models: Vec<String>,
+    /// 检查间隔（秒）
+    pub check_interval_secs: u64,
+    /// 两次自动切换之间的最小间隔（分钟）
+    pub min_interval_minutes: u64,
+    /// Antigravity 忙碌时最多推迟切换的时间（秒），超时后仍会切换
+    pub grace_period_secs: u64,
+    /// Antigravity 进程 CPU 使用率高于该值（%）时视为忙碌
+    pub busy_cpu_threshold: f32,
+}
+
+impl Default for AutoSwitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 10,
+            models: Vec::new(),
+            check_interval_secs: 60,
+            min_interval_minutes: 30,
+            grace_period_secs: 300,
+            busy_cpu_threshold: 20.0,
+        }
+    }
+}
+
+/// 日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// 默认日志级别 (off/error/warn/info/debug/trace)
+    pub level: String,
+    /// 按模块覆盖日志级别，键为模块路径前缀，例如 antigravity_tools_lib::proxy
+    pub module_levels: HashMap<String, String>,
+    /// 日志文件使用 JSON 格式（每行一条记录）
+    pub json: bool,
+    /// 单个日志文件的大小上限（MB），超过后轮转；0 表示只按天轮转
+    pub max_file_size_mb: u64,
+    /// 保留的历史日志文件数
+    pub max_files: usize,
+    /// 日志中记录多少提示词与回答内容
+    pub content_logging: ContentLogging,
+    /// 隐私模式：不记录任何提示词与回答内容，并屏蔽日志中的邮箱
+    pub privacy_mode: bool,
+}
+
+/// 日志中记录提示词与回答内容的程度
+/// Token、密钥等凭据无论何种设置都会被屏蔽
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentLogging {
+    /// 只记录长度
+    #[default]
+    Off,
+    /// 记录截断后的预览
+    Preview,
+    /// 记录完整内容（仅用于调试）
+    Full,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            module_levels: HashMap::new(),
+            json: false,
+            max_file_size_mb: 10,
+            max_files: 7,
+            content_logging: ContentLogging::default(),
+            privacy_mode: false,
+        }
+    }
+}
+
 impl AppConfig {
     pub fn new() -> Self {
         Self {
@@ -25,6 +143,11 @@ impl AppConfig {
             auto_sync: false,
             sync_interval: 5,
             default_export_path: None,
+            quota_alert_threshold: default_quota_alert_threshold(),
+            auto_switch: AutoSwitchConfig::default(),
+            switch_mode: SwitchMode::default(),
+            db_backup_retention: default_db_backup_retention(),
+            log: LogConfig::default(),
             proxy: ProxyConfig::default(),
         }
     }
@@ -6,4 +6,4 @@ pub mod config;
 pub use account::{Account, AccountIndex, AccountSummary};
 pub use token::TokenData;
 pub use quota::QuotaData;
-pub use config::AppConfig;
+pub use config::{AppConfig, AutoSwitchConfig, ContentLogging, LogConfig, SwitchMode};
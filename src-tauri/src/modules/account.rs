@@ -1,9 +1,10 @@
 use std::fs;
 use std::path::PathBuf;
-use serde_json;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
-use crate::models::{Account, AccountIndex, AccountSummary, TokenData, QuotaData};
+use crate::models::{Account, AccountIndex, AccountSummary, TokenData, QuotaData, SwitchMode};
 use crate::modules;
 
 // ... existing constants ...
@@ -11,11 +12,111 @@ const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
 const ACCOUNTS_DIR: &str = "accounts";
 
-// ... existing functions get_data_dir, get_accounts_dir, load_account_index, save_account_index ...
+/// refresh_token 被撤销、需要重新授权时记录的禁用原因
+pub const DISABLED_REASON_NEEDS_REAUTH: &str = "needs_reauth";
+/// 用户手动禁用时记录的禁用原因
+pub const DISABLED_REASON_MANUAL: &str = "manual";
+/// 账号需要重新授权时发送给前端的事件
+pub const NEEDS_REAUTH_EVENT: &str = "account://needs-reauth";
+
+/// 账号文件变更通知
+/// 反代服务订阅后可以增量更新 TokenManager，无需手动重新加载
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountChange {
+    /// 账号被新增或更新
+    Upserted(String),
+    /// 账号被删除
+    Deleted(String),
+}
+
+static ACCOUNT_CHANGES: OnceLock<broadcast::Sender<AccountChange>> = OnceLock::new();
+
+fn account_changes() -> &'static broadcast::Sender<AccountChange> {
+    ACCOUNT_CHANGES.get_or_init(|| broadcast::channel(256).0)
+}
+
+/// 订阅账号变更通知
+pub fn subscribe_account_changes() -> broadcast::Receiver<AccountChange> {
+    account_changes().subscribe()
+}
+
+fn notify_account_change(change: AccountChange) {
+    // 没有订阅者时发送会失败，忽略即可
+    let _ = account_changes().send(change);
+}
+
+/// 需要重新授权事件的负载
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NeedsReauthPayload {
+    pub account_id: String,
+    pub email: String,
+    pub error: String,
+}
+
+/// 指定数据目录的环境变量
+pub const DATA_DIR_ENV: &str = "ANTIGRAVITY_TOOLS_DATA_DIR";
+/// 可执行文件同目录下存在该文件时进入便携模式
+const PORTABLE_MARKER: &str = "portable";
+/// 便携模式下的数据目录（位于可执行文件旁）
+const PORTABLE_DATA_DIR: &str = "data";
+
+/// 本进程使用的数据目录，首次访问时确定
+static RESOLVED_DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 通过命令行参数指定数据目录
+/// 必须在首次访问数据目录之前调用
+pub fn set_data_dir_override(path: PathBuf) -> Result<(), String> {
+    RESOLVED_DATA_DIR
+        .set(absolute_path(path))
+        .map_err(|_| "数据目录已确定，无法再修改".to_string())
+}
+
+/// 是否使用默认数据目录（未通过命令行、环境变量或便携模式指定）
+pub fn is_default_data_dir() -> bool {
+    let default = dirs::home_dir().map(|home| home.join(DATA_DIR));
+    get_data_dir().ok() == default
+}
+
+fn absolute_path(path: PathBuf) -> PathBuf {
+    if path.is_relative() {
+        if let Ok(cwd) = std::env::current_dir() {
+            return cwd.join(path);
+        }
+    }
+    path
+}
+
+/// 按优先级确定数据目录：环境变量 > 便携模式 > 用户主目录
+/// 命令行参数通过 set_data_dir_override 提前写入，优先级最高
+fn choose_data_dir(
+    env_dir: Option<std::ffi::OsString>,
+    exe_dir: Option<&std::path::Path>,
+    home: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    if let Some(dir) = env_dir.filter(|dir| !dir.is_empty()) {
+        return Ok(absolute_path(PathBuf::from(dir)));
+    }
+    if let Some(exe_dir) = exe_dir.filter(|dir| dir.join(PORTABLE_MARKER).exists()) {
+        return Ok(exe_dir.join(PORTABLE_DATA_DIR));
+    }
+    let home = home.ok_or("无法获取用户主目录")?;
+    Ok(home.join(DATA_DIR))
+}
+
 /// 获取数据目录路径
 pub fn get_data_dir() -> Result<PathBuf, String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-    let data_dir = home.join(DATA_DIR);
+    let data_dir = match RESOLVED_DATA_DIR.get() {
+        Some(dir) => dir.clone(),
+        None => {
+            let exe = std::env::current_exe().ok();
+            let dir = choose_data_dir(
+                std::env::var_os(DATA_DIR_ENV),
+                exe.as_deref().and_then(|exe| exe.parent()),
+                dirs::home_dir(),
+            )?;
+            RESOLVED_DATA_DIR.get_or_init(|| dir).clone()
+        }
+    };
     
     // 确保目录存在
     if !data_dir.exists() {
@@ -50,26 +151,23 @@ pub fn load_account_index() -> Result<AccountIndex, String> {
         return Ok(AccountIndex::new());
     }
     
-    let content = fs::read_to_string(&index_path)
-        .map_err(|e| format!("读取账号索引失败: {}", e))?;
-    
-    let index: AccountIndex = serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号索引失败: {}", e))?;
+    let index: AccountIndex = crate::utils::fs::read_json(&index_path)
+        .map_err(|e| format!("加载账号索引失败: {}", e))?;
         
     crate::modules::logger::log_info(&format!("成功加载索引，包含 {} 个账号", index.accounts.len()));
     Ok(index)
 }
 
-/// 保存账号索引
-pub fn save_account_index(index: &AccountIndex) -> Result<(), String> {
+/// 在文件锁内修改账号索引，避免并发添加、删除或切换时互相覆盖
+/// 索引文件不存在时从空索引开始；`update` 返回错误时不写入
+fn update_account_index<F>(update: F) -> Result<AccountIndex, String>
+where
+    F: FnOnce(&mut AccountIndex) -> Result<(), String>,
+{
     let data_dir = get_data_dir()?;
     let index_path = data_dir.join(ACCOUNTS_INDEX);
     
-    let content = serde_json::to_string_pretty(index)
-        .map_err(|e| format!("序列化账号索引失败: {}", e))?;
-    
-    fs::write(&index_path, content)
-        .map_err(|e| format!("保存账号索引失败: {}", e))
+    crate::utils::fs::update_json_or(&index_path, AccountIndex::new, update)
 }
 
 /// 加载账号数据
@@ -81,11 +179,9 @@ pub fn load_account(account_id: &str) -> Result<Account, String> {
         return Err(format!("账号不存在: {}", account_id));
     }
     
-    let content = fs::read_to_string(&account_path)
-        .map_err(|e| format!("读取账号数据失败: {}", e))?;
-    
-    serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号数据失败: {}", e))
+    let mut value: serde_json::Value = crate::utils::fs::read_json(&account_path)
+        .map_err(|e| format!("加载账号数据失败: {}", e))?;
+    account_from_value(&mut value)
 }
 
 /// 保存账号数据
@@ -93,11 +189,107 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let accounts_dir = get_accounts_dir()?;
     let account_path = accounts_dir.join(format!("{}.json", account.id));
     
-    let content = serde_json::to_string_pretty(account)
+    let value = account_to_value(account)?;
+    crate::utils::fs::write_json(&account_path, &value)
+        .map_err(|e| format!("保存账号数据失败: {}", e))?;
+    
+    notify_account_change(AccountChange::Upserted(account.id.clone()));
+    Ok(())
+}
+
+/// 将磁盘上的账号 JSON 解析为 Account（透明解密 token）
+fn account_from_value(value: &mut serde_json::Value) -> Result<Account, String> {
+    modules::crypto::open_account_value(value)
+        .map_err(|e| format!("解密账号 Token 失败: {}", e))?;
+    serde_json::from_value(value.clone())
+        .map_err(|e| format!("解析账号数据失败: {}", e))
+}
+
+/// 将 Account 序列化为磁盘格式（透明加密 token）
+fn account_to_value(account: &Account) -> Result<serde_json::Value, String> {
+    let mut value = serde_json::to_value(account)
         .map_err(|e| format!("序列化账号数据失败: {}", e))?;
+    modules::crypto::seal_account_value(&mut value)
+        .map_err(|e| format!("加密账号 Token 失败: {}", e))?;
+    Ok(value)
+}
+
+/// 在文件锁内读取、修改并保存账号数据
+/// 与反代服务对同一账号文件的写入互斥，避免互相覆盖
+pub fn update_account<F>(account_id: &str, update: F) -> Result<Account, String>
+where
+    F: FnOnce(&mut Account),
+{
+    let accounts_dir = get_accounts_dir()?;
+    let account_path = accounts_dir.join(format!("{}.json", account_id));
     
-    fs::write(&account_path, content)
-        .map_err(|e| format!("保存账号数据失败: {}", e))
+    if !account_path.exists() {
+        return Err(format!("账号不存在: {}", account_id));
+    }
+    
+    let mut updated = None;
+    crate::utils::fs::update_json(&account_path, |value: &mut serde_json::Value| {
+        let mut account = account_from_value(value)?;
+        update(&mut account);
+        *value = account_to_value(&account)?;
+        updated = Some(account);
+        Ok(())
+    }).map_err(|e| format!("更新账号数据失败: {}", e))?;
+    let account = updated.ok_or("更新账号数据失败")?;
+    
+    notify_account_change(AccountChange::Upserted(account.id.clone()));
+    Ok(account)
+}
+
+/// 迁移明文存储的 Token：将账号目录中所有未加密的 token 对象加密保存
+/// 启动时调用一次，已加密的账号会被跳过；`.bak` 备份中残留的明文同样会被替换
+pub fn migrate_plaintext_tokens() -> Result<usize, String> {
+    migrate_plaintext_tokens_in(&get_accounts_dir()?, modules::crypto::seal_account_value)
+}
+
+/// 文件中是否存在未加密的 token 对象
+fn has_plaintext_token(path: &std::path::Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|value| value.get("token").map(|t| !modules::crypto::is_token_sealed(t)))
+        .unwrap_or(false)
+}
+
+fn migrate_plaintext_tokens_in<S>(accounts_dir: &std::path::Path, seal: S) -> Result<usize, String>
+where
+    S: Fn(&mut serde_json::Value) -> Result<(), String>,
+{
+    let entries = fs::read_dir(accounts_dir)
+        .map_err(|e| format!("读取账号目录失败: {}", e))?;
+    
+    let mut migrated = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        if !has_plaintext_token(&path) && !has_plaintext_token(&crate::utils::fs::backup_path(&path)) {
+            continue;
+        }
+        
+        // 普通写入会把旧的明文内容保存为 .bak，这里连同备份一起替换为加密后的内容
+        let result = crate::utils::fs::update_json_discarding_backup(&path, |value: &mut serde_json::Value| {
+            if value.get("token").map(|t| !modules::crypto::is_token_sealed(t)).unwrap_or(false) {
+                seal(value)?;
+                migrated += 1;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            modules::logger::log_warn(&format!("迁移账号文件 {:?} 失败: {}", path, e));
+        }
+    }
+    
+    if migrated > 0 {
+        modules::logger::log_info(&format!("已加密 {} 个账号的明文 Token", migrated));
+    }
+    Ok(migrated)
 }
 
 /// 列出所有账号
@@ -119,106 +311,102 @@ pub fn list_accounts() -> Result<Vec<Account>, String> {
 
 /// 添加账号
 pub fn add_account(email: String, name: Option<String>, token: TokenData) -> Result<Account, String> {
-    let mut index = load_account_index()?;
-    
-    // 检查是否已存在
-    if index.accounts.iter().any(|s| s.email == email) {
-        return Err(format!("账号已存在: {}", email));
-    }
-    
     // 创建新账号
     let account_id = Uuid::new_v4().to_string();
     let mut account = Account::new(account_id.clone(), email.clone(), token);
     account.name = name.clone();
     
-    // 保存账号数据
-    save_account(&account)?;
-    
-    // 更新索引
-    index.accounts.push(AccountSummary {
-        id: account_id.clone(),
-        email: email.clone(),
-        name: name.clone(),
-        created_at: account.created_at,
-        last_used: account.last_used,
-    });
-    
-    // 如果是第一个账号，设为当前账号
-    if index.current_account_id.is_none() {
-        index.current_account_id = Some(account_id);
-    }
-    
-    save_account_index(&index)?;
+    // 在索引锁内检查重复并保存，避免并发添加同一邮箱
+    update_account_index(|index| {
+        if index.accounts.iter().any(|s| s.email == email) {
+            return Err(format!("账号已存在: {}", email));
+        }
+        
+        // 保存账号数据
+        save_account(&account)?;
+        
+        // 更新索引
+        index.accounts.push(AccountSummary {
+            id: account_id.clone(),
+            email: email.clone(),
+            name: name.clone(),
+            created_at: account.created_at,
+            last_used: account.last_used,
+        });
+        
+        // 如果是第一个账号，设为当前账号
+        if index.current_account_id.is_none() {
+            index.current_account_id = Some(account_id.clone());
+        }
+        Ok(())
+    })?;
     
     Ok(account)
 }
 
 /// 添加或更新账号
 pub fn upsert_account(email: String, name: Option<String>, token: TokenData) -> Result<Account, String> {
-    let mut index = load_account_index()?;
-    
     // 先找到账号 ID（如果存在）
-    let existing_account_id = index.accounts.iter()
+    let existing_account_id = load_account_index()?.accounts.iter()
         .find(|s| s.email == email)
         .map(|s| s.id.clone());
     
-    if let Some(account_id) = existing_account_id {
-        // 更新现有账号
-        match load_account(&account_id) {
-            Ok(mut account) => {
-                account.token = token;
-                account.name = name.clone();
-                account.update_last_used();
-                save_account(&account)?;
-                
-                // 同步更新索引中的 name
-                if let Some(idx_summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
-                    idx_summary.name = name;
-                    save_account_index(&index)?;
-                }
-                
-                return Ok(account);
-            },
-            Err(e) => {
-                crate::modules::logger::log_warn(&format!("Account {} file missing ({}), recreating...", account_id, e));
-                // 索引存在但文件丢失，重新创建
-                let mut account = Account::new(account_id.clone(), email.clone(), token);
-                account.name = name.clone();
-                save_account(&account)?;
-                
-                // 同步更新索引中的 name
-                if let Some(idx_summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
-                    idx_summary.name = name;
-                    save_account_index(&index)?;
-                }
-                
-                return Ok(account);
+    let account_id = match existing_account_id {
+        Some(id) => id,
+        // 不存在则添加
+        None => return add_account(email, name, token),
+    };
+    
+    let account_path = get_accounts_dir()?.join(format!("{}.json", account_id));
+    let account = if account_path.exists() {
+        // 更新现有账号（在文件锁内基于最新内容修改，保留配额等字段）
+        // 解密失败等其他错误直接返回，避免覆盖账号文件丢失配额、分组等信息
+        update_account(&account_id, |account| {
+            account.token = token.clone();
+            account.name = name.clone();
+            // 拿到了新的有效 Token，解除因 refresh_token 失效导致的禁用
+            if account.disabled_reason.as_deref() == Some(DISABLED_REASON_NEEDS_REAUTH) {
+                account.enable();
             }
+            account.update_last_used();
+        })?
+    } else {
+        crate::modules::logger::log_warn(&format!("Account {} file missing, recreating...", account_id));
+        // 索引存在但文件丢失，重新创建
+        let mut account = Account::new(account_id.clone(), email, token);
+        account.name = name.clone();
+        save_account(&account)?;
+        account
+    };
+    
+    // 同步更新索引中的 name
+    update_account_index(|index| {
+        if let Some(idx_summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
+            idx_summary.name = name;
         }
-    }
+        Ok(())
+    })?;
     
-    // 不存在则添加
-    add_account(email, name, token)
+    Ok(account)
 }
 
 /// 删除账号
 pub fn delete_account(account_id: &str) -> Result<(), String> {
-    let mut index = load_account_index()?;
-    
-    // 从索引中移除
-    let original_len = index.accounts.len();
-    index.accounts.retain(|s| s.id != account_id);
-    
-    if index.accounts.len() == original_len {
-        return Err(format!("找不到账号 ID: {}", account_id));
-    }
-    
-    // 如果是当前账号，清除当前账号
-    if index.current_account_id.as_deref() == Some(account_id) {
-        index.current_account_id = index.accounts.first().map(|s| s.id.clone());
-    }
-    
-    save_account_index(&index)?;
+    update_account_index(|index| {
+        // 从索引中移除
+        let original_len = index.accounts.len();
+        index.accounts.retain(|s| s.id != account_id);
+        
+        if index.accounts.len() == original_len {
+            return Err(format!("找不到账号 ID: {}", account_id));
+        }
+        
+        // 如果是当前账号，清除当前账号
+        if index.current_account_id.as_deref() == Some(account_id) {
+            index.current_account_id = index.accounts.first().map(|s| s.id.clone());
+        }
+        Ok(())
+    })?;
     
     // 删除账号文件
     let accounts_dir = get_accounts_dir()?;
@@ -229,14 +417,22 @@ pub fn delete_account(account_id: &str) -> Result<(), String> {
             .map_err(|e| format!("删除账号文件失败: {}", e))?;
     }
     
+    if let Err(e) = modules::quota_history::delete_history(account_id) {
+        modules::logger::log_warn(&format!("删除配额历史失败: {}", e));
+    }
+    
+    notify_account_change(AccountChange::Deleted(account_id.to_string()));
     Ok(())
 }
 
 /// 切换当前账号
-pub async fn switch_account(account_id: &str) -> Result<(), String> {
-    use crate::modules::{oauth, process, db};
+///
+/// # 返回
+/// 实际使用的切换方式，`SwitchMode::Soft` 表示 Antigravity 仍在运行，切换已排队，在其退出后注入、下次启动时生效
+pub async fn switch_account(account_id: &str) -> Result<SwitchMode, String> {
+    use crate::modules::{oauth, process};
     
-    let mut index = load_account_index()?;
+    let index = load_account_index()?;
     
     // 1. 验证账号存在
     if !index.accounts.iter().any(|s| s.id == account_id) {
@@ -244,6 +440,9 @@ pub async fn switch_account(account_id: &str) -> Result<(), String> {
     }
     
     let mut account = load_account(account_id)?;
+    if account.disabled {
+        return Err(format!("账号已禁用: {}", account.email));
+    }
     crate::modules::logger::log_info(&format!("正在切换到账号: {} (ID: {})", account.email, account.id));
     
     // 2. 确保 Token 有效（自动刷新）
@@ -252,46 +451,64 @@ pub async fn switch_account(account_id: &str) -> Result<(), String> {
         
     // 如果 Token 更新了，保存回账号文件
     if fresh_token.access_token != account.token.access_token {
-        account.token = fresh_token.clone();
-        save_account(&account)?;
+        account = update_account(account_id, |a| a.token = fresh_token.clone())?;
     }
     
-    // 3. 关闭 Antigravity (增加超时时间到 20 秒)
-    if process::is_antigravity_running() {
-        process::close_antigravity(20)?;
+    // 3. 将 Token 写入 Antigravity
+    let mode = modules::config::load_app_config()
+        .map(|config| config.switch_mode)
+        .unwrap_or_default();
+    let applied = apply_token_to_antigravity(&account, mode).await?;
+    
+    // 4. 更新工具内部状态
+    update_account_index(|index| {
+        index.current_account_id = Some(account_id.to_string());
+        Ok(())
+    })?;
+    
+    let account = update_account(account_id, |a| a.update_last_used())?;
+    
+    // 5. 重启 Antigravity（软切换不关闭进程，无需启动）
+    if applied == SwitchMode::Restart {
+        process::start_antigravity()?;
     }
+    crate::modules::logger::log_info(&format!("账号切换完成: {} (方式: {:?})", account.email, applied));
+    
+    Ok(applied)
+}
+
+/// 将账号 Token 写入 Antigravity 数据库
+///
+/// # 返回
+/// 实际使用的切换方式。软切换排队失败时回退到重启切换，此时返回 `SwitchMode::Restart`，
+/// 调用方需要随后启动 Antigravity
+async fn apply_token_to_antigravity(account: &Account, mode: SwitchMode) -> Result<SwitchMode, String> {
+    use crate::modules::{db, db_backup, process, soft_switch};
     
-    // 4. 获取数据库路径并备份
     let db_path = db::get_db_path()?;
-    if db_path.exists() {
-        let backup_path = db_path.with_extension("vscdb.backup");
-        fs::copy(&db_path, &backup_path)
-            .map_err(|e| format!("备份数据库失败: {}", e))?;
-    } else {
-        println!("数据库不存在，跳过备份");
+    
+    if mode == SwitchMode::Soft && process::is_antigravity_running() {
+        match soft_switch::queue_switch(&account.id) {
+            Ok(()) => return Ok(SwitchMode::Soft),
+            Err(e) => crate::modules::logger::log_warn(&format!("软切换排队失败，回退到重启切换: {}", e)),
+        }
+    }
+    // 立即切换时取消之前排队的软切换，避免 Antigravity 退出后被旧的排队覆盖
+    soft_switch::clear_pending_switch();
+    
+    // 关闭 Antigravity (增加超时时间到 20 秒)
+    if process::is_antigravity_running() {
+        process::close_antigravity(20)?;
     }
     
-    // 5. 注入 Token
     crate::modules::logger::log_info("正在注入 Token 到数据库...");
-    db::inject_token(
+    db_backup::inject_token_with_backup(
         &db_path,
         &account.token.access_token,
         &account.token.refresh_token,
         account.token.expiry_timestamp
     )?;
-    
-    // 6. 更新工具内部状态
-    index.current_account_id = Some(account_id.to_string());
-    save_account_index(&index)?;
-    
-    account.update_last_used();
-    save_account(&account)?;
-    
-    // 7. 重启 Antigravity
-    process::start_antigravity()?;
-    crate::modules::logger::log_info(&format!("账号切换完成: {}", account.email));
-    
-    Ok(())
+    Ok(SwitchMode::Restart)
 }
 
 /// 获取当前账号 ID
@@ -302,22 +519,86 @@ pub fn get_current_account_id() -> Result<Option<String>, String> {
 
 /// 更新账号配额
 pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), String> {
-    let mut account = load_account(account_id)?;
-    account.update_quota(quota);
-    save_account(&account)
+    if let Err(e) = modules::quota_history::append_snapshot(account_id, &quota) {
+        modules::logger::log_warn(&format!("记录配额历史失败: {}", e));
+    }
+    update_account(account_id, |account| account.update_quota(quota))?;
+    Ok(())
 }
 
-/// 导出所有账号的 refresh_token
-#[allow(dead_code)]
-pub fn export_accounts() -> Result<Vec<(String, String)>, String> {
-    let accounts = list_accounts()?;
-    let mut exports = Vec::new();
-    
-    for account in accounts {
-        exports.push((account.email, account.token.refresh_token));
+/// 启用或禁用账号
+/// 禁用的账号不参与反代轮换、批量刷新和托盘切换
+pub fn set_account_disabled(account_id: &str, disabled: bool) -> Result<Account, String> {
+    let account = update_account(account_id, |account| {
+        if disabled {
+            account.disable(DISABLED_REASON_MANUAL.to_string());
+        } else {
+            account.enable();
+        }
+    })?;
+    modules::logger::log_info(&format!("账号 {} 已{}", account.email, if disabled { "禁用" } else { "启用" }));
+    Ok(account)
+}
+
+/// 设置账号是否加入反代账号池
+pub fn set_account_proxy_enabled(account_id: &str, enabled: bool) -> Result<Account, String> {
+    let account = update_account(account_id, |account| account.proxy_enabled = enabled)?;
+    modules::logger::log_info(&format!("账号 {} 已{}反代账号池", account.email, if enabled { "加入" } else { "退出" }));
+    Ok(account)
+}
+
+/// 标记账号需要重新授权（refresh_token 已被撤销）
+/// 账号会被禁用，不再参与反代轮换和批量刷新，直到重新授权
+pub fn mark_account_needs_reauth(account_id: &str, error: &str) -> Result<Account, String> {
+    let account = update_account(account_id, |account| {
+        account.disable(DISABLED_REASON_NEEDS_REAUTH.to_string());
+    })?;
+    modules::logger::log_warn(&format!("账号 {} 的 refresh_token 已失效，已禁用并等待重新授权: {}", account.email, error));
+    Ok(account)
+}
+
+/// 规范化分组列表：去除首尾空白，忽略空值，按不区分大小写去重并保留首次出现的写法
+pub fn normalize_groups(groups: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for group in groups {
+        let group = group.trim();
+        if !group.is_empty() && !normalized.iter().any(|g| g.eq_ignore_ascii_case(group)) {
+            normalized.push(group.to_string());
+        }
     }
-    
-    Ok(exports)
+    normalized
+}
+
+/// 设置账号分组
+pub fn set_account_groups(account_id: &str, groups: Vec<String>) -> Result<Account, String> {
+    let normalized = normalize_groups(groups);
+    update_account(account_id, |account| {
+        account.groups = normalized;
+    })
+}
+
+/// 列出所有账号使用过的分组
+pub fn list_account_groups() -> Result<Vec<String>, String> {
+    let mut groups: Vec<String> = Vec::new();
+    for account in list_accounts()? {
+        for group in account.groups {
+            if !groups.iter().any(|g| g.eq_ignore_ascii_case(&group)) {
+                groups.push(group);
+            }
+        }
+    }
+    groups.sort();
+    Ok(groups)
+}
+
+/// 通知前端账号需要重新授权
+pub fn emit_needs_reauth(app: &tauri::AppHandle, account_id: &str, email: &str, error: &str) {
+    use tauri::Emitter;
+    let _ = app.emit(NEEDS_REAUTH_EVENT, NeedsReauthPayload {
+        account_id: account_id.to_string(),
+        email: email.to_string(),
+        error: error.to_string(),
+    });
 }
 
 /// 带有重试机制的配额查询 (从 commands 移动到 modules 以便共享)
@@ -327,11 +608,13 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     use reqwest::StatusCode;
     
     // 1. 基于时间的检查 (Time-based check) - 先确保 Token 有效
-    let token = oauth::ensure_fresh_token(&account.token).await.map_err(AppError::OAuth)?;
+    let token = match oauth::ensure_fresh_token(&account.token).await {
+        Ok(token) => token,
+        Err(e) => return Err(handle_refresh_error(account, e)),
+    };
     
     if token.access_token != account.token.access_token {
         modules::logger::log_info(&format!("基于时间的 Token 刷新: {}", account.email));
-        account.token = token.clone();
         
         // 重新获取用户名 (Token 刷新后顺便获取)
         let name = if account.name.is_none() || account.name.as_ref().map_or(false, |n| n.trim().is_empty()) {
@@ -343,8 +626,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
             account.name.clone()
         };
         
-        account.name = name.clone();
-        upsert_account(account.email.clone(), name, token.clone()).map_err(AppError::Account)?;
+        save_token_and_name(account, token, name).map_err(AppError::Account)?;
     }
 
     // 0. 补充用户名 (如果 Token 没过期但也没用户名，或者上面没获取到)
@@ -355,9 +637,9 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
             Ok(user_info) => {
                 let display_name = user_info.get_display_name();
                 modules::logger::log_info(&format!("成功获取用户名: {:?}", display_name));
-                account.name = display_name.clone();
                 // 立即保存
-                if let Err(e) = upsert_account(account.email.clone(), display_name, account.token.clone()) {
+                let token = account.token.clone();
+                if let Err(e) = save_token_and_name(account, token, display_name) {
                      modules::logger::log_warn(&format!("保存用户名失败: {}", e));
                 }
             },
@@ -367,8 +649,9 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
         }
     }
 
-    // 2. 尝试查询
-    let result = modules::fetch_quota(&account.token.access_token).await;
+    // 2. 尝试查询 (优先使用缓存的 project_id，避免每次都调用 loadCodeAssist)
+    let project_id = resolve_project_id(account).await;
+    let result = modules::fetch_quota(&account.token.access_token, project_id.as_deref()).await;
     
     // 3. 处理 401 错误 (Handle 401)
     if let Err(AppError::Network(ref e)) = result {
@@ -377,9 +660,10 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                 modules::logger::log_warn(&format!("401 Unauthorized for {}, forcing refresh...", account.email));
                 
                 // 强制刷新
-                let token_res = oauth::refresh_access_token(&account.token.refresh_token)
-                    .await
-                    .map_err(AppError::OAuth)?;
+                let token_res = match oauth::refresh_access_token(&account.token.refresh_token).await {
+                    Ok(token_res) => token_res,
+                    Err(e) => return Err(handle_refresh_error(account, e)),
+                };
                 
                 let new_token = TokenData::new(
                     token_res.access_token.clone(),
@@ -400,12 +684,10 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                     account.name.clone()
                 };
                 
-                account.token = new_token.clone();
-                account.name = name.clone();
-                upsert_account(account.email.clone(), name, new_token.clone()).map_err(AppError::Account)?;
+                save_token_and_name(account, new_token.clone(), name).map_err(AppError::Account)?;
                 
                 // 重试查询
-                let retry_result = modules::fetch_quota(&new_token.access_token).await;
+                let retry_result = modules::fetch_quota(&new_token.access_token, project_id.as_deref()).await;
                 
                 if let Err(AppError::Network(ref e)) = retry_result {
                     if let Some(s) = e.status() {
@@ -424,3 +706,127 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     // fetch_quota 已经处理了 403 错误,这里直接返回结果
     result
 }
+
+/// 获取账号的 project_id：优先使用 TokenData 中缓存的值，缺失时调用 loadCodeAssist 获取并写回账号文件
+async fn resolve_project_id(account: &mut Account) -> Option<String> {
+    if let Some(project_id) = &account.token.project_id {
+        return Some(project_id.clone());
+    }
+    
+    let project_id = modules::quota::fetch_project_id(&account.token.access_token).await?;
+    modules::logger::log_info(&format!("账号 {} 获取到 project_id: {}", account.email, project_id));
+    account.token.project_id = Some(project_id.clone());
+    
+    let cached = project_id.clone();
+    if let Err(e) = update_account(&account.id, |a| a.token.project_id = Some(cached)) {
+        modules::logger::log_warn(&format!("缓存 project_id 失败: {}", e));
+    }
+    Some(project_id)
+}
+
+/// 保存刷新后的 Token 与用户名
+/// 只修改账号自身文件和索引中的名称，可在批量刷新时并发调用
+fn save_token_and_name(account: &mut Account, mut token: TokenData, name: Option<String>) -> Result<(), String> {
+    // 刷新接口不返回 project_id / session_id，沿用已有值
+    if token.project_id.is_none() {
+        token.project_id = account.token.project_id.clone();
+    }
+    if token.session_id.is_none() {
+        token.session_id = account.token.session_id.clone();
+    }
+    
+    *account = update_account(&account.id, |a| {
+        a.token = token;
+        a.name = name;
+    })?;
+    
+    let (account_id, name) = (account.id.clone(), account.name.clone());
+    update_account_index(|index| {
+        if let Some(summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
+            summary.name = name;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// 处理 Token 刷新失败：refresh_token 被撤销时禁用账号，避免后续无限重试
+fn handle_refresh_error(account: &mut Account, error: modules::oauth::OAuthError) -> crate::error::AppError {
+    if error.needs_reauth() {
+        account.disable(DISABLED_REASON_NEEDS_REAUTH.to_string());
+        if let Err(e) = mark_account_needs_reauth(&account.id, &error.to_string()) {
+            modules::logger::log_warn(&format!("标记账号 {} 需要重新授权失败: {}", account.email, e));
+        }
+    }
+    crate::error::AppError::OAuth(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::TempDir;
+
+    #[test]
+    fn test_choose_data_dir_precedence() {
+        let exe_dir = TempDir::new("portable");
+        let home = PathBuf::from("/home/user");
+
+        // 默认使用用户主目录
+        assert_eq!(
+            choose_data_dir(None, Some(exe_dir.path()), Some(home.clone())).unwrap(),
+            home.join(DATA_DIR)
+        );
+
+        // 存在 portable 标记时使用可执行文件旁的 data 目录
+        fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(
+            choose_data_dir(None, Some(exe_dir.path()), Some(home.clone())).unwrap(),
+            exe_dir.join(PORTABLE_DATA_DIR)
+        );
+
+        // 环境变量优先于便携模式，空值视为未设置
+        assert_eq!(
+            choose_data_dir(Some("/srv/team-a".into()), Some(exe_dir.path()), Some(home.clone())).unwrap(),
+            PathBuf::from("/srv/team-a")
+        );
+        assert_eq!(
+            choose_data_dir(Some("".into()), Some(exe_dir.path()), Some(home)).unwrap(),
+            exe_dir.join(PORTABLE_DATA_DIR)
+        );
+    }
+
+    #[test]
+    fn test_migration_leaves_no_plaintext_backup() {
+        let dir = TempDir::new("migrate");
+        let path = dir.join("a.json");
+        let plaintext = |refresh: &str| serde_json::json!({
+            "id": "a",
+            "token": { "access_token": "access", "refresh_token": refresh }
+        });
+        // 旧版本写入两次，.bak 中同样是明文
+        crate::utils::fs::write_json(&path, &plaintext("old-refresh")).unwrap();
+        crate::utils::fs::write_json(&path, &plaintext("refresh")).unwrap();
+        // 已加密的文件，但旧的 .bak 仍残留明文
+        let sealed_path = dir.join("b.json");
+        crate::utils::fs::write_json(&sealed_path, &plaintext("refresh")).unwrap();
+        crate::utils::fs::write_json(&sealed_path, &serde_json::json!({
+            "id": "b",
+            "token": { "version": 1, "encrypted": "sealed" }
+        })).unwrap();
+
+        let seal = |value: &mut serde_json::Value| {
+            value["token"] = serde_json::json!({ "version": 1, "encrypted": "sealed" });
+            Ok(())
+        };
+        assert_eq!(migrate_plaintext_tokens_in(&dir, seal).unwrap(), 1);
+
+        for file in [&path, &sealed_path] {
+            let backup = crate::utils::fs::backup_path(file);
+            assert!(!has_plaintext_token(file));
+            assert!(!has_plaintext_token(&backup));
+            assert!(!fs::read_to_string(&backup).unwrap().contains("refresh"));
+        }
+        // 已全部加密，再次迁移不做任何修改
+        assert_eq!(migrate_plaintext_tokens_in(&dir, seal).unwrap(), 0);
+    }
+}
@@ -0,0 +1,276 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use tauri::Emitter;
+use tokio_util::sync::CancellationToken;
+
+use crate::models::{Account, AppConfig, AutoSwitchConfig};
+use crate::modules;
+
+/// 自动切换账号后发送，负载为 AutoSwitchPayload
+pub const AUTO_SWITCH_EVENT: &str = "account://auto-switched";
+
+/// 上一次自动切换的时间戳
+static LAST_AUTO_SWITCH: AtomicI64 = AtomicI64::new(0);
+
+/// account://auto-switched 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSwitchPayload {
+    pub from_account_id: String,
+    pub from_email: String,
+    pub to_account_id: String,
+    pub to_email: String,
+    /// 切换前当前账号关注模型的最低剩余百分比
+    pub from_percentage: i32,
+    /// 目标账号关注模型的最低剩余百分比
+    pub to_percentage: i32,
+}
+
+/// 计算账号在关注模型上的剩余配额（取最低值）
+/// 没有配额数据、被禁止访问或没有匹配模型时返回 None
+fn quota_score(account: &Account, models: &[String]) -> Option<i32> {
+    let quota = account.quota.as_ref().filter(|q| !q.is_forbidden)?;
+    quota
+        .models
+        .iter()
+        .filter(|m| models.is_empty() || models.iter().any(|name| m.name.contains(name.as_str())))
+        .map(|m| m.percentage)
+        .min()
+}
+
+/// 选出剩余配额最多且高于阈值的候选账号
+fn pick_candidate<'a>(
+    accounts: &'a [Account],
+    current_id: &str,
+    config: &AutoSwitchConfig,
+) -> Option<(&'a Account, i32)> {
+    accounts
+        .iter()
+        .filter(|a| a.id != current_id && !a.disabled)
+        .filter_map(|a| quota_score(a, &config.models).map(|score| (a, score)))
+        .filter(|(_, score)| *score > config.threshold)
+        .max_by_key(|(_, score)| *score)
+}
+
+/// 自动切换看门狗
+/// 定期检查当前桌面账号的配额，低于阈值时切换到剩余配额最多的账号
+pub struct AutoSwitchWatchdog {
+    config: AutoSwitchConfig,
+    /// 取消信号
+    cancel_token: CancellationToken,
+}
+
+impl AutoSwitchWatchdog {
+    /// 创建新的看门狗
+    ///
+    /// # 参数
+    /// - `config`: 自动切换配置
+    pub fn new(config: AutoSwitchConfig) -> Self {
+        Self {
+            config,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    /// 启动后台检查任务
+    pub fn start(&self, app: tauri::AppHandle) {
+        let cancel_token = self.cancel_token.clone();
+        let config = self.config.clone();
+
+        tauri::async_runtime::spawn(async move {
+            modules::logger::log_info(&format!(
+                "自动切换账号任务已启动 (阈值: {}%, 检查间隔: {}s)",
+                config.threshold, config.check_interval_secs
+            ));
+
+            // Antigravity 忙碌而推迟切换的起始时间
+            let mut pending_since: Option<i64> = None;
+            let interval = std::time::Duration::from_secs(config.check_interval_secs.max(10));
+
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => {
+                        modules::logger::log_info("自动切换账号任务已停止");
+                        break;
+                    }
+                    _ = tokio::time::sleep(interval) => {
+                        if let Err(e) = Self::check_once(&app, &config, &mut pending_since).await {
+                            modules::logger::log_warn(&format!("自动切换检查失败: {}", e));
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// 停止后台检查任务
+    pub fn stop(&self) {
+        self.cancel_token.cancel();
+    }
+
+    /// 执行一次检查，需要时切换账号
+    async fn check_once(
+        app: &tauri::AppHandle,
+        config: &AutoSwitchConfig,
+        pending_since: &mut Option<i64>,
+    ) -> Result<(), String> {
+        let current_id = match modules::get_current_account_id()? {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        // 1. 刷新当前账号配额
+        let mut current = modules::load_account(&current_id)?;
+        let quota = modules::account::fetch_quota_with_retry(&mut current)
+            .await
+            .map_err(|e| format!("刷新当前账号配额失败: {}", e))?;
+        // 看门狗轮询频率远高于定时刷新，不写入配额历史，避免挤占历史容量、稀释消耗速率估算
+        modules::update_account(&current_id, |a| a.update_quota(quota.clone()))?;
+        current.update_quota(quota);
+
+        let current_score = match quota_score(&current, &config.models) {
+            Some(score) if score < config.threshold => score,
+            _ => {
+                *pending_since = None;
+                return Ok(());
+            }
+        };
+
+        // 2. 两次自动切换之间至少间隔 min_interval_minutes
+        let now = chrono::Utc::now().timestamp();
+        let last = LAST_AUTO_SWITCH.load(Ordering::Relaxed);
+        if now - last < (config.min_interval_minutes * 60) as i64 {
+            return Ok(());
+        }
+
+        // 3. 选择目标账号
+        let accounts = modules::list_accounts()?;
+        let (target, target_score) = match pick_candidate(&accounts, &current_id, config) {
+            Some(candidate) => candidate,
+            None => {
+                modules::logger::log_warn(&format!(
+                    "当前账号 {} 配额仅剩 {}%，但没有配额高于 {}% 的可切换账号",
+                    current.email, current_score, config.threshold
+                ));
+                return Ok(());
+            }
+        };
+
+        // 4. Antigravity 忙碌时推迟切换，超过宽限期后强制切换
+        if modules::process::is_antigravity_running() {
+            let cpu = tokio::task::spawn_blocking(modules::process::antigravity_cpu_usage)
+                .await
+                .unwrap_or(0.0);
+            if cpu > config.busy_cpu_threshold {
+                let since = *pending_since.get_or_insert(now);
+                if now - since < config.grace_period_secs as i64 {
+                    modules::logger::log_info(&format!(
+                        "Antigravity 正忙 (CPU {:.1}%)，推迟自动切换账号",
+                        cpu
+                    ));
+                    return Ok(());
+                }
+                modules::logger::log_warn("Antigravity 持续忙碌已超过宽限期，执行自动切换");
+            }
+        }
+
+        // 5. 切换
+        modules::logger::log_info(&format!(
+            "当前账号 {} 配额仅剩 {}%，自动切换到 {} ({}%)",
+            current.email, current_score, target.email, target_score
+        ));
+        modules::switch_account(&target.id).await?;
+        LAST_AUTO_SWITCH.store(now, Ordering::Relaxed);
+        *pending_since = None;
+
+        let _ = app.emit(AUTO_SWITCH_EVENT, AutoSwitchPayload {
+            from_account_id: current.id.clone(),
+            from_email: current.email.clone(),
+            to_account_id: target.id.clone(),
+            to_email: target.email.clone(),
+            from_percentage: current_score,
+            to_percentage: target_score,
+        });
+        let _ = app.emit("tray://account-switched", target.id.clone());
+        modules::tray::update_tray_menus(app);
+        Ok(())
+    }
+}
+
+/// 自动切换看门狗的全局状态
+pub struct AutoSwitchState {
+    watchdog: Mutex<Option<AutoSwitchWatchdog>>,
+}
+
+impl AutoSwitchState {
+    pub fn new() -> Self {
+        Self {
+            watchdog: Mutex::new(None),
+        }
+    }
+
+    /// 根据配置启动、重启或停止看门狗
+    pub fn apply_config(&self, app: &tauri::AppHandle, config: &AppConfig) {
+        let mut watchdog = self.watchdog.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = watchdog.take() {
+            existing.stop();
+        }
+
+        if config.auto_switch.enabled {
+            let new_watchdog = AutoSwitchWatchdog::new(config.auto_switch.clone());
+            new_watchdog.start(app.clone());
+            *watchdog = Some(new_watchdog);
+        }
+    }
+}
+
+impl Default for AutoSwitchState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    fn account(id: &str, models: &[(&str, i32)]) -> Account {
+        let token = TokenData::new("a".to_string(), "r".to_string(), 3600, None, None, None);
+        let mut account = Account::new(id.to_string(), format!("{}@example.com", id), token);
+        let mut quota = QuotaData::new();
+        for (name, percentage) in models {
+            quota.add_model(name.to_string(), *percentage, String::new());
+        }
+        account.quota = Some(quota);
+        account
+    }
+
+    #[test]
+    fn test_quota_score_uses_watched_models() {
+        let a = account("a", &[("gemini-3-pro", 5), ("claude-sonnet", 80)]);
+        assert_eq!(quota_score(&a, &[]), Some(5));
+        assert_eq!(quota_score(&a, &["claude".to_string()]), Some(80));
+        assert_eq!(quota_score(&a, &["missing".to_string()]), None);
+    }
+
+    #[test]
+    fn test_pick_candidate_prefers_most_remaining_quota() {
+        let mut disabled = account("d", &[("m", 100)]);
+        disabled.disabled = true;
+        let accounts = vec![
+            account("current", &[("m", 100)]),
+            account("low", &[("m", 5)]),
+            account("mid", &[("m", 40)]),
+            account("high", &[("m", 90)]),
+            disabled,
+        ];
+        let config = AutoSwitchConfig::default();
+        let (target, score) = pick_candidate(&accounts, "current", &config).unwrap();
+        assert_eq!(target.id, "high");
+        assert_eq!(score, 90);
+
+        let config = AutoSwitchConfig { threshold: 95, ..AutoSwitchConfig::default() };
+        assert!(pick_candidate(&accounts, "current", &config).is_none());
+    }
+}
@@ -0,0 +1,297 @@
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::TokenData;
+use crate::modules::{account, oauth};
+
+/// 同时校验的 refresh_token 数量上限，避免触发 Google 的频率限制
+const MAX_CONCURRENT_VALIDATIONS: usize = 5;
+
+/// 待导入的一条记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImportEntry {
+    /// 所在行号（JSON 数组为元素序号），从 1 开始
+    line: usize,
+    /// 导入文件中声明的邮箱（仅用于核对，最终以 Google 返回的邮箱为准）
+    email: Option<String>,
+    refresh_token: String,
+    /// 解析失败的原因，存在时跳过校验并直接记为失败
+    error: Option<String>,
+}
+
+impl ImportEntry {
+    fn valid(line: usize, email: Option<String>, refresh_token: String) -> Self {
+        Self { line, email, refresh_token, error: None }
+    }
+
+    fn invalid(line: usize, email: Option<String>, reason: String) -> Self {
+        Self { line, email, refresh_token: String::new(), error: Some(reason) }
+    }
+}
+
+/// 单条记录的导入结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkImportItem {
+    pub line: usize,
+    pub success: bool,
+    /// Google 返回的真实邮箱，失败时为导入文件中声明的邮箱
+    pub email: Option<String>,
+    pub account_id: Option<String>,
+    pub message: String,
+}
+
+/// 批量导入结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkImportReport {
+    pub total: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub items: Vec<BulkImportItem>,
+}
+
+/// 批量导入 refresh_token
+///
+/// 支持三种格式：
+/// - 每行一个 refresh_token
+/// - CSV: `email,refresh_token`（可带表头）
+/// - JSON 数组: `["1//..."]` 或 `[{"email": "...", "refresh_token": "..."}]`
+///
+/// 所有 Token 以有限并发校验（刷新 access_token + 获取用户信息），
+/// 校验通过后按原顺序逐个写入，保证账号索引的读-改-写不会并发
+///
+/// # 返回
+/// 逐行的导入结果
+pub async fn import_refresh_tokens(content: &str) -> Result<BulkImportReport, String> {
+    let entries = parse_entries(content)?;
+    if entries.is_empty() {
+        return Err("未找到可导入的 refresh_token".to_string());
+    }
+
+    crate::modules::logger::log_info(&format!("开始批量导入 {} 个 refresh_token", entries.len()));
+
+    let validated: Vec<_> = stream::iter(entries)
+        .map(|entry| async move {
+            let result = match &entry.error {
+                Some(reason) => Err(reason.clone()),
+                None => validate_token(&entry.refresh_token)
+                    .await
+                    .map_err(|e| format!("Token 校验失败: {}", e)),
+            };
+            (entry, result)
+        })
+        .buffered(MAX_CONCURRENT_VALIDATIONS)
+        .collect()
+        .await;
+
+    let mut items = Vec::with_capacity(validated.len());
+    for (entry, result) in validated {
+        items.push(save_entry(entry, result));
+    }
+
+    let success = items.iter().filter(|i| i.success).count();
+    let report = BulkImportReport {
+        total: items.len(),
+        success,
+        failed: items.len() - success,
+        items,
+    };
+
+    crate::modules::logger::log_info(&format!(
+        "批量导入完成: {} 成功, {} 失败",
+        report.success, report.failed
+    ));
+    Ok(report)
+}
+
+/// 校验 refresh_token，返回 (Token, 显示名称)
+async fn validate_token(refresh_token: &str) -> Result<(TokenData, Option<String>), String> {
+    let token_res = oauth::refresh_access_token(refresh_token).await?;
+    let user_info = oauth::get_user_info(&token_res.access_token).await?;
+
+    let token = TokenData::new(
+        token_res.access_token,
+        refresh_token.to_string(),
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        None, // project_id 将在需要时获取
+        None, // session_id
+    );
+    Ok((token, user_info.get_display_name()))
+}
+
+fn save_entry(
+    entry: ImportEntry,
+    result: Result<(TokenData, Option<String>), String>,
+) -> BulkImportItem {
+    let failed = |email: Option<String>, message: String| BulkImportItem {
+        line: entry.line,
+        success: false,
+        email,
+        account_id: None,
+        message,
+    };
+
+    let (token, name) = match result {
+        Ok(validated) => validated,
+        Err(e) => return failed(entry.email.clone(), e),
+    };
+    let email = token.email.clone().unwrap_or_default();
+
+    match account::upsert_account(email.clone(), name, token) {
+        Ok(saved) => {
+            let message = match &entry.email {
+                Some(declared) if !declared.eq_ignore_ascii_case(&email) => {
+                    format!("导入成功 (声明的邮箱 {} 与实际邮箱不一致)", declared)
+                }
+                _ => "导入成功".to_string(),
+            };
+            BulkImportItem {
+                line: entry.line,
+                success: true,
+                email: Some(email),
+                account_id: Some(saved.id),
+                message,
+            }
+        }
+        Err(e) => failed(Some(email), format!("保存账号失败: {}", e)),
+    }
+}
+
+/// 解析导入内容，自动识别 JSON 数组、CSV 与纯文本
+/// 只有整体格式无法识别时返回错误，单条记录的问题作为失败记录保留在结果中
+fn parse_entries(content: &str) -> Result<Vec<ImportEntry>, String> {
+    let trimmed = content.trim_start_matches('\u{feff}').trim();
+    if trimmed.starts_with('[') {
+        parse_json(trimmed)
+    } else {
+        Ok(parse_lines(trimmed))
+    }
+}
+
+fn parse_json(content: &str) -> Result<Vec<ImportEntry>, String> {
+    let values: Vec<Value> = serde_json::from_str(content)
+        .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+
+    let mut entries = Vec::new();
+    for (index, value) in values.iter().enumerate() {
+        let line = index + 1;
+        let (email, refresh_token) = match value {
+            Value::String(token) => (None, Some(token.as_str())),
+            Value::Object(obj) => {
+                let token = obj
+                    .get("refresh_token")
+                    .or_else(|| obj.get("token").and_then(|t| t.get("refresh_token")))
+                    .and_then(|v| v.as_str());
+                let email = obj.get("email").and_then(|v| v.as_str()).map(|s| s.to_string());
+                (email, token)
+            }
+            _ => {
+                entries.push(ImportEntry::invalid(line, None, "格式错误，应为字符串或对象".to_string()));
+                continue;
+            }
+        };
+
+        entries.push(match refresh_token.map(str::trim).filter(|t| !t.is_empty()) {
+            Some(token) => ImportEntry::valid(line, email, token.to_string()),
+            None => ImportEntry::invalid(line, email, "缺少 refresh_token".to_string()),
+        });
+    }
+    Ok(entries)
+}
+
+/// 解析纯文本或 CSV，跳过空行、`#` 注释行与表头
+fn parse_lines(content: &str) -> Vec<ImportEntry> {
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line
+            .split([',', '\t'])
+            .map(|f| f.trim().trim_matches('"').trim())
+            .collect();
+        let (email, refresh_token) = match fields.as_slice() {
+            [token] => (None, *token),
+            [email, token, ..] => (Some(*email), *token),
+            [] => continue,
+        };
+
+        if refresh_token.eq_ignore_ascii_case("refresh_token") {
+            continue;
+        }
+
+        let email = email.filter(|e| !e.is_empty()).map(|e| e.to_string());
+        entries.push(if refresh_token.is_empty() {
+            ImportEntry::invalid(index + 1, email, "缺少 refresh_token".to_string())
+        } else {
+            ImportEntry::valid(index + 1, email, refresh_token.to_string())
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_lines() {
+        let entries = parse_entries("1//aaa\n\n# comment\n  1//bbb  \n").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].refresh_token, "1//aaa");
+        assert_eq!(entries[1].line, 4);
+        assert_eq!(entries[1].refresh_token, "1//bbb");
+        assert!(entries[1].email.is_none());
+    }
+
+    #[test]
+    fn test_parse_csv_with_header() {
+        let content = "email,refresh_token\na@example.com,1//aaa\n\"b@example.com\",\"1//bbb\"\n";
+        let entries = parse_entries(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].email.as_deref(), Some("a@example.com"));
+        assert_eq!(entries[0].line, 2);
+        assert_eq!(entries[1].refresh_token, "1//bbb");
+    }
+
+    #[test]
+    fn test_parse_json_array() {
+        let content = r#"["1//aaa", {"email": "b@example.com", "refresh_token": "1//bbb"}]"#;
+        let entries = parse_entries(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].refresh_token, "1//aaa");
+        assert_eq!(entries[1].email.as_deref(), Some("b@example.com"));
+        assert!(entries.iter().all(|e| e.error.is_none()));
+
+        assert!(parse_entries(r#"[{"email": "c@example.com""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_array_keeps_bad_items_as_failures() {
+        let content = r#"[
+            "1//aaa",
+            {"email": "b@example.com"},
+            42,
+            {"email": "d@example.com", "token": {"refresh_token": "1//ddd"}},
+            {"refresh_token": "  "}
+        ]"#;
+        let entries = parse_entries(content).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries.iter().map(|e| e.line).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+        assert!(entries[0].error.is_none());
+        assert_eq!(entries[1].email.as_deref(), Some("b@example.com"));
+        assert_eq!(entries[1].error.as_deref(), Some("缺少 refresh_token"));
+        assert!(entries[2].error.is_some());
+        assert_eq!(entries[3].refresh_token, "1//ddd");
+        assert!(entries[3].error.is_none());
+        assert!(entries[4].error.is_some());
+
+        let item = save_entry(entries[2].clone(), Err(entries[2].error.clone().unwrap()));
+        assert!(!item.success);
+        assert_eq!(item.line, 3);
+    }
+}
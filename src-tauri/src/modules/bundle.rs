@@ -0,0 +1,309 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::models::Account;
+use crate::modules::{account, crypto};
+
+/// 账号包文件标识
+const BUNDLE_FORMAT: &str = "antigravity-tools-bundle";
+/// 账号包格式版本
+const BUNDLE_VERSION: u32 = 1;
+
+/// 加密账号包（写入磁盘的外层结构）
+/// `data` 为 AES-256-GCM 加密后的 BundlePayload，密钥由口令 + salt 经 PBKDF2 派生
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedBundle {
+    format: String,
+    version: u32,
+    created_at: i64,
+    account_count: usize,
+    salt: String,
+    data: String,
+}
+
+/// 账号包明文内容
+#[derive(Debug, Serialize, Deserialize)]
+struct BundlePayload {
+    accounts: Vec<Account>,
+    /// 导出时的当前账号，仅作记录，导入时不会切换
+    #[serde(default)]
+    current_email: Option<String>,
+}
+
+/// 导入时与本地已有账号（按邮箱匹配）冲突的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// 保留本地账号，跳过导入
+    Skip,
+    /// 使用账号包中的数据覆盖本地账号（包括配额与账号设置）
+    Overwrite,
+    /// 合并：使用账号包中的 Token，本地缺失的字段从账号包补全，保留本地账号设置
+    Merge,
+}
+
+/// 导入结果
+#[derive(Debug, Default, Serialize)]
+pub struct BundleImportReport {
+    pub total: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub details: Vec<String>,
+}
+
+/// 导出所有账号为口令加密的账号包
+///
+/// # 参数
+/// - `path`: 账号包保存路径
+/// - `passphrase`: 加密口令
+///
+/// # 返回
+/// 导出的账号数量
+pub fn export_bundle(path: &Path, passphrase: &str) -> Result<usize, String> {
+    if passphrase.is_empty() {
+        return Err("口令不能为空".to_string());
+    }
+
+    let accounts = account::list_accounts()?;
+    if accounts.is_empty() {
+        return Err("没有可导出的账号".to_string());
+    }
+
+    let current_email = account::get_current_account_id()?
+        .and_then(|id| accounts.iter().find(|a| a.id == id))
+        .map(|a| a.email.clone());
+    let count = accounts.len();
+    let bundle = seal_bundle(&BundlePayload { accounts, current_email }, passphrase)?;
+
+    let content = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| format!("序列化账号包失败: {}", e))?;
+    crate::utils::fs::write_atomic(path, content.as_bytes())?;
+
+    crate::modules::logger::log_info(&format!("已导出 {} 个账号到 {:?}", count, path));
+    Ok(count)
+}
+
+/// 从口令加密的账号包导入账号
+///
+/// # 参数
+/// - `path`: 账号包路径
+/// - `passphrase`: 解密口令
+/// - `strategy`: 邮箱冲突时的处理方式
+pub fn import_bundle(
+    path: &Path,
+    passphrase: &str,
+    strategy: ConflictStrategy,
+) -> Result<BundleImportReport, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取账号包失败: {}", e))?;
+    let bundle: EncryptedBundle = serde_json::from_str(&content)
+        .map_err(|e| format!("账号包格式错误: {}", e))?;
+    let payload = open_bundle(&bundle, passphrase)?;
+
+    let existing: Vec<Account> = account::list_accounts()?;
+    let mut report = BundleImportReport {
+        total: payload.accounts.len(),
+        ..Default::default()
+    };
+
+    for incoming in payload.accounts {
+        let local = existing.iter().find(|a| a.email == incoming.email);
+        if local.is_some() && strategy == ConflictStrategy::Skip {
+            report.skipped += 1;
+            report.details.push(format!("{}: 本地已存在，已跳过", incoming.email));
+            continue;
+        }
+
+        let email = incoming.email.clone();
+        match import_one(local, incoming, strategy) {
+            Ok(_) if local.is_some() => report.updated += 1,
+            Ok(_) => report.added += 1,
+            Err(e) => {
+                report.failed += 1;
+                report.details.push(format!("{}: {}", email, e));
+                crate::modules::logger::log_error(&format!("导入账号 {} 失败: {}", email, e));
+            }
+        }
+    }
+
+    crate::modules::logger::log_info(&format!(
+        "账号包导入完成: 新增 {}, 更新 {}, 跳过 {}, 失败 {}",
+        report.added, report.updated, report.skipped, report.failed
+    ));
+    Ok(report)
+}
+
+/// 导入单个账号：先经 upsert_account 写入 Token 与名称，再补齐配额和账号设置
+fn import_one(
+    local: Option<&Account>,
+    incoming: Account,
+    strategy: ConflictStrategy,
+) -> Result<Account, String> {
+    let merged = match local {
+        Some(local) if strategy == ConflictStrategy::Merge => merge_account(local, incoming),
+        _ => incoming,
+    };
+
+    let saved = account::upsert_account(merged.email.clone(), merged.name.clone(), merged.token.clone())?;
+    account::update_account(&saved.id, |account| {
+        account.quota = merged.quota;
+        account.disabled = merged.disabled;
+        account.disabled_reason = merged.disabled_reason;
+        account.disabled_at = merged.disabled_at;
+        account.proxy_enabled = merged.proxy_enabled;
+        account.groups = merged.groups;
+    })
+}
+
+/// 合并本地账号与账号包中的同邮箱账号
+/// - Token 使用账号包中的版本，project_id / session_id 缺失时沿用本地值
+/// - 名称、配额取非空且较新的值
+/// - 禁用状态与反代开关保留本地设置
+/// - 分组取本地与账号包的并集
+fn merge_account(local: &Account, incoming: Account) -> Account {
+    let mut merged = local.clone();
+
+    let mut token = incoming.token;
+    if token.project_id.is_none() {
+        token.project_id = local.token.project_id.clone();
+    }
+    if token.session_id.is_none() {
+        token.session_id = local.token.session_id.clone();
+    }
+    merged.token = token;
+    // 账号包中的 Token 视为新的有效授权，解除因 refresh_token 失效导致的禁用
+    if local.disabled_reason.as_deref() == Some(account::DISABLED_REASON_NEEDS_REAUTH) {
+        merged.enable();
+    }
+
+    if incoming.name.is_some() {
+        merged.name = incoming.name;
+    }
+
+    merged.groups = account::normalize_groups(local.groups.iter().cloned().chain(incoming.groups));
+
+    let incoming_newer = match (&local.quota, &incoming.quota) {
+        (Some(l), Some(i)) => i.last_updated > l.last_updated,
+        (None, Some(_)) => true,
+        _ => false,
+    };
+    if incoming_newer {
+        merged.quota = incoming.quota;
+    }
+
+    merged
+}
+
+fn seal_bundle(payload: &BundlePayload, passphrase: &str) -> Result<EncryptedBundle, String> {
+    let plaintext = serde_json::to_vec(payload)
+        .map_err(|e| format!("序列化账号数据失败: {}", e))?;
+    let salt = crypto::random_salt()?;
+    let key = crypto::derive_key(passphrase, &salt);
+    let encrypted = crypto::encrypt_bytes(&key, &plaintext)?;
+
+    Ok(EncryptedBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created_at: chrono::Utc::now().timestamp(),
+        account_count: payload.accounts.len(),
+        salt: general_purpose::STANDARD.encode(salt),
+        data: general_purpose::STANDARD.encode(encrypted),
+    })
+}
+
+fn open_bundle(bundle: &EncryptedBundle, passphrase: &str) -> Result<BundlePayload, String> {
+    if bundle.format != BUNDLE_FORMAT {
+        return Err("不是有效的账号包文件".to_string());
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!("不支持的账号包版本: {}", bundle.version));
+    }
+
+    let salt = general_purpose::STANDARD
+        .decode(&bundle.salt)
+        .map_err(|e| format!("账号包格式错误: {}", e))?;
+    let encrypted = general_purpose::STANDARD
+        .decode(&bundle.data)
+        .map_err(|e| format!("账号包格式错误: {}", e))?;
+    let key = crypto::derive_key(passphrase, &salt);
+    let plaintext = crypto::decrypt_bytes(&key, &encrypted)
+        .map_err(|_| "解密账号包失败，口令错误或文件已损坏".to_string())?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| format!("解析账号包内容失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    fn sample_account(email: &str, refresh_token: &str) -> Account {
+        let token = TokenData::new(
+            "access".to_string(),
+            refresh_token.to_string(),
+            3600,
+            Some(email.to_string()),
+            None,
+            None,
+        );
+        Account::new(uuid::Uuid::new_v4().to_string(), email.to_string(), token)
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let payload = BundlePayload {
+            accounts: vec![sample_account("a@example.com", "1//secret")],
+            current_email: Some("a@example.com".to_string()),
+        };
+        let bundle = seal_bundle(&payload, "correct horse").unwrap();
+        assert!(!serde_json::to_string(&bundle).unwrap().contains("1//secret"));
+
+        let opened = open_bundle(&bundle, "correct horse").unwrap();
+        assert_eq!(opened.accounts.len(), 1);
+        assert_eq!(opened.accounts[0].token.refresh_token, "1//secret");
+        assert!(open_bundle(&bundle, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_merge_keeps_local_settings_and_fills_missing_fields() {
+        let mut local = sample_account("a@example.com", "1//old");
+        local.token.project_id = Some("local-project".to_string());
+        local.proxy_enabled = false;
+        local.name = Some("Local".to_string());
+        let mut quota = QuotaData::new();
+        quota.last_updated = 200;
+        local.quota = Some(quota);
+
+        let mut incoming = sample_account("a@example.com", "1//new");
+        incoming.name = None;
+        let mut stale = QuotaData::new();
+        stale.last_updated = 100;
+        incoming.quota = Some(stale);
+
+        let merged = merge_account(&local, incoming);
+        assert_eq!(merged.id, local.id);
+        assert_eq!(merged.token.refresh_token, "1//new");
+        assert_eq!(merged.token.project_id.as_deref(), Some("local-project"));
+        assert_eq!(merged.name.as_deref(), Some("Local"));
+        assert_eq!(merged.quota.unwrap().last_updated, 200);
+        assert!(!merged.proxy_enabled);
+    }
+
+    #[test]
+    fn test_groups_survive_bundle_roundtrip_and_merge() {
+        let mut exported = sample_account("a@example.com", "1//new");
+        exported.groups = vec!["team".to_string(), "Batch".to_string()];
+        let payload = BundlePayload { accounts: vec![exported], current_email: None };
+        let bundle = seal_bundle(&payload, "correct horse").unwrap();
+        let incoming = open_bundle(&bundle, "correct horse").unwrap().accounts.remove(0);
+        assert_eq!(incoming.groups, vec!["team", "Batch"]);
+
+        let mut local = sample_account("a@example.com", "1//old");
+        local.groups = vec!["local".to_string(), "batch".to_string()];
+        let merged = merge_account(&local, incoming);
+        assert_eq!(merged.groups, vec!["local", "batch", "team"]);
+    }
+}
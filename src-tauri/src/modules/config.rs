@@ -1,6 +1,3 @@
-use std::fs;
-use serde_json;
-
 use crate::models::AppConfig;
 use super::account::get_data_dir;
 
@@ -15,11 +12,8 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         return Ok(AppConfig::new());
     }
     
-    let content = fs::read_to_string(&config_path)
-        .map_err(|e| format!("读取配置文件失败: {}", e))?;
-    
-    serde_json::from_str(&content)
-        .map_err(|e| format!("解析配置文件失败: {}", e))
+    crate::utils::fs::read_json(&config_path)
+        .map_err(|e| format!("加载配置文件失败: {}", e))
 }
 
 /// 保存应用配置
@@ -27,9 +21,27 @@ pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
     let data_dir = get_data_dir()?;
     let config_path = data_dir.join(CONFIG_FILE);
     
-    let content = serde_json::to_string_pretty(config)
-        .map_err(|e| format!("序列化配置失败: {}", e))?;
-    
-    fs::write(&config_path, content)
+    crate::utils::fs::write_json(&config_path, config)
         .map_err(|e| format!("保存配置失败: {}", e))
 }
+
+/// 在文件锁内读取、修改并保存应用配置，避免与其他写入方互相覆盖
+pub fn update_app_config<F>(update: F) -> Result<AppConfig, String>
+where
+    F: FnOnce(&mut AppConfig),
+{
+    let data_dir = get_data_dir()?;
+    let config_path = data_dir.join(CONFIG_FILE);
+
+    crate::utils::fs::update_json_or(&config_path, AppConfig::new, |config| {
+        update(config);
+        Ok(())
+    })
+    .map_err(|e| format!("保存配置失败: {}", e))
+}
+
+/// 保存 Anthropic 模型映射表，GUI 命令与管理接口共用
+pub fn save_anthropic_mapping(mapping: std::collections::HashMap<String, String>) -> Result<(), String> {
+    update_app_config(|config| config.proxy.anthropic_mapping = mapping)?;
+    Ok(())
+}
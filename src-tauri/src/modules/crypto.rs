@@ -0,0 +1,253 @@
+use base64::{engine::general_purpose, Engine as _};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use super::account::get_data_dir;
+
+/// 随机生成的本机密钥文件（权限 0600）
+const KEY_FILE: &str = "token.key";
+/// 口令派生密钥使用的盐
+const SALT_FILE: &str = "token.salt";
+/// 设置该环境变量后改用口令派生密钥，而不是密钥文件
+pub const PASSPHRASE_ENV: &str = "ANTIGRAVITY_TOKEN_PASSPHRASE";
+
+/// 加密后的 token 对象格式版本
+const TOKEN_FORMAT_VERSION: u64 = 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+static TOKEN_KEY: OnceLock<[u8; KEY_LEN]> = OnceLock::new();
+
+/// 获取本机用于加密 Token 的密钥（进程内缓存）
+/// 只缓存成功加载的密钥，读取失败（如密钥文件暂不可读）时下次调用会重新尝试
+fn token_key() -> Result<[u8; KEY_LEN], String> {
+    if let Some(key) = TOKEN_KEY.get() {
+        return Ok(*key);
+    }
+    let key = load_token_key()?;
+    // 并发加载时以先写入的为准
+    Ok(*TOKEN_KEY.get_or_init(|| key))
+}
+
+fn load_token_key() -> Result<[u8; KEY_LEN], String> {
+    let data_dir = get_data_dir()?;
+    match std::env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => {
+            let salt = load_or_create_secret(&data_dir.join(SALT_FILE), SALT_LEN)?;
+            Ok(derive_key(&passphrase, &salt))
+        }
+        _ => {
+            let key = load_or_create_secret(&data_dir.join(KEY_FILE), KEY_LEN)?;
+            let mut out = [0u8; KEY_LEN];
+            out.copy_from_slice(&key);
+            Ok(out)
+        }
+    }
+}
+
+/// 读取或生成随机密钥材料，新建文件时限制为仅当前用户可读写
+fn load_or_create_secret(path: &Path, len: usize) -> Result<Vec<u8>, String> {
+    if path.exists() {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("读取密钥文件失败: {}", e))?;
+        let secret = general_purpose::STANDARD
+            .decode(content.trim())
+            .map_err(|e| format!("密钥文件格式错误: {}", e))?;
+        if secret.len() != len {
+            return Err(format!("密钥文件长度错误: {:?}", path));
+        }
+        return Ok(secret);
+    }
+
+    let mut secret = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| "生成随机密钥失败".to_string())?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("创建密钥文件失败: {}", e))?;
+    use std::io::Write;
+    file.write_all(general_purpose::STANDARD.encode(&secret).as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("写入密钥文件失败: {}", e))?;
+
+    crate::modules::logger::log_info(&format!("已生成新的密钥文件: {:?}", path));
+    Ok(secret)
+}
+
+/// 使用 PBKDF2-HMAC-SHA256 从口令派生密钥
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).expect("迭代次数必须大于 0"),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+/// 生成随机盐
+pub fn random_salt() -> Result<Vec<u8>, String> {
+    let mut salt = vec![0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "生成随机盐失败".to_string())?;
+    Ok(salt)
+}
+
+/// AES-256-GCM 加密，输出格式为 nonce || ciphertext || tag
+pub fn encrypt_bytes(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let sealing_key = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, key).map_err(|_| "无效的加密密钥".to_string())?,
+    );
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce_bytes)
+        .map_err(|_| "生成随机数失败".to_string())?;
+
+    let mut in_out = plaintext.to_vec();
+    sealing_key
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::empty(), &mut in_out)
+        .map_err(|_| "加密失败".to_string())?;
+
+    let mut output = nonce_bytes.to_vec();
+    output.extend_from_slice(&in_out);
+    Ok(output)
+}
+
+/// AES-256-GCM 解密 encrypt_bytes 的输出
+pub fn decrypt_bytes(key: &[u8; KEY_LEN], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_LEN {
+        return Err("密文长度错误".to_string());
+    }
+    let opening_key = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, key).map_err(|_| "无效的加密密钥".to_string())?,
+    );
+
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| "无效的 nonce".to_string())?;
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = opening_key
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| "解密失败 (密钥不匹配或数据已损坏)".to_string())?;
+    Ok(plaintext.to_vec())
+}
+
+/// token 对象是否已加密
+pub fn is_token_sealed(token: &serde_json::Value) -> bool {
+    token.get("encrypted").and_then(|v| v.as_str()).is_some()
+}
+
+/// 加密账号 JSON 中的 token 对象（原地修改）
+/// `{"token": {...}}` -> `{"token": {"version": 1, "encrypted": "<base64>"}}`
+pub fn seal_account_value(account: &mut serde_json::Value) -> Result<(), String> {
+    seal_account_value_with(&token_key()?, account)
+}
+
+/// 解密账号 JSON 中的 token 对象（原地修改），明文 token 原样保留
+pub fn open_account_value(account: &mut serde_json::Value) -> Result<(), String> {
+    if !account.get("token").map(is_token_sealed).unwrap_or(false) {
+        return Ok(());
+    }
+    open_account_value_with(&token_key()?, account)
+}
+
+fn seal_account_value_with(key: &[u8; KEY_LEN], account: &mut serde_json::Value) -> Result<(), String> {
+    let token = match account.get("token") {
+        Some(token) if !is_token_sealed(token) => token,
+        _ => return Ok(()),
+    };
+
+    let plaintext = serde_json::to_vec(token).map_err(|e| format!("序列化 Token 失败: {}", e))?;
+    let encrypted = encrypt_bytes(key, &plaintext)?;
+    account["token"] = serde_json::json!({
+        "version": TOKEN_FORMAT_VERSION,
+        "encrypted": general_purpose::STANDARD.encode(encrypted),
+    });
+    Ok(())
+}
+
+fn open_account_value_with(key: &[u8; KEY_LEN], account: &mut serde_json::Value) -> Result<(), String> {
+    let encoded = match account["token"]["encrypted"].as_str() {
+        Some(encoded) => encoded,
+        None => return Ok(()),
+    };
+
+    let encrypted = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Token 密文格式错误: {}", e))?;
+    let plaintext = decrypt_bytes(key, &encrypted)?;
+    account["token"] = serde_json::from_slice(&plaintext)
+        .map_err(|e| format!("解析解密后的 Token 失败: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_account() -> serde_json::Value {
+        serde_json::json!({
+            "id": "a",
+            "email": "a@example.com",
+            "token": {
+                "access_token": "ya29.secret",
+                "refresh_token": "1//refresh",
+                "expires_in": 3600,
+                "expiry_timestamp": 0,
+                "token_type": "Bearer"
+            }
+        })
+    }
+
+    #[test]
+    fn test_seal_and_open_roundtrip() {
+        let key = derive_key("passphrase", b"0123456789abcdef");
+        let original = sample_account();
+
+        let mut account = original.clone();
+        seal_account_value_with(&key, &mut account).unwrap();
+        assert!(is_token_sealed(&account["token"]));
+        assert!(!account.to_string().contains("1//refresh"));
+        // 非 token 字段保持明文
+        assert_eq!(account["email"], "a@example.com");
+
+        open_account_value_with(&key, &mut account).unwrap();
+        assert_eq!(account, original);
+    }
+
+    #[test]
+    fn test_seal_is_idempotent() {
+        let key = derive_key("passphrase", b"0123456789abcdef");
+        let mut account = sample_account();
+        seal_account_value_with(&key, &mut account).unwrap();
+        let sealed = account.clone();
+        seal_account_value_with(&key, &mut account).unwrap();
+        assert_eq!(account, sealed);
+    }
+
+    #[test]
+    fn test_open_with_wrong_key_fails() {
+        let key = derive_key("passphrase", b"0123456789abcdef");
+        let wrong = derive_key("other", b"0123456789abcdef");
+        let mut account = sample_account();
+        seal_account_value_with(&key, &mut account).unwrap();
+        assert!(open_account_value_with(&wrong, &mut account).is_err());
+    }
+}
@@ -1,6 +1,7 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use base64::{Engine as _, engine::general_purpose};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use crate::utils::protobuf;
 
 /// 获取 Antigravity 数据库路径（跨平台）
@@ -25,6 +26,11 @@ pub fn get_db_path() -> Result<PathBuf, String> {
     }
 }
 
+/// Antigravity 运行时可能正持有写锁，等待锁释放的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// 保存登录状态的键
+const STATE_KEY: &str = "jetskiStateSync.agentManagerInitState";
+
 /// 注入 Token 到数据库
 pub fn inject_token(
     db_path: &PathBuf,
@@ -35,12 +41,14 @@ pub fn inject_token(
     // 1. 打开数据库
     let conn = Connection::open(db_path)
         .map_err(|e| format!("打开数据库失败: {}", e))?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("设置数据库超时失败: {}", e))?;
 
     // 2. 读取当前数据
     let current_data: String = conn
         .query_row(
             "SELECT value FROM ItemTable WHERE key = ?",
-            ["jetskiStateSync.agentManagerInitState"],
+            [STATE_KEY],
             |row| row.get(0),
         )
         .map_err(|e| format!("读取数据失败: {}", e))?;
@@ -63,7 +71,7 @@ pub fn inject_token(
     // 7. 写入数据库
     conn.execute(
         "UPDATE ItemTable SET value = ? WHERE key = ?",
-        [&final_b64, "jetskiStateSync.agentManagerInitState"],
+        [final_b64.as_str(), STATE_KEY],
     )
     .map_err(|e| format!("写入数据失败: {}", e))?;
 
@@ -77,3 +85,78 @@ pub fn inject_token(
 
     Ok(format!("Token 注入成功！\n数据库: {:?}", db_path))
 }
+
+/// 校验数据库完整性
+/// 依次检查 SQLite `PRAGMA integrity_check` 与登录状态 Protobuf 能否完整解码
+///
+/// # 参数
+/// - `db_path`: 数据库路径
+pub fn check_integrity(db_path: &Path) -> Result<(), String> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("打开数据库失败: {}", e))?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("设置数据库超时失败: {}", e))?;
+
+    // 1. SQLite 自身的完整性检查
+    let result: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("数据库完整性检查失败: {}", e))?;
+    if result != "ok" {
+        return Err(format!("数据库已损坏: {}", result));
+    }
+
+    // 2. 登录状态（从未登录过的数据库没有这一项）
+    let current_data: Option<String> = conn
+        .query_row(
+            "SELECT value FROM ItemTable WHERE key = ?",
+            [STATE_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("读取数据失败: {}", e))?;
+    let current_data = match current_data {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    let blob = general_purpose::STANDARD
+        .decode(&current_data)
+        .map_err(|e| format!("登录状态 Base64 解码失败: {}", e))?;
+    protobuf::validate(&blob).map_err(|e| format!("登录状态解析失败: {}", e))?;
+    if let Some(oauth) = protobuf::find_field(&blob, 6)? {
+        protobuf::validate(&oauth).map_err(|e| format!("OAuth 信息解析失败: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// 读取数据库中当前登录的 access_token
+///
+/// # 返回
+/// 未登录（不存在 Field 6）时返回 None
+pub fn read_access_token(db_path: &PathBuf) -> Result<Option<String>, String> {
+    let conn = Connection::open(db_path)
+        .map_err(|e| format!("打开数据库失败: {}", e))?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("设置数据库超时失败: {}", e))?;
+
+    let current_data: String = conn
+        .query_row(
+            "SELECT value FROM ItemTable WHERE key = ?",
+            [STATE_KEY],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("读取数据失败: {}", e))?;
+
+    let blob = general_purpose::STANDARD
+        .decode(&current_data)
+        .map_err(|e| format!("Base64 解码失败: {}", e))?;
+
+    let oauth = match protobuf::find_field(&blob, 6)? {
+        Some(oauth) => oauth,
+        None => return Ok(None),
+    };
+    let access_token = protobuf::find_field(&oauth, 1)?
+        .map(|token| String::from_utf8_lossy(&token).into_owned());
+    Ok(access_token)
+}
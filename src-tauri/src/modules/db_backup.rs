@@ -0,0 +1,275 @@
+use rusqlite::{backup::Progress, Connection, DatabaseName, OpenFlags};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::modules::{account, config, db, logger, process};
+
+/// 备份目录（位于工具数据目录下）
+const BACKUP_DIR: &str = "db_backups";
+/// 备份文件扩展名
+const BACKUP_EXT: &str = "vscdb";
+/// 读取配置失败时使用的保留份数
+const DEFAULT_RETENTION: usize = 10;
+
+/// 一份 Antigravity 数据库备份
+#[derive(Debug, Clone, Serialize)]
+pub struct DbBackupInfo {
+    /// 备份 ID（文件名去掉扩展名），格式为 `{毫秒时间戳}-{原因}`
+    pub id: String,
+    /// 创建时间（秒）
+    pub created_at: i64,
+    /// 创建原因，例如 switch / before-restore
+    pub reason: String,
+    /// 文件大小（字节）
+    pub size: u64,
+}
+
+fn backup_dir() -> Result<PathBuf, String> {
+    let dir = account::get_data_dir()?.join(BACKUP_DIR);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| format!("创建数据库备份目录失败: {}", e))?;
+    }
+    Ok(dir)
+}
+
+fn retention() -> usize {
+    config::load_app_config()
+        .map(|c| c.db_backup_retention)
+        .unwrap_or(DEFAULT_RETENTION)
+        .max(1)
+}
+
+/// 备份 Antigravity 数据库，并按保留份数清理旧备份
+///
+/// # 参数
+/// - `db_path`: 数据库路径
+/// - `reason`: 备份原因，写入备份 ID
+pub fn create_backup(db_path: &Path, reason: &str) -> Result<DbBackupInfo, String> {
+    create_backup_in(&backup_dir()?, db_path, reason, retention())
+}
+
+fn create_backup_in(dir: &Path, db_path: &Path, reason: &str, retention: usize) -> Result<DbBackupInfo, String> {
+    let id = format!("{}-{}", chrono::Utc::now().timestamp_millis(), reason);
+    let path = dir.join(format!("{}.{}", id, BACKUP_EXT));
+
+    // 使用 SQLite 在线备份，Antigravity 运行中也能得到一致的快照
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("打开数据库失败: {}", e))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| format!("设置数据库超时失败: {}", e))?;
+    conn.backup(DatabaseName::Main, &path, None)
+        .map_err(|e| format!("备份数据库失败: {}", e))?;
+
+    prune_backups(dir, retention)?;
+    parse_backup(&path).ok_or_else(|| format!("读取备份信息失败: {:?}", path))
+}
+
+fn parse_backup(path: &Path) -> Option<DbBackupInfo> {
+    if path.extension().and_then(|e| e.to_str()) != Some(BACKUP_EXT) {
+        return None;
+    }
+    let id = path.file_stem()?.to_str()?.to_string();
+    let (millis, reason) = id.split_once('-')?;
+    let created_at = millis.parse::<i64>().ok()? / 1000;
+    let size = fs::metadata(path).ok()?.len();
+    Some(DbBackupInfo {
+        reason: reason.to_string(),
+        id,
+        created_at,
+        size,
+    })
+}
+
+/// 列出目录中的备份，最新的在前
+fn list_backups_in(dir: &Path) -> Result<Vec<DbBackupInfo>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("读取数据库备份目录失败: {}", e))?;
+    let mut backups: Vec<DbBackupInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| parse_backup(&entry.path()))
+        .collect();
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+fn prune_backups(dir: &Path, retention: usize) -> Result<(), String> {
+    for old in list_backups_in(dir)?.into_iter().skip(retention) {
+        let path = dir.join(format!("{}.{}", old.id, BACKUP_EXT));
+        if let Err(e) = fs::remove_file(&path) {
+            logger::log_warn(&format!("删除旧数据库备份失败: {:?}, {}", path, e));
+        }
+    }
+    Ok(())
+}
+
+/// 列出所有 Antigravity 数据库备份，最新的在前
+pub fn list_backups() -> Result<Vec<DbBackupInfo>, String> {
+    list_backups_in(&backup_dir()?)
+}
+
+fn backup_path(dir: &Path, backup_id: &str) -> Result<PathBuf, String> {
+    if backup_id.is_empty() || backup_id.contains(['/', '\\']) || backup_id.contains("..") {
+        return Err(format!("无效的备份 ID: {}", backup_id));
+    }
+    let path = dir.join(format!("{}.{}", backup_id, BACKUP_EXT));
+    if !path.exists() {
+        return Err(format!("备份不存在: {}", backup_id));
+    }
+    Ok(path)
+}
+
+/// 用备份内容覆盖数据库
+/// 通过 SQLite 恢复接口写入，避免与残留的 WAL 文件不一致
+fn restore_into(db_path: &Path, backup_path: &Path) -> Result<(), String> {
+    let mut conn = Connection::open(db_path).map_err(|e| format!("打开数据库失败: {}", e))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| format!("设置数据库超时失败: {}", e))?;
+    conn.restore(DatabaseName::Main, backup_path, None::<fn(Progress)>)
+        .map_err(|e| format!("恢复数据库失败: {}", e))
+}
+
+/// 将 Antigravity 数据库恢复到指定备份
+/// Antigravity 运行中时会先关闭，恢复后重新启动
+///
+/// # 参数
+/// - `backup_id`: 备份 ID
+pub fn restore_backup(backup_id: &str) -> Result<(), String> {
+    let dir = backup_dir()?;
+    let path = backup_path(&dir, backup_id)?;
+    db::check_integrity(&path).map_err(|e| format!("备份已损坏，无法恢复: {}", e))?;
+
+    let was_running = process::is_antigravity_running();
+    if was_running {
+        process::close_antigravity(20)?;
+    }
+
+    // 恢复前再备份一次当前数据库，便于撤销本次恢复
+    let db_path = db::get_db_path()?;
+    if db_path.exists() {
+        if let Err(e) = create_backup(&db_path, "before-restore") {
+            logger::log_warn(&format!("恢复前备份当前数据库失败: {}", e));
+        }
+    }
+
+    restore_into(&db_path, &path)?;
+    db::check_integrity(&db_path).map_err(|e| format!("恢复后完整性检查失败: {}", e))?;
+    logger::log_info(&format!("已将 Antigravity 数据库恢复到备份 {}", backup_id));
+
+    if was_running {
+        process::start_antigravity()?;
+    }
+    Ok(())
+}
+
+/// 带备份与回滚的 Token 注入
+/// 注入前后都会做完整性检查，注入失败或注入后校验不通过时自动恢复到注入前的备份
+///
+/// # 参数
+/// - `db_path`: 数据库路径
+/// - `access_token` / `refresh_token` / `expiry`: 要注入的 Token
+pub fn inject_token_with_backup(
+    db_path: &Path,
+    access_token: &str,
+    refresh_token: &str,
+    expiry: i64,
+) -> Result<(), String> {
+    inject_with_backup_in(&backup_dir()?, retention(), db_path, access_token, refresh_token, expiry)
+}
+
+fn inject_with_backup_in(
+    dir: &Path,
+    retention: usize,
+    db_path: &Path,
+    access_token: &str,
+    refresh_token: &str,
+    expiry: i64,
+) -> Result<(), String> {
+    db::check_integrity(db_path).map_err(|e| format!("注入前完整性检查失败: {}", e))?;
+    let backup = create_backup_in(dir, db_path, "switch", retention)?;
+
+    let result = db::inject_token(&db_path.to_path_buf(), access_token, refresh_token, expiry)
+        .and_then(|_| db::check_integrity(db_path));
+    if let Err(e) = result {
+        logger::log_error(&format!("注入 Token 失败，正在回滚到备份 {}: {}", backup.id, e));
+        restore_into(db_path, &dir.join(format!("{}.{}", backup.id, BACKUP_EXT)))
+            .map_err(|re| format!("注入 Token 失败 ({})，且回滚失败: {}", e, re))?;
+        return Err(format!("注入 Token 失败，已回滚到注入前的状态: {}", e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::TempDir;
+    use base64::{engine::general_purpose, Engine as _};
+
+    /// 创建一个与 Antigravity 结构相同的数据库
+    fn create_db(dir: &Path, state: &str) -> PathBuf {
+        let path = dir.join("state.vscdb");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute("CREATE TABLE ItemTable (key TEXT UNIQUE ON CONFLICT REPLACE, value BLOB)", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO ItemTable (key, value) VALUES ('jetskiStateSync.agentManagerInitState', ?)",
+            [state],
+        )
+        .unwrap();
+        path
+    }
+
+    fn valid_state() -> String {
+        // Field 1: "abc"
+        general_purpose::STANDARD.encode([0x0a, 0x03, b'a', b'b', b'c'])
+    }
+
+    #[test]
+    fn test_integrity_check_detects_corrupt_state() {
+        let dir = TempDir::new("db_backup");
+        let db_path = create_db(&dir, &valid_state());
+        assert!(db::check_integrity(&db_path).is_ok());
+
+        // 长度字段声明 100 字节，实际只有 3 字节
+        let corrupt = general_purpose::STANDARD.encode([0x0a, 0x64, b'a', b'b', b'c']);
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "UPDATE ItemTable SET value = ? WHERE key = 'jetskiStateSync.agentManagerInitState'",
+                [corrupt],
+            )
+            .unwrap();
+        assert!(db::check_integrity(&db_path).is_err());
+    }
+
+    #[test]
+    fn test_inject_creates_backup_and_keeps_retention() {
+        let dir = TempDir::new("db_backup");
+        let backups = dir.join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        let db_path = create_db(&dir, &valid_state());
+
+        for i in 0..4 {
+            inject_with_backup_in(&backups, 2, &db_path, &format!("access-{}", i), "refresh", 0).unwrap();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(db::read_access_token(&db_path).unwrap().as_deref(), Some("access-3"));
+
+        let list = list_backups_in(&backups).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list[0].id > list[1].id);
+        assert_eq!(list[0].reason, "switch");
+
+        // 最新的备份是注入 access-3 之前的状态
+        restore_into(&db_path, &backups.join(format!("{}.{}", list[0].id, BACKUP_EXT))).unwrap();
+        assert_eq!(db::read_access_token(&db_path).unwrap().as_deref(), Some("access-2"));
+    }
+
+    #[test]
+    fn test_backup_path_rejects_traversal() {
+        let dir = TempDir::new("db_backup");
+        assert!(backup_path(&dir, "../state").is_err());
+        assert!(backup_path(&dir, "a/b").is_err());
+        assert!(backup_path(&dir, "1-missing").is_err());
+    }
+}
@@ -13,6 +13,8 @@ pub struct TrayTexts {
     pub no_account: String,
     pub unknown_quota: String,
     pub forbidden: String,
+    pub proxy_exclude: String,
+    pub proxy_include: String,
 }
 
 /// 从 JSON 加载翻译
@@ -52,5 +54,7 @@ pub fn get_tray_texts(lang: &str) -> TrayTexts {
         no_account: t.get("no_account").cloned().unwrap_or_else(|| "No Account".to_string()),
         unknown_quota: t.get("unknown_quota").cloned().unwrap_or_else(|| "Unknown".to_string()),
         forbidden: t.get("forbidden").cloned().unwrap_or_else(|| "Account Forbidden".to_string()),
+        proxy_exclude: t.get("proxy_exclude").cloned().unwrap_or_else(|| "Exclude Current from Proxy".to_string()),
+        proxy_include: t.get("proxy_include").cloned().unwrap_or_else(|| "Include Current in Proxy".to_string()),
     }
 }
@@ -1,77 +1,544 @@
-use tracing::{info, warn, error};
-use tracing_subscriber;
-use std::fs;
-use std::path::PathBuf;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{error, info, span, warn, Event, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt as tracing_fmt, reload, Layer, Registry};
+
+use crate::models::{ContentLogging, LogConfig};
 use crate::modules::account::get_data_dir;
+use crate::utils::redact;
+
+/// 当前写入的日志文件
+const LOG_FILE: &str = "app.log";
+/// 轮转后的历史日志文件前缀，完整文件名为 `app-{时间}.log`
+const ROTATED_PREFIX: &str = "app-";
+
+/// 日志级别过滤器的热更新句柄
+static FILTER_HANDLE: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+/// 日志文件输出
+static LOG_SINK: OnceLock<Arc<LogSink>> = OnceLock::new();
+/// 隐私模式
+static PRIVACY_MODE: AtomicBool = AtomicBool::new(false);
+/// 内容记录级别 (ContentLogging as u8)
+static CONTENT_LOGGING: AtomicU8 = AtomicU8::new(ContentLogging::Off as u8);
 
 pub fn get_log_dir() -> Result<PathBuf, String> {
     let data_dir = get_data_dir()?;
     let log_dir = data_dir.join("logs");
-    
+
     if !log_dir.exists() {
         fs::create_dir_all(&log_dir).map_err(|e| format!("创建日志目录失败: {}", e))?;
     }
-    
+
     Ok(log_dir)
 }
 
+fn apply_privacy(config: &LogConfig) {
+    PRIVACY_MODE.store(config.privacy_mode, Ordering::Relaxed);
+    CONTENT_LOGGING.store(config.content_logging as u8, Ordering::Relaxed);
+}
+
+fn content_logging() -> ContentLogging {
+    if PRIVACY_MODE.load(Ordering::Relaxed) {
+        return ContentLogging::Off;
+    }
+    match CONTENT_LOGGING.load(Ordering::Relaxed) {
+        x if x == ContentLogging::Full as u8 => ContentLogging::Full,
+        x if x == ContentLogging::Preview as u8 => ContentLogging::Preview,
+        _ => ContentLogging::Off,
+    }
+}
+
+/// 按内容记录级别生成提示词或回答的日志文本
+///
+/// # 参数
+/// - `text`: 提示词或回答内容
+/// - `max_chars`: Preview 级别下保留的字符数
+pub fn content_preview(text: &str, max_chars: usize) -> String {
+    match content_logging() {
+        ContentLogging::Off => format!("[已隐藏 {} 字符]", text.chars().count()),
+        ContentLogging::Preview => redact::truncate_chars(text, max_chars),
+        ContentLogging::Full => text.to_string(),
+    }
+}
+
+/// 屏蔽日志行中的凭据，隐私模式下同时屏蔽邮箱
+pub fn redact_line(line: &str) -> String {
+    let masked = redact::mask_secrets(line);
+    if PRIVACY_MODE.load(Ordering::Relaxed) {
+        redact::mask_emails(&masked)
+    } else {
+        masked
+    }
+}
+
+/// 输出前脱敏的标准输出
+/// fmt layer 每条日志只调用一次 write，因此可以按整条日志处理
+struct RedactingStdout;
+
+impl Write for RedactingStdout {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let line = redact_line(&String::from_utf8_lossy(buf));
+        std::io::stdout().write_all(line.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+/// 根据配置构建级别过滤器
+///
+/// # 返回
+/// 过滤器与无法解析的级别配置
+fn build_filter(config: &LogConfig) -> (Targets, Vec<String>) {
+    let mut invalid = Vec::new();
+    let default = config.level.parse::<LevelFilter>().unwrap_or_else(|_| {
+        invalid.push(format!("level = {}", config.level));
+        LevelFilter::INFO
+    });
+
+    let mut filter = Targets::new().with_default(default);
+    for (module, level) in &config.module_levels {
+        match level.parse::<LevelFilter>() {
+            Ok(level) => filter = filter.with_target(module.clone(), level),
+            Err(_) => invalid.push(format!("{} = {}", module, level)),
+        }
+    }
+    (filter, invalid)
+}
+
 /// 初始化日志系统
+/// 控制台与日志文件共用同一套级别过滤与脱敏，proxy 等模块通过 tracing 输出的日志也会写入文件
 pub fn init_logger() {
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_level(true)
-        .init();
-    
-    // 简单的文件日志模拟 (因缺少 tracing-appender)
-    if let Ok(log_dir) = get_log_dir() {
-        let log_file = log_dir.join("app.log");
-        let _ = fs::write(log_file, format!("Log init at {}\n", chrono::Local::now()));
-    }
-    
+    let config = crate::modules::config::load_app_config()
+        .map(|c| c.log)
+        .unwrap_or_default();
+    apply_privacy(&config);
+    let (filter, invalid) = build_filter(&config);
+    let (filter_layer, filter_handle) = reload::Layer::new(filter);
+
+    let sink = get_log_dir().and_then(|dir| LogSink::open(dir, &config)).map(Arc::new);
+    let file_layer = sink.as_ref().ok().map(|sink| FileLayer { sink: sink.clone() });
+
+    let initialized = Registry::default()
+        .with(filter_layer)
+        .with(
+            tracing_fmt::layer()
+                .with_target(false)
+                .with_thread_ids(false)
+                .with_level(true)
+                .with_writer(|| RedactingStdout),
+        )
+        .with(file_layer)
+        .try_init();
+    if initialized.is_err() {
+        // 已经初始化过（例如命令行模式下重复调用）
+        return;
+    }
+
+    let _ = FILTER_HANDLE.set(filter_handle);
+    match sink {
+        Ok(sink) => {
+            let _ = LOG_SINK.set(sink);
+        }
+        Err(e) => warn!("日志文件不可用，仅输出到控制台: {}", e),
+    }
+    for item in invalid {
+        warn!("无效的日志级别配置，已忽略: {}", item);
+    }
+
     info!("日志系统已初始化");
 }
 
+/// 按新配置更新日志级别、格式与轮转策略，无需重启
+pub fn apply_config(config: &LogConfig) {
+    apply_privacy(config);
+    let (filter, invalid) = build_filter(config);
+    if let Some(handle) = FILTER_HANDLE.get() {
+        if let Err(e) = handle.reload(filter) {
+            warn!("更新日志级别失败: {}", e);
+        }
+    }
+    if let Some(sink) = LOG_SINK.get() {
+        sink.apply_config(config);
+    }
+    for item in invalid {
+        warn!("无效的日志级别配置，已忽略: {}", item);
+    }
+}
+
 /// 清理日志缓存
 pub fn clear_logs() -> Result<(), String> {
-    let log_dir = get_log_dir()?;
-    if log_dir.exists() {
-        fs::remove_dir_all(&log_dir).map_err(|e| format!("清理日志目录失败: {}", e))?;
-        fs::create_dir_all(&log_dir).map_err(|e| format!("重建日志目录失败: {}", e))?;
-        
-        // 重建后立即写入一条初始日志，确保文件存在
-        let log_file = log_dir.join("app.log");
-        let _ = fs::write(log_file, format!("Log cleared at {}\n", chrono::Local::now()));
+    match LOG_SINK.get() {
+        Some(sink) => sink.clear()?,
+        None => reset_log_dir(&get_log_dir()?)?,
     }
+    info!("日志已清理");
     Ok(())
 }
 
-fn append_log(level: &str, message: &str) {
-    if let Ok(log_dir) = get_log_dir() {
-        let log_file = log_dir.join("app.log");
-        // 使用 append 模式打开文件，如果文件不存在则创建
-        if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(log_file) {
-            use std::io::Write;
-            let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-            let _ = writeln!(file, "[{}] [{}] {}", time, level, message);
-        }
+/// 删除并重建日志目录
+fn reset_log_dir(dir: &Path) -> Result<(), String> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|e| format!("清理日志目录失败: {}", e))?;
     }
+    fs::create_dir_all(dir).map_err(|e| format!("重建日志目录失败: {}", e))
 }
 
 /// 记录信息日志
 pub fn log_info(message: &str) {
     info!("{}", message);
-    append_log("INFO", message);
 }
 
 /// 记录警告日志
 pub fn log_warn(message: &str) {
     warn!("{}", message);
-    append_log("WARN", message);
 }
 
 /// 记录错误日志
 pub fn log_error(message: &str) {
     error!("{}", message);
-    append_log("ERROR", message);
+}
+
+/// 按天和大小轮转的日志文件
+struct LogSink {
+    dir: PathBuf,
+    json: AtomicBool,
+    state: Mutex<SinkState>,
+}
+
+struct SinkState {
+    file: Option<File>,
+    /// 当前文件已写入的字节数
+    size: u64,
+    /// 当前文件对应的日期，跨天时轮转
+    date: chrono::NaiveDate,
+    /// 0 表示不限制大小
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl LogSink {
+    fn open(dir: PathBuf, config: &LogConfig) -> Result<Self, String> {
+        let sink = Self {
+            dir,
+            json: AtomicBool::new(config.json),
+            state: Mutex::new(SinkState {
+                file: None,
+                size: 0,
+                date: chrono::Local::now().date_naive(),
+                max_bytes: config.max_file_size_mb * 1024 * 1024,
+                max_files: config.max_files,
+            }),
+        };
+
+        // 上次运行留下的日志不是今天写的，先轮转
+        let path = sink.dir.join(LOG_FILE);
+        let stale = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map(|t| chrono::DateTime::<chrono::Local>::from(t).date_naive() != chrono::Local::now().date_naive())
+            .unwrap_or(false);
+
+        let mut state = sink.lock();
+        if stale {
+            sink.rotate(&mut state)?;
+        } else {
+            sink.open_current(&mut state)?;
+        }
+        drop(state);
+        Ok(sink)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SinkState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn apply_config(&self, config: &LogConfig) {
+        self.json.store(config.json, Ordering::Relaxed);
+        let mut state = self.lock();
+        state.max_bytes = config.max_file_size_mb * 1024 * 1024;
+        state.max_files = config.max_files;
+    }
+
+    fn open_current(&self, state: &mut SinkState) -> Result<(), String> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(LOG_FILE))
+            .map_err(|e| format!("打开日志文件失败: {}", e))?;
+        state.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        state.date = chrono::Local::now().date_naive();
+        state.file = Some(file);
+        Ok(())
+    }
+
+    /// 清空日志目录并重新打开当前日志文件
+    /// 全程持有锁：先关闭文件句柄再删除 (Windows 上无法删除打开中的文件)，期间的日志写入等待锁而不会丢失
+    fn clear(&self) -> Result<(), String> {
+        let mut state = self.lock();
+        state.file = None;
+        let result = reset_log_dir(&self.dir);
+        // 清理失败也要重新打开，保证之后的日志继续写入
+        self.open_current(&mut state)?;
+        result
+    }
+
+    /// 将当前文件重命名为历史文件并清理超出保留数量的旧文件
+    fn rotate(&self, state: &mut SinkState) -> Result<(), String> {
+        state.file = None;
+        let current = self.dir.join(LOG_FILE);
+        if current.exists() {
+            let rotated = self.dir.join(format!(
+                "{}{}.log",
+                ROTATED_PREFIX,
+                chrono::Local::now().format("%Y%m%d-%H%M%S%3f")
+            ));
+            fs::rename(&current, &rotated).map_err(|e| format!("轮转日志文件失败: {}", e))?;
+        }
+        prune_rotated(&self.dir, state.max_files);
+        self.open_current(state)
+    }
+
+    fn write_line(&self, line: &str) {
+        let mut state = self.lock();
+        let len = line.len() as u64 + 1;
+        let new_day = chrono::Local::now().date_naive() != state.date;
+        let too_large = state.max_bytes > 0 && state.size > 0 && state.size + len > state.max_bytes;
+        if (new_day || too_large || state.file.is_none()) && self.rotate(&mut state).is_err() {
+            return;
+        }
+
+        if let Some(file) = state.file.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                state.size += len;
+            }
+        }
+    }
+}
+
+/// 历史日志文件，最新的在前
+fn rotated_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with(ROTATED_PREFIX) && n.ends_with(".log"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by(|a, b| b.cmp(a));
+    files
+}
+
+fn prune_rotated(dir: &Path, max_files: usize) {
+    for old in rotated_files(dir).into_iter().skip(max_files) {
+        let _ = fs::remove_file(old);
+    }
+}
+
+/// 收集事件与 span 的字段
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::String(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
+
+/// 将事件写入日志文件的 tracing layer
+/// 事件会带上所在 span 的字段（例如反代请求的 request_id）
+struct FileLayer {
+    sink: Arc<LogSink>,
+}
+
+impl<S> Layer<S> for FileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<JsonFields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut span_fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<JsonFields>() {
+                    span_fields.extend(fields.0.clone());
+                }
+            }
+        }
+
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let line = format_record(
+            self.sink.json.load(Ordering::Relaxed),
+            &chrono::Local::now(),
+            metadata.level(),
+            metadata.target(),
+            span_fields,
+            fields.0,
+        );
+        self.sink.write_line(&redact_line(&line));
+    }
+}
+
+/// 格式化一条日志
+/// 文本格式: `[时间] [级别] 消息 key=value ...`；JSON 格式为单行对象，span 字段与事件字段平铺
+fn format_record(
+    json: bool,
+    time: &chrono::DateTime<chrono::Local>,
+    level: &tracing::Level,
+    target: &str,
+    span_fields: Map<String, Value>,
+    mut fields: Map<String, Value>,
+) -> String {
+    let message = fields.remove("message").unwrap_or(Value::String(String::new()));
+
+    if json {
+        let mut record = Map::new();
+        record.insert("timestamp".to_string(), Value::String(time.to_rfc3339()));
+        record.insert("level".to_string(), Value::String(level.to_string()));
+        record.insert("target".to_string(), Value::String(target.to_string()));
+        record.insert("message".to_string(), message);
+        record.extend(span_fields);
+        record.extend(fields);
+        return Value::Object(record).to_string();
+    }
+
+    let mut line = format!(
+        "[{}] [{}] {}",
+        time.format("%Y-%m-%d %H:%M:%S"),
+        level,
+        message.as_str().map(str::to_string).unwrap_or_else(|| message.to_string())
+    );
+    for (key, value) in span_fields.iter().chain(fields.iter()) {
+        match value {
+            Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+            other => line.push_str(&format!(" {}={}", key, other)),
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::TempDir;
+
+    #[test]
+    fn test_size_rotation_keeps_retention() {
+        let dir = TempDir::new("logger");
+        let config = LogConfig { max_files: 2, ..LogConfig::default() };
+        let sink = LogSink::open(dir.to_path_buf(), &config).unwrap();
+        sink.lock().max_bytes = 64;
+
+        for i in 0..20 {
+            sink.write_line(&format!("line {:02} {}", i, "x".repeat(20)));
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        assert_eq!(rotated_files(&dir).len(), 2);
+        let current = fs::read_to_string(dir.join(LOG_FILE)).unwrap();
+        assert!(current.len() <= 64);
+        assert!(current.contains("line 19"));
+    }
+
+    #[test]
+    fn test_clear_removes_files_and_keeps_writing() {
+        let dir = TempDir::new("logger");
+        let config = LogConfig { max_files: 5, ..LogConfig::default() };
+        let sink = LogSink::open(dir.to_path_buf(), &config).unwrap();
+        sink.write_line("before");
+        sink.rotate(&mut sink.lock()).unwrap();
+        sink.write_line("rotated away");
+        assert_eq!(rotated_files(&dir).len(), 1);
+
+        sink.clear().unwrap();
+        sink.write_line("after");
+
+        assert!(rotated_files(&dir).is_empty());
+        assert_eq!(fs::read_to_string(dir.join(LOG_FILE)).unwrap(), "after\n");
+    }
+
+    #[test]
+    fn test_format_record() {
+        let time = chrono::Local::now();
+        let mut span_fields = Map::new();
+        span_fields.insert("request_id".to_string(), Value::from("abc"));
+        let mut fields = Map::new();
+        fields.insert("message".to_string(), Value::from("请求完成"));
+        fields.insert("status".to_string(), Value::from(200));
+
+        let text = format_record(false, &time, &tracing::Level::INFO, "t", span_fields.clone(), fields.clone());
+        assert!(text.ends_with("[INFO] 请求完成 request_id=abc status=200"));
+
+        let json: Value =
+            serde_json::from_str(&format_record(true, &time, &tracing::Level::WARN, "t", span_fields, fields)).unwrap();
+        assert_eq!(json["level"], "WARN");
+        assert_eq!(json["message"], "请求完成");
+        assert_eq!(json["request_id"], "abc");
+        assert_eq!(json["status"], 200);
+    }
+
+    #[test]
+    fn test_build_filter_reports_invalid_levels() {
+        let mut config = LogConfig::default();
+        config.module_levels.insert("antigravity_tools_lib::proxy".to_string(), "debug".to_string());
+        config.module_levels.insert("hyper".to_string(), "loud".to_string());
+        let (filter, invalid) = build_filter(&config);
+        assert_eq!(invalid, vec!["hyper = loud".to_string()]);
+        assert!(filter.would_enable("antigravity_tools_lib::proxy::server", &tracing::Level::DEBUG));
+        assert!(!filter.would_enable("antigravity_tools_lib::modules", &tracing::Level::DEBUG));
+    }
 }
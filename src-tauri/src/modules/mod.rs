@@ -3,12 +3,20 @@ pub mod quota;
 pub mod config;
 pub mod logger;
 pub mod db;
+pub mod db_backup;
 pub mod process;
 pub mod oauth;
 pub mod oauth_server;
 pub mod migration;
 pub mod tray;
 pub mod i18n;
+pub mod crypto;
+pub mod bundle;
+pub mod bulk_import;
+pub mod quota_refresh;
+pub mod quota_history;
+pub mod auto_switch;
+pub mod soft_switch;
 
 pub use account::*;
 pub use quota::*;
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use dashmap::DashMap;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::sync::OnceLock;
 
 // Google OAuth 配置
 const CLIENT_ID: &str = "1071006060591-tmhssin2h21lcre235vtolojh4g403ep.apps.googleusercontent.com";
@@ -8,7 +11,77 @@ const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v2/userinfo";
 
 const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// 统一的提前刷新阈值（秒）
+/// TokenManager、TokenRefresher 与桌面端 ensure_fresh_token 共用，
+/// 后台刷新周期 (5 分钟) 必须小于该值，保证 Token 不会在两次检查之间过期
+pub const REFRESH_AHEAD_SECS: i64 = 600;
+
+type RefreshFuture = Shared<BoxFuture<'static, Result<TokenResponse, OAuthError>>>;
+
+/// 正在进行中的刷新请求 (refresh_token -> 共享 Future)
+static IN_FLIGHT_REFRESHES: OnceLock<DashMap<String, RefreshFuture>> = OnceLock::new();
+
+fn in_flight_refreshes() -> &'static DashMap<String, RefreshFuture> {
+    IN_FLIGHT_REFRESHES.get_or_init(DashMap::new)
+}
+
+/// 判断 Token 是否需要刷新
+/// 
+/// # 参数
+/// - `expiry_timestamp`: Token 过期时间戳（秒）
+/// - `ahead_secs`: 提前刷新时间（秒）
+pub fn needs_refresh(expiry_timestamp: i64, ahead_secs: i64) -> bool {
+    chrono::Utc::now().timestamp() + ahead_secs >= expiry_timestamp
+}
+
+/// Token 刷新错误分类
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum OAuthError {
+    /// refresh_token 已被撤销或过期 (invalid_grant)，重试无意义，需要用户重新授权
+    #[error("refresh_token 已失效，需要重新授权: {0}")]
+    InvalidGrant(String),
+
+    /// 网络错误或 Google 服务端错误，可在下一周期重试
+    #[error("刷新请求失败: {0}")]
+    Transient(String),
+
+    /// 其他错误 (客户端配置错误、响应解析失败等)
+    #[error("刷新失败: {0}")]
+    Other(String),
+}
+
+impl OAuthError {
+    /// 是否需要用户重新授权
+    pub fn needs_reauth(&self) -> bool {
+        matches!(self, OAuthError::InvalidGrant(_))
+    }
+}
+
+// 兼容以 String 作为错误类型的调用方
+impl From<OAuthError> for String {
+    fn from(e: OAuthError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 根据 Token 端点返回的状态码和响应体对错误进行分类
+/// 
+/// Google 的错误格式: `{"error": "invalid_grant", "error_description": "Token has been expired or revoked."}`
+fn classify_refresh_error(status: reqwest::StatusCode, body: &str) -> OAuthError {
+    let error_code = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(|s| s.to_string()));
+
+    match error_code.as_deref() {
+        Some("invalid_grant") => OAuthError::InvalidGrant(body.to_string()),
+        _ if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            OAuthError::Transient(format!("({}) {}", status.as_u16(), body))
+        }
+        _ => OAuthError::Other(format!("({}) {}", status.as_u16(), body)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub access_token: String,
     pub expires_in: i64,
@@ -121,7 +194,30 @@ pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenRespon
 }
 
 /// 使用 refresh_token 刷新 access_token
-pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, String> {
+/// 
+/// 同一 refresh_token 的并发调用会合并为一次网络请求（单飞），
+/// 所有调用者等待同一个 Future 并拿到相同的结果
+pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, OAuthError> {
+    let refresh = in_flight_refreshes()
+        .entry(refresh_token.to_string())
+        .or_insert_with(|| {
+            let refresh_token = refresh_token.to_string();
+            async move { request_token_refresh(&refresh_token).await }
+                .boxed()
+                .shared()
+        })
+        .clone();
+
+    let result = refresh.clone().await;
+
+    // 仅移除自己等待的那个 Future，避免误删之后新发起的刷新
+    in_flight_refreshes().remove_if(refresh_token, |_, current| current.ptr_eq(&refresh));
+
+    result
+}
+
+/// 实际发起刷新请求
+async fn request_token_refresh(refresh_token: &str) -> Result<TokenResponse, OAuthError> {
     let client = crate::utils::http::create_client(15);
     
     let params = [
@@ -138,19 +234,24 @@ pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse,
         .form(&params)
         .send()
         .await
-        .map_err(|e| format!("刷新请求失败: {}", e))?;
+        .map_err(|e| OAuthError::Transient(e.to_string()))?;
 
-    if response.status().is_success() {
+    let status = response.status();
+    if status.is_success() {
         let token_data = response
             .json::<TokenResponse>()
             .await
-            .map_err(|e| format!("刷新数据解析失败: {}", e))?;
+            .map_err(|e| OAuthError::Other(format!("刷新数据解析失败: {}", e)))?;
         
         crate::modules::logger::log_info(&format!("Token 刷新成功！有效期: {} 秒", token_data.expires_in));
         Ok(token_data)
     } else {
         let error_text = response.text().await.unwrap_or_default();
-        Err(format!("刷新失败: {}", error_text))
+        let error = classify_refresh_error(status, &error_text);
+        if error.needs_reauth() {
+            crate::modules::logger::log_warn("refresh_token 已被撤销或过期，需要重新授权");
+        }
+        Err(error)
     }
 }
 
@@ -179,11 +280,9 @@ pub async fn get_user_info(access_token: &str) -> Result<UserInfo, String> {
 /// 返回最新的 access_token
 pub async fn ensure_fresh_token(
     current_token: &crate::models::TokenData,
-) -> Result<crate::models::TokenData, String> {
-    let now = chrono::Local::now().timestamp();
-    
-    // 如果没有过期时间，或者还有超过 5 分钟有效期，直接返回
-    if current_token.expiry_timestamp > now + 300 {
+) -> Result<crate::models::TokenData, OAuthError> {
+    // 如果距离过期还有超过 REFRESH_AHEAD_SECS 的时间，直接返回
+    if !needs_refresh(current_token.expiry_timestamp, REFRESH_AHEAD_SECS) {
         return Ok(current_token.clone());
     }
     
@@ -201,3 +300,30 @@ pub async fn ensure_fresh_token(
         None,  // session_id 会在 token_manager 中生成
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_classify_invalid_grant() {
+        let body = r#"{"error": "invalid_grant", "error_description": "Token has been expired or revoked."}"#;
+        let err = classify_refresh_error(StatusCode::BAD_REQUEST, body);
+        assert!(err.needs_reauth());
+    }
+
+    #[test]
+    fn test_classify_server_error_is_transient() {
+        let err = classify_refresh_error(StatusCode::SERVICE_UNAVAILABLE, "upstream unavailable");
+        assert!(matches!(err, OAuthError::Transient(_)));
+        assert!(!err.needs_reauth());
+    }
+
+    #[test]
+    fn test_classify_invalid_client() {
+        let body = r#"{"error": "invalid_client", "error_description": "Unauthorized"}"#;
+        let err = classify_refresh_error(StatusCode::UNAUTHORIZED, body);
+        assert!(matches!(err, OAuthError::Other(_)));
+    }
+}
@@ -101,6 +101,28 @@ fn get_antigravity_pids() -> Vec<u32> {
     pids
 }
 
+/// 获取所有 Antigravity 进程的 CPU 使用率之和（%）
+/// 需要两次采样，会阻塞约 sysinfo::MINIMUM_CPU_UPDATE_INTERVAL，请勿在异步上下文中直接调用
+pub fn antigravity_cpu_usage() -> f32 {
+    let pids: Vec<sysinfo::Pid> = get_antigravity_pids()
+        .into_iter()
+        .map(sysinfo::Pid::from_u32)
+        .collect();
+    if pids.is_empty() {
+        return 0.0;
+    }
+
+    let mut system = System::new();
+    system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&pids));
+    thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&pids));
+
+    pids.iter()
+        .filter_map(|pid| system.process(*pid))
+        .map(|process| process.cpu_usage())
+        .sum()
+}
+
 /// 关闭 Antigravity 进程
 pub fn close_antigravity(timeout_secs: u64) -> Result<(), String> {
     crate::modules::logger::log_info("正在关闭 Antigravity...");
@@ -38,7 +38,7 @@ fn create_client() -> reqwest::Client {
 }
 
 /// 获取 Project ID
-async fn fetch_project_id(access_token: &str) -> Option<String> {
+pub async fn fetch_project_id(access_token: &str) -> Option<String> {
     let client = create_client();
     let body = json!({
         "metadata": {
@@ -72,16 +72,16 @@ async fn fetch_project_id(access_token: &str) -> Option<String> {
 }
 
 /// 查询账号配额
-pub async fn fetch_quota(access_token: &str) -> crate::error::AppResult<QuotaData> {
+/// 
+/// # 参数
+/// - `access_token`: 账号的 access_token
+/// - `project_id`: 账号的 project_id（由调用方缓存，见 fetch_project_id）
+pub async fn fetch_quota(access_token: &str, project_id: Option<&str>) -> crate::error::AppResult<QuotaData> {
     use crate::error::AppError;
     crate::modules::logger::log_info("开始外部查询配额...");
     let client = create_client();
     
-    // 1. 获取 Project ID
-    let project_id = fetch_project_id(access_token).await;
-    crate::modules::logger::log_info(&format!("Project ID 获取结果: {:?}", project_id));
-    
-    // 2. 构建请求体
+    // 1. 构建请求体
     let mut payload = serde_json::Map::new();
     if let Some(pid) = project_id {
         payload.insert("project".to_string(), json!(pid));
@@ -169,16 +169,3 @@ pub async fn fetch_quota(access_token: &str) -> crate::error::AppResult<QuotaDat
     
     Err(last_error.unwrap_or_else(|| AppError::Unknown("配额查询失败".to_string())))
 }
-
-/// 批量查询所有账号配额 (备用功能)
-#[allow(dead_code)]
-pub async fn fetch_all_quotas(accounts: Vec<(String, String)>) -> Vec<(String, crate::error::AppResult<QuotaData>)> {
-    let mut results = Vec::new();
-    
-    for (account_id, access_token) in accounts {
-        let result = fetch_quota(&access_token).await;
-        results.push((account_id, result));
-    }
-    
-    results
-}
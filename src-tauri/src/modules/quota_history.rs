@@ -0,0 +1,301 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::models::QuotaData;
+use crate::modules::account;
+
+/// 历史数据目录，每个账号一个 JSONL 文件（每行一次配额快照）
+const HISTORY_DIR: &str = "quota_history";
+/// 每个账号最多保留的快照数量，超出后丢弃最早的记录
+const MAX_POINTS_PER_ACCOUNT: usize = 5000;
+
+/// 反代服务自上次快照以来为每个账号转发的请求数 (account_id -> 请求数)
+static PROXY_REQUESTS: OnceLock<DashMap<String, AtomicU64>> = OnceLock::new();
+
+fn proxy_requests() -> &'static DashMap<String, AtomicU64> {
+    PROXY_REQUESTS.get_or_init(DashMap::new)
+}
+
+/// 一次配额快照
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaHistoryPoint {
+    pub timestamp: i64,
+    /// 模型名 -> 剩余百分比
+    pub models: BTreeMap<String, i32>,
+    /// 与上一次快照之间反代服务使用该账号转发的请求数
+    #[serde(default)]
+    pub proxy_requests: u64,
+}
+
+/// 单个模型的配额预测
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaForecast {
+    pub account_id: String,
+    pub email: String,
+    pub model: String,
+    pub current_percentage: i32,
+    /// 本周期（上次重置以来）每小时消耗的百分比
+    pub burn_rate_per_hour: Option<f64>,
+    /// 每消耗 1% 配额对应的反代请求数
+    pub requests_per_percent: Option<f64>,
+    /// 按当前消耗速度预计耗尽的时间戳
+    pub exhausts_at: Option<i64>,
+    /// 下一次配额重置的时间戳
+    pub resets_at: Option<i64>,
+    /// 是否会在重置前耗尽
+    pub exhausts_before_reset: bool,
+}
+
+/// 由 burn rate 推算出的预测结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BurnEstimate {
+    burn_rate_per_hour: Option<f64>,
+    requests_per_percent: Option<f64>,
+    exhausts_at: Option<i64>,
+}
+
+/// 记录一次反代请求（由 TokenManager 在分配账号时调用）
+pub fn record_proxy_request(account_id: &str) {
+    proxy_requests()
+        .entry(account_id.to_string())
+        .or_insert_with(|| AtomicU64::new(0))
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+fn take_proxy_requests(account_id: &str) -> u64 {
+    proxy_requests()
+        .get(account_id)
+        .map(|count| count.swap(0, Ordering::Relaxed))
+        .unwrap_or(0)
+}
+
+fn history_path(account_id: &str) -> Result<PathBuf, String> {
+    let dir = account::get_data_dir()?.join(HISTORY_DIR);
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("创建配额历史目录失败: {}", e))?;
+    }
+    Ok(dir.join(format!("{}.jsonl", account_id)))
+}
+
+/// 追加一次配额快照
+///
+/// # 参数
+/// - `account_id`: 账号 ID
+/// - `quota`: 本次刷新得到的配额
+pub fn append_snapshot(account_id: &str, quota: &QuotaData) -> Result<(), String> {
+    if quota.is_forbidden || quota.models.is_empty() {
+        return Ok(());
+    }
+
+    let point = QuotaHistoryPoint {
+        timestamp: quota.last_updated,
+        models: quota.models.iter().map(|m| (m.name.clone(), m.percentage)).collect(),
+        proxy_requests: take_proxy_requests(account_id),
+    };
+    let line = serde_json::to_string(&point).map_err(|e| format!("序列化配额快照失败: {}", e))?;
+
+    let path = history_path(account_id)?;
+    let lock = crate::utils::fs::file_lock(&path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("打开配额历史文件失败: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("写入配额历史失败: {}", e))?;
+    drop(file);
+
+    // 超出保留数量时截断最早的记录
+    let points = read_points(&path)?;
+    if points.len() > MAX_POINTS_PER_ACCOUNT {
+        let mut content = String::new();
+        for point in &points[points.len() - MAX_POINTS_PER_ACCOUNT..] {
+            content.push_str(&serde_json::to_string(point).map_err(|e| format!("序列化配额快照失败: {}", e))?);
+            content.push('\n');
+        }
+        crate::utils::fs::write_atomic(&path, content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 读取历史文件，跳过无法解析的行（例如写入中途崩溃留下的半行）
+fn read_points(path: &std::path::Path) -> Result<Vec<QuotaHistoryPoint>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取配额历史失败: {}", e))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// 查询账号的配额历史
+///
+/// # 参数
+/// - `account_id`: 账号 ID
+/// - `model`: 只返回指定模型，为 None 时返回全部模型
+/// - `since`: 只返回该时间戳之后的快照
+pub fn get_history(
+    account_id: &str,
+    model: Option<&str>,
+    since: Option<i64>,
+) -> Result<Vec<QuotaHistoryPoint>, String> {
+    let path = history_path(account_id)?;
+    let lock = crate::utils::fs::file_lock(&path);
+    let points = {
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        read_points(&path)?
+    };
+
+    Ok(points
+        .into_iter()
+        .filter(|p| since.map_or(true, |since| p.timestamp >= since))
+        .filter_map(|mut p| {
+            if let Some(model) = model {
+                p.models.retain(|name, _| name == model);
+                if p.models.is_empty() {
+                    return None;
+                }
+            }
+            Some(p)
+        })
+        .collect())
+}
+
+/// 删除账号的配额历史（删除账号时调用）
+pub fn delete_history(account_id: &str) -> Result<(), String> {
+    let path = history_path(account_id)?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| format!("删除配额历史失败: {}", e))?;
+    }
+    proxy_requests().remove(account_id);
+    Ok(())
+}
+
+/// 预测账号各模型的配额耗尽时间
+///
+/// # 参数
+/// - `account_id`: 只预测指定账号，为 None 时预测全部账号
+pub fn get_forecasts(account_id: Option<&str>) -> Result<Vec<QuotaForecast>, String> {
+    let now = chrono::Utc::now().timestamp();
+    let mut forecasts = Vec::new();
+
+    for acc in account::list_accounts()? {
+        if account_id.is_some_and(|id| id != acc.id) {
+            continue;
+        }
+        let quota = match &acc.quota {
+            Some(quota) if !quota.is_forbidden => quota,
+            _ => continue,
+        };
+        let history = get_history(&acc.id, None, None)?;
+
+        for model in &quota.models {
+            let series: Vec<(i64, i32, u64)> = history
+                .iter()
+                .filter_map(|p| p.models.get(&model.name).map(|pct| (p.timestamp, *pct, p.proxy_requests)))
+                .collect();
+            let estimate = estimate_burn(&series);
+            let resets_at = chrono::DateTime::parse_from_rfc3339(&model.reset_time)
+                .ok()
+                .map(|t| t.timestamp())
+                .filter(|t| *t > now);
+
+            let exhausts_before_reset = match (estimate.exhausts_at, resets_at) {
+                (Some(exhausts_at), Some(resets_at)) => exhausts_at < resets_at,
+                (Some(_), None) => true,
+                _ => false,
+            };
+
+            forecasts.push(QuotaForecast {
+                account_id: acc.id.clone(),
+                email: acc.email.clone(),
+                model: model.name.clone(),
+                current_percentage: model.percentage,
+                burn_rate_per_hour: estimate.burn_rate_per_hour,
+                requests_per_percent: estimate.requests_per_percent,
+                exhausts_at: estimate.exhausts_at,
+                resets_at,
+                exhausts_before_reset,
+            });
+        }
+    }
+    Ok(forecasts)
+}
+
+/// 根据单个模型的时间序列 (时间戳, 剩余百分比, 反代请求数) 估算消耗速度
+/// 只使用最近一次重置（百分比回升）之后的数据
+fn estimate_burn(series: &[(i64, i32, u64)]) -> BurnEstimate {
+    let none = BurnEstimate {
+        burn_rate_per_hour: None,
+        requests_per_percent: None,
+        exhausts_at: None,
+    };
+
+    let start = series
+        .windows(2)
+        .rposition(|w| w[1].1 > w[0].1)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let cycle = &series[start..];
+    let (first, last) = match (cycle.first(), cycle.last()) {
+        (Some(first), Some(last)) if last.0 > first.0 => (first, last),
+        _ => return none,
+    };
+
+    let consumed = (first.1 - last.1) as f64;
+    let hours = (last.0 - first.0) as f64 / 3600.0;
+    let burn_rate = consumed / hours;
+    if burn_rate <= 0.0 {
+        return BurnEstimate { burn_rate_per_hour: Some(0.0), ..none };
+    }
+
+    // 第一个快照的请求数属于上一个周期
+    let requests: u64 = cycle.iter().skip(1).map(|p| p.2).sum();
+    let requests_per_percent = (requests > 0).then(|| requests as f64 / consumed);
+    let exhausts_at = last.0 + (last.1.max(0) as f64 / burn_rate * 3600.0) as i64;
+
+    BurnEstimate {
+        burn_rate_per_hour: Some(burn_rate),
+        requests_per_percent,
+        exhausts_at: Some(exhausts_at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_burn_linear_consumption() {
+        // 每小时消耗 10%，共 20 次请求
+        let series = [(0, 100, 0), (3600, 90, 10), (7200, 80, 10)];
+        let estimate = estimate_burn(&series);
+        assert_eq!(estimate.burn_rate_per_hour, Some(10.0));
+        assert_eq!(estimate.requests_per_percent, Some(1.0));
+        assert_eq!(estimate.exhausts_at, Some(7200 + 8 * 3600));
+    }
+
+    #[test]
+    fn test_estimate_burn_uses_only_current_cycle() {
+        // 第三个点之后发生了重置，只使用重置之后的数据
+        let series = [(0, 50, 0), (3600, 10, 0), (7200, 100, 0), (10800, 95, 0)];
+        let estimate = estimate_burn(&series);
+        assert_eq!(estimate.burn_rate_per_hour, Some(5.0));
+        assert_eq!(estimate.requests_per_percent, None);
+    }
+
+    #[test]
+    fn test_estimate_burn_needs_two_points() {
+        assert_eq!(estimate_burn(&[(0, 100, 0)]).burn_rate_per_hour, None);
+        assert_eq!(estimate_burn(&[]).exhausts_at, None);
+        assert_eq!(estimate_burn(&[(0, 80, 0), (3600, 80, 5)]).exhausts_at, None);
+    }
+}
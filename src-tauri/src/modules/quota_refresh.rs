@@ -0,0 +1,392 @@
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tauri::Emitter;
+use tokio_util::sync::CancellationToken;
+
+use crate::models::{Account, AppConfig};
+use crate::modules;
+
+/// 每次配额刷新完成后发送，负载为 QuotaUpdatedPayload
+pub const QUOTA_UPDATED_EVENT: &str = "quota://updated";
+/// 模型配额降到阈值以下时发送，负载为 QuotaAlert
+pub const QUOTA_LOW_EVENT: &str = "quota://low";
+/// 模型配额重置后回到阈值以上时发送，负载为 QuotaAlert
+pub const QUOTA_RECOVERED_EVENT: &str = "quota://recovered";
+/// 批量刷新中每个账号完成时发送，负载为 QuotaProgressPayload
+pub const QUOTA_PROGRESS_EVENT: &str = "quota://progress";
+
+/// 批量刷新时同时查询的账号数量上限
+const MAX_CONCURRENT_REFRESHES: usize = 8;
+
+/// 同一时刻只允许一次批量刷新（定时任务与手动刷新互斥）
+static REFRESH_LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+
+/// 批量刷新统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshStats {
+    pub total: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub details: Vec<String>,
+}
+
+/// 单个模型的配额变化
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaChange {
+    pub account_id: String,
+    pub email: String,
+    pub model: String,
+    /// 刷新前的剩余百分比，首次获取时为 None
+    pub old_percentage: Option<i32>,
+    pub new_percentage: i32,
+}
+
+/// 配额阈值提醒
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaAlert {
+    pub account_id: String,
+    pub email: String,
+    pub model: String,
+    pub percentage: i32,
+    pub threshold: i32,
+}
+
+/// quota://progress 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaProgressPayload {
+    pub account_id: String,
+    pub email: String,
+    /// 已完成的账号数量（含本账号）
+    pub completed: usize,
+    /// 本次需要刷新的账号总数
+    pub total: usize,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// quota://updated 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaUpdatedPayload {
+    pub stats: RefreshStats,
+    pub changes: Vec<QuotaChange>,
+}
+
+/// 两次刷新之间的配额差异
+#[derive(Debug, Default)]
+struct QuotaDiff {
+    changes: Vec<QuotaChange>,
+    low: Vec<QuotaAlert>,
+    recovered: Vec<QuotaAlert>,
+}
+
+/// 刷新账号配额，并向前端发送配额变化与阈值提醒
+///
+/// # 参数
+/// - `app`: 用于发送事件，无界面模式下为 None（只写日志）
+/// - `group`: 只刷新指定分组内的账号，为 None 时刷新全部账号
+pub async fn refresh_quotas(app: Option<&tauri::AppHandle>, group: Option<&str>) -> Result<RefreshStats, String> {
+    let _guard = REFRESH_LOCK
+        .get_or_init(|| tokio::sync::Mutex::new(()))
+        .lock()
+        .await;
+
+    modules::logger::log_info("开始批量刷新所有账号配额");
+    let accounts: Vec<Account> = modules::list_accounts()?
+        .into_iter()
+        .filter(|a| group.map_or(true, |g| a.in_group(g)))
+        .collect();
+    let before = accounts.clone();
+
+    let pending: Vec<Account> = accounts
+        .into_iter()
+        .filter(|account| {
+            if account.disabled {
+                modules::logger::log_info(&format!("  - Skipping {} (Disabled)", account.email));
+                return false;
+            }
+            if account.quota.as_ref().is_some_and(|q| q.is_forbidden) {
+                modules::logger::log_info(&format!("  - Skipping {} (Forbidden)", account.email));
+                return false;
+            }
+            true
+        })
+        .collect();
+    let total = pending.len();
+
+    // 每个账号只读写自己的账号文件（均有文件锁保护），可以安全地并发刷新
+    let mut results = stream::iter(pending)
+        .map(|account| refresh_one(app, account))
+        .buffer_unordered(MAX_CONCURRENT_REFRESHES);
+
+    let mut stats = RefreshStats::default();
+    let mut completed = 0;
+    while let Some((account, result)) = results.next().await {
+        completed += 1;
+        match &result {
+            Ok(()) => stats.success += 1,
+            Err(msg) => {
+                stats.failed += 1;
+                stats.details.push(msg.clone());
+            }
+        }
+        if let Some(app) = app {
+            let _ = app.emit(QUOTA_PROGRESS_EVENT, QuotaProgressPayload {
+                account_id: account.id,
+                email: account.email,
+                completed,
+                total,
+                success: result.is_ok(),
+                error: result.err(),
+            });
+        }
+    }
+    stats.total = stats.success + stats.failed;
+
+    modules::logger::log_info(&format!("批量刷新完成: {} 成功, {} 失败", stats.success, stats.failed));
+
+    let after = modules::list_accounts()?;
+    let threshold = modules::load_app_config().unwrap_or_default().quota_alert_threshold;
+    let diff = diff_quotas(&before, &after, threshold);
+    emit_quota_diff(app, &stats, diff);
+
+    Ok(stats)
+}
+
+/// 刷新单个账号的配额并保存
+async fn refresh_one(app: Option<&tauri::AppHandle>, mut account: Account) -> (Account, Result<(), String>) {
+    modules::logger::log_info(&format!("  - Processing {}", account.email));
+
+    let result = match modules::account::fetch_quota_with_retry(&mut account).await {
+        Ok(quota) => {
+            // 保存配额
+            modules::update_account_quota(&account.id, quota)
+                .map(|_| modules::logger::log_info(&format!("    ✅ {} Success", account.email)))
+                .map_err(|e| format!("Account {}: Save quota failed - {}", account.email, e))
+        },
+        Err(e) => {
+            if let (true, Some(app)) = (account.disabled, app) {
+                modules::account::emit_needs_reauth(app, &account.id, &account.email, &e.to_string());
+            }
+            Err(format!("Account {}: Fetch quota failed - {}", account.email, e))
+        }
+    };
+
+    if let Err(msg) = &result {
+        modules::logger::log_error(msg);
+    }
+    (account, result)
+}
+
+/// 记录并发送配额变化与阈值提醒事件
+fn emit_quota_diff(app: Option<&tauri::AppHandle>, stats: &RefreshStats, diff: QuotaDiff) {
+    for alert in &diff.low {
+        modules::logger::log_warn(&format!(
+            "账号 {} 的模型 {} 剩余配额 {}%，低于阈值 {}%",
+            alert.email, alert.model, alert.percentage, alert.threshold
+        ));
+        if let Some(app) = app {
+            let _ = app.emit(QUOTA_LOW_EVENT, alert);
+        }
+    }
+    for alert in &diff.recovered {
+        modules::logger::log_info(&format!(
+            "账号 {} 的模型 {} 配额已恢复至 {}%",
+            alert.email, alert.model, alert.percentage
+        ));
+        if let Some(app) = app {
+            let _ = app.emit(QUOTA_RECOVERED_EVENT, alert);
+        }
+    }
+
+    if let Some(app) = app {
+        let _ = app.emit(QUOTA_UPDATED_EVENT, QuotaUpdatedPayload {
+            stats: stats.clone(),
+            changes: diff.changes,
+        });
+        crate::modules::tray::update_tray_menus(app);
+    }
+}
+
+/// 对比刷新前后的账号配额
+/// 只有百分比发生变化的模型才计入 changes；
+/// 从阈值及以上降到阈值以下记为 low，从阈值以下回到阈值及以上记为 recovered
+fn diff_quotas(before: &[Account], after: &[Account], threshold: i32) -> QuotaDiff {
+    let previous: HashMap<(&str, &str), i32> = before
+        .iter()
+        .flat_map(|a| {
+            a.quota.iter().flat_map(move |q| {
+                q.models.iter().map(move |m| ((a.id.as_str(), m.name.as_str()), m.percentage))
+            })
+        })
+        .collect();
+
+    let mut diff = QuotaDiff::default();
+    for account in after {
+        let quota = match &account.quota {
+            Some(quota) => quota,
+            None => continue,
+        };
+        for model in &quota.models {
+            let old = previous.get(&(account.id.as_str(), model.name.as_str())).copied();
+            if old == Some(model.percentage) {
+                continue;
+            }
+
+            diff.changes.push(QuotaChange {
+                account_id: account.id.clone(),
+                email: account.email.clone(),
+                model: model.name.clone(),
+                old_percentage: old,
+                new_percentage: model.percentage,
+            });
+
+            let alert = || QuotaAlert {
+                account_id: account.id.clone(),
+                email: account.email.clone(),
+                model: model.name.clone(),
+                percentage: model.percentage,
+                threshold,
+            };
+            match old {
+                Some(old) if old >= threshold && model.percentage < threshold => diff.low.push(alert()),
+                None if model.percentage < threshold => diff.low.push(alert()),
+                Some(old) if old < threshold && model.percentage >= threshold => diff.recovered.push(alert()),
+                _ => {}
+            }
+        }
+    }
+    diff
+}
+
+/// 配额定时刷新器
+/// 按 AppConfig.refresh_interval 周期刷新所有账号配额
+pub struct QuotaScheduler {
+    /// 刷新间隔（分钟）
+    interval_minutes: u64,
+    /// 取消信号
+    cancel_token: CancellationToken,
+}
+
+impl QuotaScheduler {
+    /// 创建新的配额定时刷新器
+    ///
+    /// # 参数
+    /// - `interval_minutes`: 刷新间隔（分钟）
+    pub fn new(interval_minutes: u64) -> Self {
+        Self {
+            interval_minutes,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    /// 启动后台刷新任务
+    pub fn start(&self, app: tauri::AppHandle) {
+        let cancel_token = self.cancel_token.clone();
+        let interval = std::time::Duration::from_secs(self.interval_minutes * 60);
+        let interval_minutes = self.interval_minutes;
+
+        tauri::async_runtime::spawn(async move {
+            modules::logger::log_info(&format!("配额定时刷新任务已启动 (间隔: {} 分钟)", interval_minutes));
+
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => {
+                        modules::logger::log_info("配额定时刷新任务已停止");
+                        break;
+                    }
+                    _ = tokio::time::sleep(interval) => {
+                        if let Err(e) = refresh_quotas(Some(&app), None).await {
+                            modules::logger::log_error(&format!("定时刷新配额失败: {}", e));
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// 停止后台刷新任务
+    pub fn stop(&self) {
+        self.cancel_token.cancel();
+    }
+}
+
+/// 配额定时刷新器的全局状态
+pub struct QuotaSchedulerState {
+    scheduler: Mutex<Option<QuotaScheduler>>,
+}
+
+impl QuotaSchedulerState {
+    pub fn new() -> Self {
+        Self {
+            scheduler: Mutex::new(None),
+        }
+    }
+
+    /// 根据配置启动、重启或停止定时刷新
+    pub fn apply_config(&self, app: &tauri::AppHandle, config: &AppConfig) {
+        let mut scheduler = self.scheduler.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = scheduler.take() {
+            existing.stop();
+        }
+
+        if config.auto_refresh && config.refresh_interval > 0 {
+            let new_scheduler = QuotaScheduler::new(config.refresh_interval as u64);
+            new_scheduler.start(app.clone());
+            *scheduler = Some(new_scheduler);
+        }
+    }
+}
+
+impl Default for QuotaSchedulerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    fn account_with_quota(id: &str, models: &[(&str, i32)]) -> Account {
+        let token = TokenData::new("a".to_string(), "r".to_string(), 3600, None, None, None);
+        let mut account = Account::new(id.to_string(), format!("{}@example.com", id), token);
+        let mut quota = QuotaData::new();
+        for (name, percentage) in models {
+            quota.add_model(name.to_string(), *percentage, String::new());
+        }
+        account.quota = Some(quota);
+        account
+    }
+
+    #[test]
+    fn test_diff_reports_only_changed_models() {
+        let before = vec![account_with_quota("a", &[("m1", 80), ("m2", 50)])];
+        let after = vec![account_with_quota("a", &[("m1", 80), ("m2", 40)])];
+        let diff = diff_quotas(&before, &after, 20);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].model, "m2");
+        assert_eq!(diff.changes[0].old_percentage, Some(50));
+        assert!(diff.low.is_empty() && diff.recovered.is_empty());
+    }
+
+    #[test]
+    fn test_diff_detects_threshold_crossings() {
+        let before = vec![
+            account_with_quota("a", &[("m1", 30)]),
+            account_with_quota("b", &[("m1", 0)]),
+        ];
+        let after = vec![
+            account_with_quota("a", &[("m1", 10)]),
+            account_with_quota("b", &[("m1", 100)]),
+            account_with_quota("c", &[("m1", 5)]),
+        ];
+        let diff = diff_quotas(&before, &after, 20);
+        let low: Vec<_> = diff.low.iter().map(|a| a.account_id.as_str()).collect();
+        let recovered: Vec<_> = diff.recovered.iter().map(|a| a.account_id.as_str()).collect();
+        assert_eq!(low, vec!["a", "c"]);
+        assert_eq!(recovered, vec!["b"]);
+    }
+}
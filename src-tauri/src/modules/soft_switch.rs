@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::modules::{self, account, db, db_backup, process};
+
+/// 排队中的软切换，Antigravity 退出后注入
+const PENDING_SWITCH_FILE: &str = "pending_switch.json";
+/// 检查 Antigravity 是否已退出的间隔
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// 是否已有后台任务在等待 Antigravity 退出
+static WATCHER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// 排队中的软切换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSwitch {
+    pub account_id: String,
+    pub queued_at: i64,
+}
+
+fn pending_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join(PENDING_SWITCH_FILE))
+}
+
+/// 将切换排队到 Antigravity 下次启动前
+///
+/// Antigravity 运行时会把内存中的登录状态写回 state.vscdb，直接注入的 Token 在其退出时会被覆盖，
+/// 因此只记录目标账号，由后台任务在 Antigravity 退出后注入，下次启动即使用新账号。
+/// 重复排队时以最后一次为准
+pub fn queue_switch(account_id: &str) -> Result<(), String> {
+    let pending = PendingSwitch {
+        account_id: account_id.to_string(),
+        queued_at: chrono::Utc::now().timestamp(),
+    };
+    crate::utils::fs::write_json(&pending_path()?, &pending)
+        .map_err(|e| format!("保存待切换账号失败: {}", e))?;
+    modules::logger::log_info(&format!("Antigravity 正在运行，账号切换已排队，将在其退出后生效: {}", account_id));
+
+    ensure_watcher();
+    Ok(())
+}
+
+/// 读取排队中的软切换
+pub fn pending_switch() -> Option<PendingSwitch> {
+    let path = pending_path().ok()?;
+    if !path.exists() {
+        return None;
+    }
+    crate::utils::fs::read_json(&path).ok()
+}
+
+/// 取消排队中的软切换（例如随后执行了重启切换）
+pub fn clear_pending_switch() {
+    if let Ok(path) = pending_path() {
+        let _ = std::fs::remove_file(crate::utils::fs::backup_path(&path));
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                modules::logger::log_warn(&format!("清除待切换账号失败: {}", e));
+            }
+        }
+    }
+}
+
+/// 启动时恢复上次未完成的软切换
+pub fn resume_pending_switch() {
+    if pending_switch().is_some() {
+        ensure_watcher();
+    }
+}
+
+/// 启动后台任务，等待 Antigravity 退出后注入排队的账号
+fn ensure_watcher() {
+    if WATCHER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(pending) = pending_switch() {
+            if process::is_antigravity_running() {
+                tokio::time::sleep(WATCH_INTERVAL).await;
+                continue;
+            }
+
+            if let Err(e) = apply_pending(&pending).await {
+                modules::logger::log_error(&format!("应用排队的账号切换失败: {}", e));
+            }
+            // 期间可能又排队了新的切换，只清除已处理的这一次
+            if pending_switch().is_some_and(|p| p.queued_at == pending.queued_at && p.account_id == pending.account_id) {
+                clear_pending_switch();
+            }
+        }
+        WATCHER_RUNNING.store(false, Ordering::SeqCst);
+        // 退出前刚好又排队了新的切换
+        resume_pending_switch();
+    });
+}
+
+/// Antigravity 已退出，注入排队账号的 Token
+async fn apply_pending(pending: &PendingSwitch) -> Result<(), String> {
+    let account = account::load_account(&pending.account_id)?;
+    if account.disabled {
+        return Err(format!("账号已禁用: {}", account.email));
+    }
+
+    // 排队期间 Token 可能已过期
+    let fresh_token = modules::oauth::ensure_fresh_token(&account.token).await
+        .map_err(|e| format!("Token 刷新失败: {}", e))?;
+    let account = if fresh_token.access_token != account.token.access_token {
+        account::update_account(&account.id, |a| a.token = fresh_token)?
+    } else {
+        account
+    };
+
+    let db_path = db::get_db_path()?;
+    if db::read_access_token(&db_path)?.as_deref() == Some(account.token.access_token.as_str()) {
+        return Ok(());
+    }
+    db_backup::inject_token_with_backup(
+        &db_path,
+        &account.token.access_token,
+        &account.token.refresh_token,
+        account.token.expiry_timestamp,
+    )?;
+    modules::logger::log_info(&format!("Antigravity 已退出，已注入排队的账号: {}", account.email));
+    Ok(())
+}
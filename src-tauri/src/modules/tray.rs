@@ -29,6 +29,7 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
     // 快捷操作区
     let switch_next = MenuItem::with_id(app, "switch_next", &texts.switch_next, true, None::<&str>)?;
     let refresh_curr = MenuItem::with_id(app, "refresh_curr", &texts.refresh_current, true, None::<&str>)?;
+    let toggle_proxy = MenuItem::with_id(app, "toggle_proxy", &texts.proxy_exclude, true, None::<&str>)?;
     
     // 系统功能
     let show_i = MenuItem::with_id(app, "show", &texts.show_window, true, None::<&str>)?;
@@ -45,6 +46,7 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
         &sep1,
         &switch_next,
         &refresh_curr,
+        &toggle_proxy,
         &sep2,
         &show_i,
         &sep3,
@@ -96,10 +98,34 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
                         }
                     });
                 }
+                "toggle_proxy" => {
+                    tauri::async_runtime::spawn(async move {
+                        if let Ok(Some(account_id)) = modules::get_current_account_id() {
+                            if let Ok(account) = modules::load_account(&account_id) {
+                                match modules::account::set_account_proxy_enabled(&account_id, !account.proxy_enabled) {
+                                    Ok(updated) => {
+                                        // 热更新运行中的反代服务
+                                        let state = app_handle.state::<crate::commands::proxy::ProxyServiceState>();
+                                        if let Err(e) = crate::commands::proxy::sync_account_to_proxy(&state, &account_id).await {
+                                            modules::logger::log_error(&format!("同步账号到反代服务失败: {}", e));
+                                        }
+                                        let _ = app_handle.emit("tray://account-updated", updated.id.clone());
+                                        update_tray_menus(&app_handle);
+                                    },
+                                    Err(e) => {
+                                        modules::logger::log_error(&format!("托盘切换反代状态失败: {}", e));
+                                    }
+                                }
+                            }
+                        }
+                    });
+                }
                 "switch_next" => {
                     tauri::async_runtime::spawn(async move {
-                         // 1. 获取所有账号
-                         if let Ok(accounts) = modules::list_accounts() {
+                         // 1. 获取所有账号（跳过已禁用的账号）
+                         if let Ok(accounts) = modules::list_accounts().map(|list| {
+                             list.into_iter().filter(|a| !a.disabled).collect::<Vec<_>>()
+                         }) {
                              if accounts.is_empty() { return; }
                              
                              let current_id = modules::get_current_account_id().unwrap_or(None);
@@ -170,10 +196,12 @@ pub fn update_tray_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
          
          let mut menu_lines = Vec::new();
          let mut user_text = format!("{}: {}", texts.current, texts.no_account);
+         let mut proxy_enabled = true;
 
          if let Some(id) = current {
              if let Ok(account) = modules::load_account(&id) {
                  user_text = format!("{}: {}", texts.current, account.email);
+                 proxy_enabled = account.proxy_enabled;
                  
                  if let Some(q) = account.quota {
                      if q.is_forbidden {
@@ -221,11 +249,13 @@ pub fn update_tray_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
          
          let switch_next = MenuItem::with_id(&app_clone, "switch_next", &texts.switch_next, true, None::<&str>);
          let refresh_curr = MenuItem::with_id(&app_clone, "refresh_curr", &texts.refresh_current, true, None::<&str>);
+         let toggle_text = if proxy_enabled { &texts.proxy_exclude } else { &texts.proxy_include };
+         let toggle_proxy = MenuItem::with_id(&app_clone, "toggle_proxy", toggle_text, true, None::<&str>);
          
          let show_i = MenuItem::with_id(&app_clone, "show", &texts.show_window, true, None::<&str>);
          let quit_i = MenuItem::with_id(&app_clone, "quit", &texts.quit, true, None::<&str>);
          
-         if let (Ok(i_u), Ok(s_n), Ok(r_c), Ok(s), Ok(q)) = (info_user, switch_next, refresh_curr, show_i, quit_i) {
+         if let (Ok(i_u), Ok(s_n), Ok(r_c), Ok(t_p), Ok(s), Ok(q)) = (info_user, switch_next, refresh_curr, toggle_proxy, show_i, quit_i) {
              let sep1 = PredefinedMenuItem::separator(&app_clone).ok();
              let sep2 = PredefinedMenuItem::separator(&app_clone).ok();
              let sep3 = PredefinedMenuItem::separator(&app_clone).ok();
@@ -239,6 +269,7 @@ pub fn update_tray_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
              if let Some(ref s) = sep1 { items.push(s); }
              items.push(&s_n);
              items.push(&r_c);
+             items.push(&t_p);
              if let Some(ref s) = sep2 { items.push(s); }
              items.push(&s);
              if let Some(ref s) = sep3 { items.push(s); }
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use crate::modules::account::{subscribe_account_changes, AccountChange};
+use crate::proxy::token_manager::TokenManager;
+
+/// 账号目录监听器
+/// 负责在反代服务运行期间增量同步 TokenManager：
+/// - 订阅 modules::account 的变更通知，实时新增、更新、移除账号
+/// - 定期全量扫描账号目录，兜底处理外部直接修改文件的情况
+pub struct AccountWatcher {
+    /// 全量扫描间隔（毫秒）- 默认 30 秒
+    rescan_interval_ms: u64,
+    /// 取消信号
+    cancel_token: CancellationToken,
+}
+
+impl AccountWatcher {
+    /// 创建新的账号监听器
+    ///
+    /// # 参数
+    /// - `rescan_interval_ms`: 全量扫描间隔（毫秒）
+    pub fn new(rescan_interval_ms: u64) -> Self {
+        Self {
+            rescan_interval_ms,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    /// 使用默认配置创建监听器
+    /// - 全量扫描间隔: 30 秒
+    pub fn with_defaults() -> Self {
+        Self::new(30 * 1000)
+    }
+
+    /// 启动后台监听任务
+    ///
+    /// # 参数
+    /// - `token_manager`: TokenManager 的 Arc 引用
+    pub fn start(&self, token_manager: Arc<TokenManager>) {
+        let cancel_token = self.cancel_token.clone();
+        let interval_ms = self.rescan_interval_ms;
+        // 在启动时订阅，避免错过任务调度前发生的变更
+        let mut changes = subscribe_account_changes();
+
+        tokio::spawn(async move {
+            tracing::info!("账号目录监听任务已启动 (全量扫描间隔: {}ms)", interval_ms);
+
+            let mut rescan = tokio::time::interval(tokio::time::Duration::from_millis(interval_ms));
+            // 跳过 interval 的首次立即触发（启动时已加载过账号）
+            rescan.tick().await;
+
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => {
+                        tracing::info!("账号目录监听任务已停止");
+                        break;
+                    }
+                    change = changes.recv() => {
+                        match change {
+                            Ok(change) => Self::apply_change(&token_manager, change).await,
+                            Err(RecvError::Lagged(skipped)) => {
+                                tracing::warn!("账号变更通知积压，丢失 {} 条，执行全量同步", skipped);
+                                Self::rescan(&token_manager).await;
+                            }
+                            Err(RecvError::Closed) => {
+                                // 发送端是全局静态变量，正常情况下不会关闭
+                                tracing::warn!("账号变更通知通道已关闭，账号目录监听任务退出");
+                                break;
+                            }
+                        }
+                    }
+                    _ = rescan.tick() => {
+                        Self::rescan(&token_manager).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// 停止监听任务
+    pub fn stop(&self) {
+        tracing::info!("正在停止账号目录监听任务...");
+        self.cancel_token.cancel();
+    }
+
+    /// 应用单条账号变更
+    async fn apply_change(token_manager: &TokenManager, change: AccountChange) {
+        let account_id = match &change {
+            AccountChange::Upserted(id) | AccountChange::Deleted(id) => id,
+        };
+
+        match token_manager.reload_account(account_id).await {
+            Ok(active) => {
+                tracing::debug!("账号变更已同步: {:?} (参与轮换: {})", change, active);
+            }
+            Err(e) => {
+                tracing::warn!("同步账号变更 {:?} 失败: {}", change, e);
+            }
+        }
+    }
+
+    /// 全量同步账号目录
+    async fn rescan(token_manager: &TokenManager) {
+        if let Err(e) = token_manager.load_accounts().await {
+            tracing::warn!("全量同步账号目录失败: {}", e);
+        }
+    }
+}
+
+impl Default for AccountWatcher {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
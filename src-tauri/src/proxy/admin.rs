@@ -0,0 +1,224 @@
+//! 管理接口 (/admin/*)
+//! 供无界面部署和远程运维使用，使用独立于 API 密钥的管理密钥鉴权
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::models::Account;
+use crate::modules;
+use crate::proxy::{auth, events};
+use crate::proxy::server::AppState;
+
+/// 账号健康状况
+#[derive(Debug, Serialize)]
+struct AdminAccount {
+    id: String,
+    email: String,
+    name: Option<String>,
+    disabled: bool,
+    disabled_reason: Option<String>,
+    proxy_enabled: bool,
+    groups: Vec<String>,
+    /// 是否正在参与反代轮换
+    in_rotation: bool,
+    /// 账号被禁止访问 (403)
+    forbidden: bool,
+    /// 所有模型中最低的剩余百分比
+    min_quota_percentage: Option<i32>,
+    quota_updated_at: Option<i64>,
+    token_expires_at: i64,
+}
+
+impl AdminAccount {
+    fn new(account: Account, in_rotation: bool) -> Self {
+        let quota = account.quota.as_ref();
+        Self {
+            in_rotation,
+            forbidden: quota.is_some_and(|q| q.is_forbidden),
+            min_quota_percentage: quota.and_then(|q| q.models.iter().map(|m| m.percentage).min()),
+            quota_updated_at: quota.map(|q| q.last_updated),
+            token_expires_at: account.token.expiry_timestamp,
+            id: account.id,
+            email: account.email,
+            name: account.name,
+            disabled: account.disabled,
+            disabled_reason: account.disabled_reason,
+            proxy_enabled: account.proxy_enabled,
+            groups: account.groups,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupQuery {
+    group: Option<String>,
+}
+
+/// 构建管理接口路由，所有路由都需要管理密钥
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/accounts", get(list_accounts))
+        .route("/accounts/reload", post(reload_accounts))
+        .route("/accounts/:id/enable", post(enable_account))
+        .route("/accounts/:id/disable", post(disable_account))
+        .route("/quota/refresh", post(refresh_quota))
+        .route("/stats", get(get_stats))
+        .route("/mapping", get(get_mapping).put(update_mapping))
+        .route("/events", get(event_stream))
+        .route_layer(middleware::from_fn_with_state(state, require_admin_key))
+}
+
+fn admin_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(serde_json::json!({
+            "error": {
+                "message": message.into(),
+                "type": "admin_error"
+            }
+        })),
+    )
+        .into_response()
+}
+
+async fn require_admin_key(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let expected = state.admin_key.read().await.clone();
+    if expected.is_empty() {
+        return admin_error(StatusCode::FORBIDDEN, "管理接口未启用，请先在配置中设置 admin_key");
+    }
+    match auth::provided_key(request.headers(), auth::ADMIN_KEY_HEADER) {
+        Some(key) if auth::keys_match(&expected, key) => next.run(request).await,
+        _ => admin_error(StatusCode::UNAUTHORIZED, "管理密钥无效"),
+    }
+}
+
+async fn list_accounts(State(state): State<AppState>) -> Response {
+    let accounts = match modules::list_accounts() {
+        Ok(accounts) => accounts,
+        Err(e) => return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let in_rotation: Vec<String> = state
+        .token_manager
+        .get_all_tokens()
+        .into_iter()
+        .map(|t| t.account_id)
+        .collect();
+
+    let accounts: Vec<AdminAccount> = accounts
+        .into_iter()
+        .map(|a| {
+            let active = in_rotation.contains(&a.id);
+            AdminAccount::new(a, active)
+        })
+        .collect();
+    Json(accounts).into_response()
+}
+
+async fn set_disabled(state: &AppState, account_id: &str, disabled: bool) -> Response {
+    let account = match modules::account::set_account_disabled(account_id, disabled) {
+        Ok(account) => account,
+        Err(e) => return admin_error(StatusCode::NOT_FOUND, e),
+    };
+    let in_rotation = match state.token_manager.reload_account(account_id).await {
+        Ok(active) => active,
+        Err(e) => return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    if let Some(app) = state.token_manager.app_handle() {
+        modules::tray::update_tray_menus(app);
+    }
+    Json(AdminAccount::new(account, in_rotation)).into_response()
+}
+
+async fn enable_account(State(state): State<AppState>, Path(account_id): Path<String>) -> Response {
+    set_disabled(&state, &account_id, false).await
+}
+
+async fn disable_account(State(state): State<AppState>, Path(account_id): Path<String>) -> Response {
+    set_disabled(&state, &account_id, true).await
+}
+
+async fn reload_accounts(State(state): State<AppState>) -> Response {
+    match state.token_manager.load_accounts().await {
+        Ok(active_accounts) => Json(serde_json::json!({ "active_accounts": active_accounts })).into_response(),
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, format!("重新加载账号失败: {}", e)),
+    }
+}
+
+async fn refresh_quota(State(state): State<AppState>, Query(query): Query<GroupQuery>) -> Response {
+    let app = state.token_manager.app_handle();
+    match modules::quota_refresh::refresh_quotas(app, query.group.as_deref()).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn get_stats(State(state): State<AppState>) -> Response {
+    let stats = state.stats.snapshot();
+    Json(serde_json::json!({
+        "total_requests": stats.total_requests,
+        "success_count": stats.success_count,
+        "error_count": stats.error_count,
+        "active_accounts": state.token_manager.len(),
+    }))
+    .into_response()
+}
+
+async fn get_mapping(State(state): State<AppState>) -> Response {
+    Json(state.anthropic_mapping.read().await.clone()).into_response()
+}
+
+/// 更新模型映射表，立即生效并保存到配置
+async fn update_mapping(
+    State(state): State<AppState>,
+    Json(mapping): Json<HashMap<String, String>>,
+) -> Response {
+    *state.anthropic_mapping.write().await = mapping.clone();
+    tracing::info!("模型映射已通过管理接口更新");
+
+    match modules::config::save_anthropic_mapping(mapping.clone()) {
+        Ok(()) => Json(mapping).into_response(),
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, format!("保存模型映射失败: {}", e)),
+    }
+}
+
+/// 以 SSE 推送请求生命周期事件，事件名为事件类型
+/// 订阅方处理过慢导致事件被丢弃时，发送一条 lagged 事件，数据为丢弃的条数
+async fn event_stream(State(state): State<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = events::subscribe();
+    let shutdown = state.shutdown.clone();
+
+    let stream = async_stream::stream! {
+        loop {
+            let received = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                received = rx.recv() => received,
+            };
+            match received {
+                Ok(event) => {
+                    if let Ok(data) = serde_json::to_string(&event) {
+                        yield Ok(Event::default().event(event.kind.name()).data(data));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    yield Ok(Event::default().event("lagged").data(skipped.to_string()));
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,184 @@
+//! 反代接口鉴权
+//! /v1/* 使用 API 密钥（主密钥或 api_key_groups 中限定分组的密钥），/admin/* 使用独立的管理密钥
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+
+use crate::proxy::server::AppState;
+use crate::proxy::upstream_error::ClientProtocol;
+
+/// API 密钥请求头
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// 管理密钥请求头
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
+/// 指定账号分组的请求头，只对主密钥生效
+const ACCOUNT_GROUP_HEADER: &str = "x-account-group";
+
+/// 通过鉴权的 API 密钥类型，由 require_api_key 写入请求扩展
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeyScope {
+    /// 主密钥 (api_key)
+    Primary,
+    /// 限定账号分组的密钥
+    Group(String),
+}
+
+impl ApiKeyScope {
+    /// 请求可使用的账号分组，None 表示不限制
+    /// 分组密钥固定使用配置的分组，忽略 x-account-group；主密钥可通过该请求头自选分组
+    pub fn account_group(&self, headers: &HeaderMap) -> Option<String> {
+        match self {
+            ApiKeyScope::Group(group) => Some(group.clone()),
+            ApiKeyScope::Primary => headers
+                .get(ACCOUNT_GROUP_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(|g| g.trim())
+                .filter(|g| !g.is_empty())
+                .map(|g| g.to_string()),
+        }
+    }
+}
+
+/// 从指定请求头或 Authorization: Bearer 中读取密钥，指定请求头优先
+pub fn provided_key<'a>(headers: &'a HeaderMap, header: &str) -> Option<&'a str> {
+    headers
+        .get(header)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(axum::http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .map(|k| k.trim())
+}
+
+/// 常量时间比较，避免通过响应时间猜测密钥；未配置的空密钥不匹配任何输入
+pub fn keys_match(expected: &str, provided: &str) -> bool {
+    !expected.is_empty()
+        && expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// 监听地址是否只接受本机连接
+pub fn is_loopback_address(bind_address: &str) -> bool {
+    bind_address.eq_ignore_ascii_case("localhost")
+        || bind_address
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// 判断密钥是主密钥还是分组密钥，都不是时返回 None
+fn key_scope(api_key: &str, api_key_groups: &HashMap<String, String>, provided: &str) -> Option<ApiKeyScope> {
+    if keys_match(api_key, provided) {
+        return Some(ApiKeyScope::Primary);
+    }
+    api_key_groups
+        .iter()
+        .find(|(key, _)| keys_match(key, provided))
+        .map(|(_, group)| ApiKeyScope::Group(group.clone()))
+}
+
+/// /v1/* 鉴权中间件，接受 x-api-key 或 Authorization: Bearer
+pub async fn require_api_key(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let scope = match provided_key(request.headers(), API_KEY_HEADER) {
+        Some(key) => {
+            let api_key = state.api_key.read().await;
+            let api_key_groups = state.api_key_groups.read().await;
+            key_scope(&api_key, &api_key_groups, key)
+        }
+        None => None,
+    };
+
+    let Some(scope) = scope else {
+        let protocol = if request.uri().path().starts_with("/v1/messages") {
+            ClientProtocol::Anthropic
+        } else {
+            ClientProtocol::OpenAI
+        };
+        return protocol.error_response(StatusCode::UNAUTHORIZED, "invalid_request_error", "API 密钥无效");
+    };
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
+
+/// /metrics 鉴权中间件，监听非本机地址时使用
+/// 指标包含账号 ID、冷却与 Token 过期时间，只接受管理密钥或主 API 密钥（x-admin-key、x-api-key 或 Authorization: Bearer）
+pub async fn require_metrics_key(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let authorized = {
+        let admin_key = state.admin_key.read().await;
+        let api_key = state.api_key.read().await;
+        let headers = request.headers();
+        [ADMIN_KEY_HEADER, API_KEY_HEADER]
+            .iter()
+            .filter_map(|header| provided_key(headers, header))
+            .any(|key| keys_match(&admin_key, key) || keys_match(&api_key, key))
+    };
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "unauthorized\n").into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provided_key_and_match() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(provided_key(&headers, API_KEY_HEADER), None);
+
+        headers.insert("authorization", "Bearer sk-secret".parse().unwrap());
+        assert_eq!(provided_key(&headers, API_KEY_HEADER), Some("sk-secret"));
+
+        // 指定请求头优先于 Authorization
+        headers.insert(API_KEY_HEADER, "other".parse().unwrap());
+        assert_eq!(provided_key(&headers, API_KEY_HEADER), Some("other"));
+
+        assert!(keys_match("sk-secret", "sk-secret"));
+        assert!(!keys_match("sk-secret", "sk-secreT"));
+        assert!(!keys_match("sk-secret", "sk"));
+        assert!(!keys_match("", ""));
+    }
+
+    #[test]
+    fn test_key_scope() {
+        let groups = HashMap::from([("sk-team".to_string(), "team".to_string())]);
+        assert_eq!(key_scope("sk-main", &groups, "sk-main"), Some(ApiKeyScope::Primary));
+        assert_eq!(key_scope("sk-main", &groups, "sk-team"), Some(ApiKeyScope::Group("team".to_string())));
+        assert_eq!(key_scope("sk-main", &groups, "sk-other"), None);
+        // 主密钥为空时只接受分组密钥
+        assert_eq!(key_scope("", &groups, ""), None);
+    }
+
+    #[test]
+    fn test_is_loopback_address() {
+        assert!(is_loopback_address("127.0.0.1"));
+        assert!(is_loopback_address("::1"));
+        assert!(is_loopback_address("localhost"));
+        assert!(!is_loopback_address("0.0.0.0"));
+        assert!(!is_loopback_address("192.168.1.10"));
+    }
+
+    #[test]
+    fn test_account_group() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ApiKeyScope::Primary.account_group(&headers), None);
+
+        headers.insert(ACCOUNT_GROUP_HEADER, "other".parse().unwrap());
+        assert_eq!(ApiKeyScope::Primary.account_group(&headers), Some("other".to_string()));
+        // 分组密钥不能通过请求头切换到其他分组
+        let team = ApiKeyScope::Group("team".to_string());
+        assert_eq!(team.account_group(&headers), Some("team".to_string()));
+    }
+}
@@ -1,6 +1,8 @@
 use serde::Serialize;
 use serde_json::Value;
 
+use crate::proxy::pipeline::{GenerateChunk, Usage};
+
 /// 流式状态机块类型
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum BlockType {
@@ -63,49 +65,29 @@ impl ClaudeStreamConverter {
         }
     }
 
-    /// 处理 Gemini chunk 并返回 Anthropic 事件列表
+    /// 处理上游分片并返回 Anthropic 事件列表
     /// 
     /// # 参数
-    /// - `json_chunk`: Gemini 格式的 JSON chunk
+    /// - `chunk`: 协议无关的上游分片
     /// 
     /// # 返回
     /// - Vec<StreamEvent>: Anthropic SSE 事件列表
-    pub fn process_chunk(&mut self, json_chunk: &Value) -> Vec<StreamEvent> {
+    pub fn process(&mut self, chunk: &GenerateChunk) -> Vec<StreamEvent> {
         let mut events = Vec::new();
 
-        // 安全检查：空 choices
-        let choices = match json_chunk.get("choices").and_then(|c| c.as_array()) {
-            Some(arr) if !arr.is_empty() => arr,
-            _ => return events,
-        };
-
-        let choice = &choices[0];
-        let delta = &choice["delta"];
-        
-        let delta_content = delta.get("content")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-            
-        // 检查 thinking 字段
-        let is_thought = delta.get("thought")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-            
-        let thought_signature = delta.get("thoughtSignature")
-            .and_then(|v| v.as_str());
-
-        // 检查 function_call 字段
-        let function_call = delta.get("functionCall");
+        let delta_content = chunk.text.as_str();
+        let is_thought = chunk.is_thought;
+        let thought_signature = chunk.thought_signature.as_deref();
 
         // 更新 has_content 状态
-        if !delta_content.is_empty() || is_thought || thought_signature.is_some() || function_call.is_some() {
+        if chunk.has_content() {
             self.has_content = true;
         }
 
         // --- 状态机逻辑 ---
 
         // 1. 处理 function_call（工具调用）
-        if let Some(fc) = function_call {
+        if let Some(fc) = &chunk.function_call {
             // 先处理 trailing_signature（来自之前的空 text）
             if self.trailing_signature.is_some() {
                 events.extend(self.emit_trailing_signature_block());
@@ -146,9 +128,8 @@ impl ClaudeStreamConverter {
         }
 
         // 5. 处理 Stop Reason（如果存在）
-        if let Some(reason_str) = choice.get("finish_reason").and_then(|v| v.as_str()) {
-            let usage = json_chunk.get("usage");
-            events.extend(self.emit_finish(reason_str, usage));
+        if let Some(stop_reason) = chunk.anthropic_stop_reason() {
+            events.extend(self.emit_finish(stop_reason, chunk.usage.as_ref()));
         }
 
         events
@@ -429,12 +410,12 @@ impl ClaudeStreamConverter {
     /// 发送结束事件
     /// 
     /// # 参数
-    /// - `finish_reason`: 结束原因
+    /// - `stop_reason`: Anthropic 格式的结束原因，使用过工具时改为 tool_use
     /// - `usage`: 可选的使用量信息
     /// 
     /// # 返回
     /// - Vec<StreamEvent>: 生成的事件列表
-    pub fn emit_finish(&mut self, finish_reason: &str, usage: Option<&Value>) -> Vec<StreamEvent> {
+    pub fn emit_finish(&mut self, stop_reason: &str, usage: Option<&Usage>) -> Vec<StreamEvent> {
         let mut events = Vec::new();
 
         // 关闭最后一个块
@@ -446,22 +427,10 @@ impl ClaudeStreamConverter {
         }
 
         // 确定 stop_reason
-        let stop_reason = if self.used_tool {
-            "tool_use"
-        } else {
-            match finish_reason {
-                "length" | "MAX_TOKENS" => "max_tokens",
-                "stop" | "STOP" => "end_turn",
-                "tool_calls" | "function_call" => "tool_use",
-                _ => "end_turn"
-            }
-        };
+        let stop_reason = if self.used_tool { "tool_use" } else { stop_reason };
 
         // 提取 usage 信息
-        let output_tokens = usage
-            .and_then(|u| u.get("completion_tokens").or(u.get("output_tokens")))
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
+        let output_tokens = usage.map(|u| u.output_tokens).unwrap_or(0);
 
         // 发送 message_delta
         events.push(self.create_event("message_delta", serde_json::json!({
@@ -577,55 +546,39 @@ mod tests {
         ]
     }
 
-    // 生成随机 finish_reason
+    // 生成随机 Gemini finishReason
     fn arb_finish_reason() -> impl Strategy<Value = String> {
         prop_oneof![
-            Just("stop".to_string()),
             Just("STOP".to_string()),
-            Just("length".to_string()),
             Just("MAX_TOKENS".to_string()),
-            Just("tool_calls".to_string()),
+            Just("SAFETY".to_string()),
+            Just("MALFORMED_FUNCTION_CALL".to_string()),
+            Just("OTHER".to_string()),
         ]
     }
 
     // 生成 thinking chunk
-    fn arb_thinking_chunk(text: String, signature: Option<String>) -> serde_json::Value {
-        let mut delta = serde_json::json!({
-            "content": text,
-            "thought": true
-        });
-        if let Some(sig) = signature {
-            delta["thoughtSignature"] = serde_json::Value::String(sig);
+    fn arb_thinking_chunk(text: String, signature: Option<String>) -> GenerateChunk {
+        GenerateChunk {
+            text,
+            is_thought: true,
+            thought_signature: signature,
+            ..Default::default()
         }
-        serde_json::json!({
-            "choices": [{
-                "delta": delta
-            }]
-        })
     }
 
     // 生成 text chunk
-    fn arb_text_chunk(text: String) -> serde_json::Value {
-        serde_json::json!({
-            "choices": [{
-                "delta": {
-                    "content": text
-                }
-            }]
-        })
+    fn arb_text_chunk(text: String) -> GenerateChunk {
+        GenerateChunk { text, ..Default::default() }
     }
 
     // 生成 finish chunk
-    fn arb_finish_chunk(reason: String) -> serde_json::Value {
-        serde_json::json!({
-            "choices": [{
-                "delta": {},
-                "finish_reason": reason
-            }],
-            "usage": {
-                "completion_tokens": 100
-            }
-        })
+    fn arb_finish_chunk(reason: String) -> GenerateChunk {
+        GenerateChunk {
+            finish_reason: Some(reason),
+            usage: Some(Usage { input_tokens: 10, output_tokens: 100 }),
+            ..Default::default()
+        }
     }
 
     // 检查事件序列是否符合规范
@@ -705,11 +658,11 @@ mod tests {
 
             // 处理 thinking chunk
             let chunk = arb_thinking_chunk(text, signature);
-            all_events.extend(converter.process_chunk(&chunk));
+            all_events.extend(converter.process(&chunk));
 
             // 处理 finish chunk
             let finish_chunk = arb_finish_chunk(finish_reason);
-            all_events.extend(converter.process_chunk(&finish_chunk));
+            all_events.extend(converter.process(&finish_chunk));
 
             // 验证事件序列
             prop_assert!(
@@ -731,11 +684,11 @@ mod tests {
 
             // 处理 text chunk
             let chunk = arb_text_chunk(text);
-            all_events.extend(converter.process_chunk(&chunk));
+            all_events.extend(converter.process(&chunk));
 
             // 处理 finish chunk
             let finish_chunk = arb_finish_chunk(finish_reason);
-            all_events.extend(converter.process_chunk(&finish_chunk));
+            all_events.extend(converter.process(&finish_chunk));
 
             // 验证事件序列
             prop_assert!(
@@ -759,15 +712,15 @@ mod tests {
 
             // 处理 thinking chunk
             let thinking_chunk = arb_thinking_chunk(thinking_text, signature);
-            all_events.extend(converter.process_chunk(&thinking_chunk));
+            all_events.extend(converter.process(&thinking_chunk));
 
             // 处理 text chunk
             let text_chunk = arb_text_chunk(text);
-            all_events.extend(converter.process_chunk(&text_chunk));
+            all_events.extend(converter.process(&text_chunk));
 
             // 处理 finish chunk
             let finish_chunk = arb_finish_chunk(finish_reason);
-            all_events.extend(converter.process_chunk(&finish_chunk));
+            all_events.extend(converter.process(&finish_chunk));
 
             // 验证事件序列
             prop_assert!(
@@ -792,22 +745,16 @@ mod tests {
     }
 
     // 生成 function_call chunk
-    fn arb_function_call_chunk(name: String, signature: Option<String>) -> serde_json::Value {
-        let mut delta = serde_json::json!({
-            "functionCall": {
+    fn arb_function_call_chunk(name: String, signature: Option<String>) -> GenerateChunk {
+        GenerateChunk {
+            function_call: Some(serde_json::json!({
                 "name": name,
                 "args": {"key": "value"},
                 "id": format!("call_{}", name)
-            }
-        });
-        if let Some(sig) = signature {
-            delta["thoughtSignature"] = serde_json::Value::String(sig);
+            })),
+            thought_signature: signature,
+            ..Default::default()
         }
-        serde_json::json!({
-            "choices": [{
-                "delta": delta
-            }]
-        })
     }
 
     // 从事件中提取 content_block 类型
@@ -840,7 +787,7 @@ mod tests {
             
             // 处理 thinking chunk
             let chunk = arb_thinking_chunk(text, signature);
-            let events = converter.process_chunk(&chunk);
+            let events = converter.process(&chunk);
 
             // 验证生成的是 thinking 类型的块
             let block_type = extract_block_type(&events);
@@ -859,7 +806,7 @@ mod tests {
             
             // 处理 text chunk
             let chunk = arb_text_chunk(text);
-            let events = converter.process_chunk(&chunk);
+            let events = converter.process(&chunk);
 
             // 验证生成的是 text 类型的块
             let block_type = extract_block_type(&events);
@@ -881,7 +828,7 @@ mod tests {
             
             // 处理 function_call chunk
             let chunk = arb_function_call_chunk(name, signature);
-            let events = converter.process_chunk(&chunk);
+            let events = converter.process(&chunk);
 
             // 验证生成的是 tool_use 类型的块
             let block_type = extract_block_type(&events);
@@ -900,11 +847,11 @@ mod tests {
             
             // 处理 text chunk
             let text_chunk = arb_text_chunk("test".to_string());
-            converter.process_chunk(&text_chunk);
+            converter.process(&text_chunk);
 
             // 处理 finish chunk
             let finish_chunk = arb_finish_chunk(finish_reason.clone());
-            let events = converter.process_chunk(&finish_chunk);
+            let events = converter.process(&finish_chunk);
 
             // 找到 message_delta 事件并验证 stop_reason
             let message_delta = events.iter().find(|e| e.event == "message_delta");
@@ -920,9 +867,7 @@ mod tests {
 
             // 验证 stop_reason 映射正确
             let expected = match finish_reason.as_str() {
-                "length" | "MAX_TOKENS" => "max_tokens",
-                "stop" | "STOP" => "end_turn",
-                "tool_calls" | "function_call" => "tool_use",
+                "MAX_TOKENS" => "max_tokens",
                 _ => "end_turn"
             };
             prop_assert_eq!(stop_reason, expected, "stop_reason should be correctly mapped");
@@ -939,14 +884,14 @@ mod tests {
             
             // 处理 function_call chunk
             let chunk = arb_function_call_chunk(name, signature);
-            converter.process_chunk(&chunk);
+            converter.process(&chunk);
 
             // 验证 used_tool 标志被设置
             prop_assert!(converter.used_tool, "used_tool flag should be set after function_call");
 
             // 处理 finish chunk
-            let finish_chunk = arb_finish_chunk("stop".to_string());
-            let events = converter.process_chunk(&finish_chunk);
+            let finish_chunk = arb_finish_chunk("STOP".to_string());
+            let events = converter.process(&finish_chunk);
 
             // 验证 stop_reason 是 tool_use
             let message_delta = events.iter().find(|e| e.event == "message_delta");
@@ -981,11 +926,11 @@ mod tests {
 
             // 处理带签名的 thinking chunk
             let chunk = arb_thinking_chunk(text, Some(signature.clone()));
-            all_events.extend(converter.process_chunk(&chunk));
+            all_events.extend(converter.process(&chunk));
 
             // 处理 finish chunk（会触发 end_block）
-            let finish_chunk = arb_finish_chunk("stop".to_string());
-            all_events.extend(converter.process_chunk(&finish_chunk));
+            let finish_chunk = arb_finish_chunk("STOP".to_string());
+            all_events.extend(converter.process(&finish_chunk));
 
             // 验证有 signature_delta 事件
             let has_signature_delta = all_events.iter().any(|e| {
@@ -1014,19 +959,15 @@ mod tests {
             let mut all_events = Vec::new();
 
             // 处理空 text 带签名的 chunk（trailing signature）
-            let chunk = serde_json::json!({
-                "choices": [{
-                    "delta": {
-                        "content": "",
-                        "thoughtSignature": signature
-                    }
-                }]
-            });
-            all_events.extend(converter.process_chunk(&chunk));
+            let chunk = GenerateChunk {
+                thought_signature: Some(signature),
+                ..Default::default()
+            };
+            all_events.extend(converter.process(&chunk));
 
             // 处理 finish chunk
-            let finish_chunk = arb_finish_chunk("stop".to_string());
-            all_events.extend(converter.process_chunk(&finish_chunk));
+            let finish_chunk = arb_finish_chunk("STOP".to_string());
+            all_events.extend(converter.process(&finish_chunk));
 
             // 验证有空 thinking 块承载签名
             let mut found_empty_thinking = false;
@@ -9,9 +9,17 @@ pub struct ProxyConfig {
     
     /// 监听端口
     pub port: u16,
+
+    /// 监听地址，无界面部署在服务器或容器中时可设为 0.0.0.0
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
     
-    /// API 密钥
+    /// API 密钥，/v1/* 请求需通过 x-api-key 或 Authorization: Bearer 携带该密钥或 api_key_groups 中的密钥
     pub api_key: String,
+
+    /// 管理接口 (/admin/*) 密钥，为空时不开放管理接口
+    #[serde(default)]
+    pub admin_key: String,
     
 
     /// 是否自动启动
@@ -28,6 +36,61 @@ pub struct ProxyConfig {
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
+
+    /// 按 API 密钥限定账号分组 (key: API 密钥, value: 账号分组)
+    /// 使用这些密钥的请求只会从对应分组中选择账号
+    #[serde(default)]
+    pub api_key_groups: std::collections::HashMap<String, String>,
+
+    /// 请求失败时的重试策略
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+/// 重试策略，OpenAI 与 Anthropic 接口共用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// 单个请求最多使用的账号次数（含首次），0 表示与分组内账号数相同
+    #[serde(default)]
+    pub max_attempts: usize,
+
+    /// 同一账号上限流短延迟等待的最大次数，超过后轮换账号
+    #[serde(default = "default_max_same_account_waits")]
+    pub max_same_account_waits: u32,
+
+    /// 上游建议的重试延迟不超过该值(毫秒)时在同一账号等待，否则轮换账号
+    #[serde(default = "default_short_delay_threshold_ms")]
+    pub short_delay_threshold_ms: u64,
+
+    /// 轮换账号前的退避基数(毫秒)，第 n 次轮换等待 base * 2^(n-1)，0 表示立即轮换
+    #[serde(default)]
+    pub backoff_base_ms: u64,
+
+    /// 退避等待上限(毫秒)
+    #[serde(default = "default_backoff_max_ms")]
+    pub backoff_max_ms: u64,
+
+    /// 退避抖动比例 (0~1)，实际等待在 [1 - jitter, 1 + jitter] 倍之间随机
+    #[serde(default = "default_backoff_jitter")]
+    pub backoff_jitter: f64,
+
+    /// 单个请求重试的总时限(秒)，超过后不再等待或轮换，0 表示不限制
+    #[serde(default)]
+    pub deadline_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 0,
+            max_same_account_waits: default_max_same_account_waits(),
+            short_delay_threshold_ms: default_short_delay_threshold_ms(),
+            backoff_base_ms: 0,
+            backoff_max_ms: default_backoff_max_ms(),
+            backoff_jitter: default_backoff_jitter(),
+            deadline_secs: 0,
+        }
+    }
 }
 
 /// 上游代理配置
@@ -44,11 +107,15 @@ impl Default for ProxyConfig {
         Self {
             enabled: false,
             port: 8045,
+            bind_address: default_bind_address(),
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
+            admin_key: String::new(),
             auto_start: false,
             anthropic_mapping: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
             upstream_proxy: UpstreamProxyConfig::default(),
+            api_key_groups: std::collections::HashMap::new(),
+            retry: RetryPolicy::default(),
         }
     }
 }
@@ -56,3 +123,23 @@ impl Default for ProxyConfig {
 fn default_request_timeout() -> u64 {
     120  // 默认 120 秒,原来 60 秒太短
 }
+
+fn default_bind_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_max_same_account_waits() -> u32 {
+    3
+}
+
+fn default_short_delay_threshold_ms() -> u64 {
+    5000
+}
+
+fn default_backoff_max_ms() -> u64 {
+    10_000
+}
+
+fn default_backoff_jitter() -> f64 {
+    0.2
+}
@@ -1,5 +1,5 @@
 use serde_json::{json, Value};
-use crate::proxy::converter::AnthropicChatRequest;
+use crate::proxy::converter::{AnthropicChatRequest, OpenAIChatRequest};
 
 /// 默认 thinking budget (API 限制最大值 < 8192)
 const DEFAULT_THINKING_BUDGET: i32 = 8191;
@@ -158,6 +158,90 @@ pub fn build_generation_config(request: &AnthropicChatRequest, mapped_model: &st
     config
 }
 
+/// 画图模型名前缀，上游只接受不带后缀的模型名
+const IMAGE_MODEL: &str = "gemini-3-pro-image";
+
+/// 将 OpenAI 请求的模型名转换为上游模型名
+/// 
+/// 画图模型的后缀 (如 gemini-3-pro-image-16x9-4k) 只用于解析图片配置，需要剥离
+pub fn openai_upstream_model(model_name: &str) -> String {
+    if model_name.contains(IMAGE_MODEL) {
+        IMAGE_MODEL.to_string()
+    } else {
+        model_name.to_string()
+    }
+}
+
+/// 解析画图模型的 imageConfig
+/// 
+/// 宽高比优先级：extra 参数 (aspectRatio) > size 参数 > 模型名后缀 > 默认 1:1
+/// 4K 优先级：quality 参数 > 模型名后缀 (-4k / -hd)，extra 参数 imageSize 为 4K 或 hd 时也启用
+fn build_image_config(request: &OpenAIChatRequest) -> Value {
+    let model_name = &request.model;
+    let model_suffix_ar = if model_name.contains("-16x9") { Some("16:9") }
+        else if model_name.contains("-9x16") { Some("9:16") }
+        else if model_name.contains("-4x3") { Some("4:3") }
+        else if model_name.contains("-3x4") { Some("3:4") }
+        else if model_name.contains("-1x1") { Some("1:1") }
+        else { None };
+    let model_suffix_4k = model_name.contains("-4k") || model_name.contains("-hd");
+
+    let extra_ar = request.extra.as_ref()
+        .and_then(|m| m.get("aspectRatio").or(m.get("aspect_ratio")))
+        .and_then(|v| v.as_str());
+    let extra_size = request.extra.as_ref()
+        .and_then(|m| m.get("imageSize").or(m.get("image_size")))
+        .and_then(|v| v.as_str());
+
+    let aspect_ratio = if let Some(ar) = extra_ar {
+        ar
+    } else {
+        match request.size.as_deref() {
+            Some("1024x1792") => "9:16",
+            Some("1792x1024") => "16:9",
+            Some("768x1024") => "3:4",
+            Some("1024x768") => "4:3",
+            Some("1024x1024") => "1:1",
+            Some(_) => "1:1", // 未知尺寸回退到 1:1
+            None => model_suffix_ar.unwrap_or("1:1"),
+        }
+    };
+
+    let is_hd = match request.quality.as_deref() {
+        Some("hd") => true,
+        Some(_) => false,
+        None => model_suffix_4k,
+    };
+
+    let mut image_config = json!({ "aspectRatio": aspect_ratio });
+    if is_hd || extra_size == Some("4K") || extra_size == Some("hd") {
+        image_config["imageSize"] = json!("4K");
+    }
+    image_config
+}
+
+/// 构建 OpenAI 请求的 generationConfig
+/// 
+/// # 参数
+/// - `request`: OpenAI 请求
+/// 
+/// # 返回
+/// - generationConfig JSON 对象，画图模型会注入 imageConfig
+pub fn build_openai_generation_config(request: &OpenAIChatRequest) -> Value {
+    let mut config = json!({
+        "temperature": request.temperature.unwrap_or(1.0),
+        "topP": request.top_p.unwrap_or(0.95),
+        "maxOutputTokens": request.max_tokens.unwrap_or(8096),
+        "candidateCount": 1
+    });
+
+    if request.model.contains(IMAGE_MODEL) {
+        config["imageConfig"] = build_image_config(request);
+    }
+
+    config
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -26,22 +26,6 @@ pub enum MessageContent {
 }
 
 impl MessageContent {
-    /// 获取文本内容的预览
-    pub fn preview(&self) -> String {
-        match self {
-            MessageContent::Text(s) => if s.chars().count() > 200 { format!("{}...", s.chars().take(200).collect::<String>()) } else { s.clone() },
-            MessageContent::Array(parts) => {
-                let mut s = String::new();
-                for part in parts {
-                    if let ContentPart::Text { text } = part {
-                        s.push_str(text);
-                    }
-                }
-                if s.chars().count() > 200 { format!("{}...", s.chars().take(200).collect::<String>()) } else { s }
-            }
-        }
-    }
-
     /// 获取完整文本内容
     pub fn text(&self) -> String {
          match self {
@@ -462,9 +446,13 @@ pub fn convert_openai_to_gemini_contents(messages: &Vec<OpenAIMessage>) -> Vec<G
     let mut pending_images: Vec<GeminiInlineData> = Vec::new();
 
     for (i, msg) in messages.iter().enumerate() {
-        // Debug: 查看消息内容预览
-        let preview = msg.content.preview();
-        tracing::info!("Msg[{}][{}] content={:?}", i, msg.role, preview);
+        // Debug: 查看消息内容预览（按日志内容记录级别脱敏）
+        tracing::debug!(
+            "Msg[{}][{}] content={:?}",
+            i,
+            msg.role,
+            crate::modules::logger::content_preview(&msg.content.text(), 200)
+        );
 
         // 角色映射
         let role = match msg.role.as_str() {
@@ -0,0 +1,115 @@
+//! 反代请求生命周期事件
+//! 通过进程级广播通道发布，由 Tauri 命令转发到前端，无界面部署时可通过 /admin/events 以 SSE 订阅
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+use crate::modules::logger;
+
+/// 转发到前端的事件名，负载为 ProxyEvent
+pub const PROXY_EVENT: &str = "proxy://event";
+
+/// 广播通道容量，订阅方处理不过来时会丢弃最旧的事件
+const CHANNEL_CAPACITY: usize = 256;
+
+static EVENTS: OnceLock<broadcast::Sender<ProxyEvent>> = OnceLock::new();
+
+fn sender() -> &'static broadcast::Sender<ProxyEvent> {
+    EVENTS.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+/// 一条请求生命周期事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyEvent {
+    /// 与响应头 x-request-id 及日志中的 request_id 一致
+    pub request_id: String,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub kind: ProxyEventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProxyEventKind {
+    /// 收到客户端请求
+    Started { protocol: &'static str, model: String },
+    /// 选定本次尝试使用的账号
+    AccountChosen { account_id: String, email: String, attempt: usize },
+    /// 本次尝试失败，等待后重试同一账号或轮换到下一个账号
+    Retry { email: String, reason: String, delay_ms: Option<u64> },
+    /// 请求成功
+    Completed { status: u16, duration_ms: u64 },
+    /// 请求最终失败
+    Failed { status: u16, error: String, duration_ms: u64 },
+}
+
+impl ProxyEventKind {
+    /// 事件类型名，与序列化后的 type 字段相同
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProxyEventKind::Started { .. } => "started",
+            ProxyEventKind::AccountChosen { .. } => "account_chosen",
+            ProxyEventKind::Retry { .. } => "retry",
+            ProxyEventKind::Completed { .. } => "completed",
+            ProxyEventKind::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// 订阅请求事件
+pub fn subscribe() -> broadcast::Receiver<ProxyEvent> {
+    sender().subscribe()
+}
+
+/// 发布请求事件，没有订阅者时直接丢弃
+/// 错误信息与重试原因会先按日志规则脱敏
+pub fn publish(request_id: &str, kind: ProxyEventKind) {
+    let kind = match kind {
+        ProxyEventKind::Retry { email, reason, delay_ms } => ProxyEventKind::Retry {
+            email,
+            reason: logger::redact_line(&reason),
+            delay_ms,
+        },
+        ProxyEventKind::Failed { status, error, duration_ms } => ProxyEventKind::Failed {
+            status,
+            error: logger::redact_line(&error),
+            duration_ms,
+        },
+        other => other,
+    };
+    let _ = sender().send(ProxyEvent {
+        request_id: request_id.to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        kind,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers() {
+        let mut rx = subscribe();
+        publish("req-1", ProxyEventKind::Retry {
+            email: "a@example.com".to_string(),
+            reason: "Bearer ya29.secret-token 429".to_string(),
+            delay_ms: Some(500),
+        });
+
+        // 其他测试可能同时发布事件，只检查本测试的事件
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if event.request_id == "req-1" {
+                break event;
+            }
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "retry");
+        assert_eq!(json["delay_ms"], 500);
+        assert!(!json["reason"].as_str().unwrap().contains("secret-token"));
+        assert_eq!(event.kind.name(), "retry");
+    }
+}
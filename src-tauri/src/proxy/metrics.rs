@@ -0,0 +1,333 @@
+//! Prometheus 指标
+//! 计数器为进程级全局状态，反代服务重启后继续累计；账号与签名缓存等状态类指标在抓取时实时生成
+
+use dashmap::DashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::proxy::token_manager::ProxyToken;
+use crate::proxy::{SignatureManager, TokenManager};
+
+/// OpenAI 兼容接口 (/v1/chat/completions)
+pub const PROTOCOL_OPENAI: &str = "openai";
+/// Anthropic 兼容接口 (/v1/messages)
+pub const PROTOCOL_ANTHROPIC: &str = "anthropic";
+
+/// Token 刷新成功
+pub const REFRESH_SUCCESS: &str = "success";
+/// Token 刷新失败（网络或上游错误，可重试）
+pub const REFRESH_FAILURE: &str = "failure";
+/// refresh_token 已被撤销，账号需要重新授权
+pub const REFRESH_REVOKED: &str = "revoked";
+
+/// 上游延迟直方图的桶上限（秒）
+const LATENCY_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::default)
+}
+
+/// 累积直方图
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bucket, le) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if secs <= le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// (协议, 模型, 状态码) -> 请求数
+    requests: DashMap<(String, String, u16), AtomicU64>,
+    /// 协议 -> 上游延迟
+    upstream_latency: DashMap<String, Histogram>,
+    /// 协议 -> 同一账号的等待重试次数
+    retries: DashMap<String, AtomicU64>,
+    /// 协议 -> 失败后轮换账号的次数
+    rotations: DashMap<String, AtomicU64>,
+    /// 刷新结果 -> 次数
+    token_refreshes: DashMap<&'static str, AtomicU64>,
+    /// 账号 ID -> 上游限流给出的冷却结束时间 (Unix 毫秒)
+    account_cooldowns: DashMap<String, i64>,
+}
+
+fn increment<K: std::hash::Hash + Eq>(map: &DashMap<K, AtomicU64>, key: K) {
+    map.entry(key)
+        .or_insert_with(|| AtomicU64::new(0))
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// 不在已知模型列表中的模型统一使用的标签
+pub const MODEL_OTHER: &str = "other";
+
+/// 请求指标的模型标签
+/// 模型名由客户端任意指定，未知模型归为 "other"，避免标签数量随请求无限增长
+fn model_label(model: &str) -> &str {
+    if crate::proxy::model_mapper::is_known_model(model) {
+        model
+    } else {
+        MODEL_OTHER
+    }
+}
+
+/// 记录一次已完成的客户端请求
+pub fn record_request(protocol: &str, model: &str, status: u16) {
+    increment(&metrics().requests, (protocol.to_string(), model_label(model).to_string(), status));
+}
+
+/// 记录一次上游调用的耗时（流式请求为收到首个分片的时间）
+pub fn observe_upstream_latency(protocol: &str, elapsed: Duration) {
+    metrics()
+        .upstream_latency
+        .entry(protocol.to_string())
+        .or_default()
+        .observe(elapsed);
+}
+
+/// 记录一次同一账号的等待重试
+pub fn record_retry(protocol: &str) {
+    increment(&metrics().retries, protocol.to_string());
+}
+
+/// 记录一次轮换到下一个账号
+pub fn record_rotation(protocol: &str) {
+    increment(&metrics().rotations, protocol.to_string());
+}
+
+/// 记录上游对账号的限流冷却时间 (retryDelay)，未给出延迟时不记录
+pub fn record_account_cooldown(account_id: &str, retry_delay_ms: Option<u64>) {
+    if let Some(delay_ms) = retry_delay_ms {
+        let until = chrono::Utc::now().timestamp_millis() + delay_ms as i64;
+        metrics().account_cooldowns.insert(account_id.to_string(), until);
+    }
+}
+
+/// 账号剩余的限流冷却秒数，没有冷却时为 0
+fn cooldown_remaining_secs(m: &Metrics, account_id: &str, now_ms: i64) -> f64 {
+    m.account_cooldowns
+        .get(account_id)
+        .map(|until| (*until - now_ms).max(0) as f64 / 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// 记录一次 Token 刷新结果
+pub fn record_token_refresh(outcome: &'static str) {
+    increment(&metrics().token_refreshes, outcome);
+}
+
+/// 以 Prometheus 文本格式输出全部指标
+pub fn render(token_manager: &TokenManager, signature_manager: &SignatureManager) -> String {
+    render_metrics(metrics(), &token_manager.get_all_tokens(), signature_manager.len())
+}
+
+/// 转义标签值中的反斜杠、双引号和换行
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// 按标签排序后输出计数器
+fn render_counter<K, F>(out: &mut String, name: &str, help: &str, map: &DashMap<K, AtomicU64>, labels: F)
+where
+    K: std::hash::Hash + Eq,
+    F: Fn(&K) -> String,
+{
+    header(out, name, "counter", help);
+    let mut rows: Vec<(String, u64)> = map
+        .iter()
+        .map(|entry| (labels(entry.key()), entry.value().load(Ordering::Relaxed)))
+        .collect();
+    rows.sort();
+    for (labels, value) in rows {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+fn render_metrics(m: &Metrics, tokens: &[ProxyToken], signature_count: usize) -> String {
+    let mut out = String::new();
+
+    render_counter(
+        &mut out,
+        "antigravity_proxy_requests_total",
+        "按协议、模型和 HTTP 状态码统计的客户端请求数",
+        &m.requests,
+        |(protocol, model, status)| {
+            format!("protocol=\"{}\",model=\"{}\",status=\"{}\"", protocol, escape_label(model), status)
+        },
+    );
+
+    let name = "antigravity_proxy_upstream_latency_seconds";
+    header(&mut out, name, "histogram", "上游调用耗时（流式请求为收到首个分片的时间）");
+    let mut protocols: Vec<String> = m.upstream_latency.iter().map(|e| e.key().clone()).collect();
+    protocols.sort();
+    for protocol in protocols {
+        if let Some(h) = m.upstream_latency.get(&protocol) {
+            for (bucket, le) in h.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{protocol=\"{}\",le=\"{}\"}} {}",
+                    name, protocol, le, bucket.load(Ordering::Relaxed)
+                );
+            }
+            let count = h.count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{protocol=\"{}\",le=\"+Inf\"}} {}", name, protocol, count);
+            let sum = h.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            let _ = writeln!(out, "{}_sum{{protocol=\"{}\"}} {}", name, protocol, sum);
+            let _ = writeln!(out, "{}_count{{protocol=\"{}\"}} {}", name, protocol, count);
+        }
+    }
+
+    render_counter(
+        &mut out,
+        "antigravity_proxy_retries_total",
+        "限流短延迟后在同一账号上的重试次数",
+        &m.retries,
+        |protocol| format!("protocol=\"{}\"", protocol),
+    );
+    render_counter(
+        &mut out,
+        "antigravity_proxy_account_rotations_total",
+        "请求失败后轮换到其他账号的次数",
+        &m.rotations,
+        |protocol| format!("protocol=\"{}\"", protocol),
+    );
+    render_counter(
+        &mut out,
+        "antigravity_proxy_token_refreshes_total",
+        "按结果统计的 access_token 刷新次数",
+        &m.token_refreshes,
+        |outcome| format!("result=\"{}\"", outcome),
+    );
+
+    let mut tokens: Vec<&ProxyToken> = tokens.iter().collect();
+    tokens.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+    let now = chrono::Utc::now().timestamp();
+    let now_ms = chrono::Utc::now().timestamp_millis();
+
+    let name = "antigravity_proxy_account_available";
+    header(&mut out, name, "gauge", "账号是否参与轮换、仍有配额且不在限流冷却中 (1/0)");
+    for token in &tokens {
+        let available = !token.quota_exhausted && cooldown_remaining_secs(m, &token.account_id, now_ms) <= 0.0;
+        let _ = writeln!(out, "{}{{account_id=\"{}\"}} {}", name, token.account_id, u8::from(available));
+    }
+
+    let name = "antigravity_proxy_account_cooldown_seconds";
+    header(&mut out, name, "gauge", "上游限流 (429) 给出的剩余冷却秒数，没有冷却时为 0");
+    for token in &tokens {
+        let remaining = cooldown_remaining_secs(m, &token.account_id, now_ms);
+        let _ = writeln!(out, "{}{{account_id=\"{}\"}} {}", name, token.account_id, remaining);
+    }
+
+    let name = "antigravity_proxy_account_quota_exhausted";
+    header(&mut out, name, "gauge", "最近一次配额刷新显示账号已无配额，配额重置前仅在没有其他账号时使用 (1/0)");
+    for token in &tokens {
+        let _ = writeln!(out, "{}{{account_id=\"{}\"}} {}", name, token.account_id, u8::from(token.quota_exhausted));
+    }
+
+    let name = "antigravity_proxy_account_token_expires_in_seconds";
+    header(&mut out, name, "gauge", "access_token 距离过期的秒数");
+    for token in &tokens {
+        let expires_in = token.timestamp - now;
+        let _ = writeln!(out, "{}{{account_id=\"{}\"}} {}", name, token.account_id, expires_in);
+    }
+
+    let name = "antigravity_proxy_signature_cache_entries";
+    header(&mut out, name, "gauge", "SignatureManager 中缓存的思维链签名数量");
+    let _ = writeln!(out, "{} {}", name, signature_count);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_histogram() {
+        let m = Metrics::default();
+        increment(&m.requests, ("openai".to_string(), "gemini-\"x\"".to_string(), 200));
+        increment(&m.requests, ("openai".to_string(), "gemini-\"x\"".to_string(), 200));
+        increment(&m.rotations, "anthropic".to_string());
+        m.upstream_latency
+            .entry("openai".to_string())
+            .or_default()
+            .observe(Duration::from_millis(300));
+
+        let out = render_metrics(&m, &[], 3);
+        assert!(out.contains(
+            "antigravity_proxy_requests_total{protocol=\"openai\",model=\"gemini-\\\"x\\\"\",status=\"200\"} 2"
+        ));
+        assert!(out.contains("antigravity_proxy_account_rotations_total{protocol=\"anthropic\"} 1"));
+        assert!(out.contains("antigravity_proxy_upstream_latency_seconds_bucket{protocol=\"openai\",le=\"0.25\"} 0"));
+        assert!(out.contains("antigravity_proxy_upstream_latency_seconds_bucket{protocol=\"openai\",le=\"0.5\"} 1"));
+        assert!(out.contains("antigravity_proxy_upstream_latency_seconds_count{protocol=\"openai\"} 1"));
+        assert!(out.contains("antigravity_proxy_signature_cache_entries 3"));
+        assert!(out.contains("# TYPE antigravity_proxy_token_refreshes_total counter"));
+    }
+
+    #[test]
+    fn test_account_cooldown_gauge() {
+        let m = Metrics::default();
+        let token = |id: &str| ProxyToken {
+            account_id: id.to_string(),
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expires_in: 3600,
+            timestamp: chrono::Utc::now().timestamp() + 3600,
+            email: format!("{}@example.com", id),
+            account_path: std::path::PathBuf::new(),
+            project_id: None,
+            session_id: String::new(),
+            groups: Vec::new(),
+            quota_exhausted: false,
+        };
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        m.account_cooldowns.insert("a".to_string(), now_ms + 30_000);
+        m.account_cooldowns.insert("b".to_string(), now_ms - 1_000);
+
+        let out = render_metrics(&m, &[token("a"), token("b")], 0);
+        assert!(out.contains("antigravity_proxy_account_available{account_id=\"a\"} 0"));
+        assert!(out.contains("antigravity_proxy_account_available{account_id=\"b\"} 1"));
+        assert!(out.contains("antigravity_proxy_account_cooldown_seconds{account_id=\"b\"} 0"));
+        assert_eq!(cooldown_remaining_secs(&m, "a", now_ms), 30.0);
+        assert_eq!(cooldown_remaining_secs(&m, "c", now_ms), 0.0);
+    }
+
+    #[test]
+    fn test_unknown_models_share_one_label() {
+        assert_eq!(model_label("gemini-2.5-flash"), "gemini-2.5-flash");
+        assert_eq!(model_label("claude-3-5-sonnet-20241022"), "claude-3-5-sonnet-20241022");
+        assert_eq!(model_label("gemini-random-1234"), MODEL_OTHER);
+        assert_eq!(model_label(""), MODEL_OTHER);
+    }
+}
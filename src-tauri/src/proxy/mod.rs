@@ -2,19 +2,28 @@
 pub mod config;
 pub mod token_manager;
 pub mod token_refresher;
+pub mod account_watcher;
 pub mod signature_manager;
 pub mod project_resolver;
 pub mod server;
+pub mod admin;
+pub mod auth;
+pub mod events;
+pub mod metrics;
+pub mod stats;
 pub mod converter;
 pub mod client;
 pub mod claude_converter;
 pub mod retry_handler;
+pub mod pipeline;
+pub mod upstream_error;
 pub mod model_mapper;
 pub mod config_builder;
 
-pub use config::ProxyConfig;
+pub use config::{ProxyConfig, RetryPolicy};
 pub use token_manager::TokenManager;
 pub use token_refresher::TokenRefresher;
+pub use account_watcher::AccountWatcher;
 pub use signature_manager::SignatureManager;
 pub use server::AxumServer;
 pub use config_builder::{build_thinking_config, build_safety_settings, build_generation_config};
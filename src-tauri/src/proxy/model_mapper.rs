@@ -18,6 +18,57 @@ const SUPPORTED_MODELS: &[&str] = &[
     "gemini-2.0-flash-exp",
 ];
 
+/// /v1/models 返回的模型列表: (模型 ID, 提供方, 创建时间)
+pub const LISTED_MODELS: &[(&str, &str, i64)] = &[
+    // Gemini Native (from Log)
+    ("gemini-2.5-flash-thinking", "google", 1734336000),
+    ("gemini-2.5-flash", "google", 1734336000),
+    ("gemini-2.5-flash-lite", "google", 1734336000),
+    ("gemini-2.5-pro", "google", 1734336000),
+    ("gemini-3-pro-low", "google", 1734336000),
+    ("gemini-3-pro-high", "google", 1734336000),
+    ("gemini-3-flash", "google", 1734336000),
+    // Claude Native (from Log)
+    ("claude-sonnet-4-5", "anthropic", 1734336000),
+    ("claude-sonnet-4-5-thinking", "anthropic", 1734336000),
+    ("claude-opus-4-5-thinking", "anthropic", 1734336000),
+    // Internal Image Models
+    ("gemini-3-pro-image", "google", 1734336000),
+    ("gemini-3-pro-image-16x9", "google", 1734336000),
+    ("gemini-3-pro-image-9x16", "google", 1734336000),
+    ("gemini-3-pro-image-4k", "google", 1734336000),
+    ("gemini-2.5-flash-image", "google", 1759363200),
+    ("gemini-2.5-flash-image-preview", "google", 1756166400),
+    ("gemini-3-pro-image-preview", "google", 1737158400),
+];
+
+/// 精确匹配映射表
+const EXACT_MAPPING: &[(&str, &str)] = &[
+    // Claude Sonnet 系列
+    ("claude-sonnet-4-5-20250929", "claude-sonnet-4-5-thinking"),
+    ("claude-3-5-sonnet-20241022", "claude-sonnet-4-5"),
+    ("claude-3-5-sonnet-20240620", "claude-sonnet-4-5"),
+    // Claude Opus 系列
+    ("claude-opus-4", "claude-opus-4-5-thinking"),
+    ("claude-opus-4-5-20251101", "claude-opus-4-5-thinking"),
+    ("claude-opus-4-5", "claude-opus-4-5-thinking"),
+    // Claude Haiku 系列
+    ("claude-haiku-4", "claude-sonnet-4-5"),
+    ("claude-3-haiku-20240307", "claude-sonnet-4-5"),
+    ("claude-haiku-4-5-20251001", "claude-sonnet-4-5"),
+    // Gemini 内部映射
+    ("gemini-3-pro-high", "gemini-3-pro-preview"),
+    ("gemini-3-pro-low", "gemini-3-pro-preview"),
+    ("gemini-3-flash", "gemini-3-flash-preview"),
+];
+
+/// 是否为已知模型名（模型列表、直接透传或精确映射中出现过的名称）
+pub fn is_known_model(model_name: &str) -> bool {
+    LISTED_MODELS.iter().any(|(id, _, _)| *id == model_name)
+        || SUPPORTED_MODELS.contains(&model_name)
+        || EXACT_MAPPING.iter().any(|(from, _)| *from == model_name)
+}
+
 impl ModelMapper {
     /// 创建新的模型映射器
     pub fn new(custom_mapping: HashMap<String, String>) -> Self {
@@ -78,30 +129,8 @@ impl ModelMapper {
 
     /// 默认映射规则
     fn default_mapping(model_name: &str) -> String {
-        // 精确匹配映射表
-        let exact_mapping: HashMap<&str, &str> = [
-            // Claude Sonnet 系列
-            ("claude-sonnet-4-5-20250929", "claude-sonnet-4-5-thinking"),
-            ("claude-3-5-sonnet-20241022", "claude-sonnet-4-5"),
-            ("claude-3-5-sonnet-20240620", "claude-sonnet-4-5"),
-            // Claude Opus 系列
-            ("claude-opus-4", "claude-opus-4-5-thinking"),
-            ("claude-opus-4-5-20251101", "claude-opus-4-5-thinking"),
-            ("claude-opus-4-5", "claude-opus-4-5-thinking"),
-            // Claude Haiku 系列
-            ("claude-haiku-4", "claude-sonnet-4-5"),
-            ("claude-3-haiku-20240307", "claude-sonnet-4-5"),
-            ("claude-haiku-4-5-20251001", "claude-sonnet-4-5"),
-            // Gemini 内部映射
-            ("gemini-3-pro-high", "gemini-3-pro-preview"),
-            ("gemini-3-pro-low", "gemini-3-pro-preview"),
-            ("gemini-3-flash", "gemini-3-flash-preview"),
-        ]
-        .into_iter()
-        .collect();
-
         // 1. 精确匹配
-        if let Some(mapped) = exact_mapping.get(model_name) {
+        if let Some((_, mapped)) = EXACT_MAPPING.iter().find(|(from, _)| *from == model_name) {
             return mapped.to_string();
         }
 
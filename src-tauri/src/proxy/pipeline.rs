@@ -0,0 +1,383 @@
+//! 协议无关的请求管线
+//! OpenAI / Anthropic 请求先转换为 GenerateRequest，由 GeminiClient 统一发送；
+//! 上游流式响应解析为 GenerateChunk，再由各协议适配器转换为客户端格式
+
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::proxy::config_builder;
+use crate::proxy::converter::{self, AnthropicChatRequest, GeminiContent, OpenAIChatRequest};
+use crate::proxy::model_mapper::ModelMapper;
+use crate::proxy::upstream_error::UpstreamError;
+
+/// 上游流式响应，每一项为一个解析后的分片
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<GenerateChunk, UpstreamError>> + Send>>;
+
+/// 上游请求头画像
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamProfile {
+    /// 模拟 Antigravity 客户端
+    Antigravity,
+    /// 模拟 Claude CLI，启用 interleaved thinking 等 Beta 特性
+    ClaudeCli,
+}
+
+impl UpstreamProfile {
+    /// 除 Authorization 与 Host 外需要附加的请求头
+    pub fn headers(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            UpstreamProfile::Antigravity => &[("User-Agent", "antigravity/1.11.3 windows/amd64")],
+            UpstreamProfile::ClaudeCli => &[
+                ("User-Agent", "claude-cli/1.0.83 (external, cli)"),
+                ("X-App", "cli"),
+                ("Anthropic-Beta", "claude-code-20250219,interleaved-thinking-2025-05-14"),
+                ("X-Stainless-Lang", "js"),
+                ("X-Stainless-Package-Version", "0.55.1"),
+                ("X-Stainless-Os", "MacOS"),
+                ("X-Stainless-Arch", "arm64"),
+            ],
+        }
+    }
+}
+
+/// 协议无关的上游生成请求
+#[derive(Debug, Clone)]
+pub struct GenerateRequest {
+    /// 客户端请求的模型名
+    pub model: String,
+    /// 映射后发送给上游的模型名
+    pub upstream_model: String,
+    pub contents: Vec<GeminiContent>,
+    pub system_text: String,
+    pub generation_config: Value,
+    /// 为空时不发送 stopSequences
+    pub stop_sequences: Vec<String>,
+    pub safety_settings: Option<Value>,
+    /// 是否设置 functionCallingConfig 为 VALIDATED
+    pub validated_tool_calling: bool,
+    pub profile: UpstreamProfile,
+}
+
+impl GenerateRequest {
+    /// 由 OpenAI 请求构建
+    ///
+    /// system 消息合并为 systemInstruction，画图模型的后缀解析为 imageConfig
+    pub fn from_openai(request: &OpenAIChatRequest) -> Self {
+        let (system_messages, chat_messages): (Vec<_>, Vec<_>) =
+            request.messages.iter().partition(|m| m.role == "system");
+        let system_text = system_messages
+            .iter()
+            .map(|m| m.content.text())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chat_messages: Vec<converter::OpenAIMessage> = chat_messages.into_iter().cloned().collect();
+
+        // OpenAI 的 stop 参数可以是字符串或字符串数组
+        let stop_sequences = match request.extra.as_ref().and_then(|m| m.get("stop")) {
+            Some(Value::String(s)) => vec![s.clone()],
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            model: request.model.clone(),
+            upstream_model: config_builder::openai_upstream_model(&request.model),
+            contents: converter::convert_openai_to_gemini_contents(&chat_messages),
+            system_text,
+            generation_config: config_builder::build_openai_generation_config(request),
+            stop_sequences,
+            safety_settings: None,
+            validated_tool_calling: true,
+            profile: UpstreamProfile::Antigravity,
+        }
+    }
+
+    /// 由 Anthropic 请求构建
+    ///
+    /// # 参数
+    /// - `request`: Anthropic 请求
+    /// - `model_mapping`: 自定义模型映射
+    /// - `signature_map`: 历史思维链签名，用于回填 thinking 块
+    pub async fn from_anthropic(
+        request: &AnthropicChatRequest,
+        model_mapping: &HashMap<String, String>,
+        signature_map: Arc<tokio::sync::Mutex<HashMap<String, String>>>,
+    ) -> Self {
+        let contents = converter::convert_anthropic_to_gemini_contents_ext(request, signature_map).await;
+
+        // web_search 工具会强制使用特定模型
+        let tools_json: Option<Vec<Value>> = request
+            .tools
+            .as_ref()
+            .map(|tools| tools.iter().map(|t| json!({ "name": &t.name })).collect());
+        let upstream_model = ModelMapper::new(model_mapping.clone()).map_model_with_tools(&request.model, tools_json.as_ref());
+        if upstream_model != request.model {
+            tracing::info!("(Anthropic) 模型映射: {} -> {}", request.model, upstream_model);
+        }
+
+        Self {
+            model: request.model.clone(),
+            generation_config: config_builder::build_generation_config(request, &upstream_model),
+            upstream_model,
+            contents,
+            system_text: request.system.clone().unwrap_or_default(),
+            stop_sequences: request.stop_sequences.clone().unwrap_or_default(),
+            safety_settings: Some(config_builder::build_safety_settings()),
+            // 工具调用配置会导致 MALFORMED_FUNCTION_CALL，暂不发送
+            validated_tool_calling: false,
+            profile: UpstreamProfile::ClaudeCli,
+        }
+    }
+
+    /// 构建 v1internal 请求体
+    pub fn to_body(&self, project_id: &str, session_id: &str) -> Value {
+        let mut generation_config = self.generation_config.clone();
+        if !self.stop_sequences.is_empty() {
+            generation_config["stopSequences"] = json!(self.stop_sequences);
+        }
+
+        let mut request = json!({
+            "contents": self.contents,
+            "systemInstruction": {
+                "role": "user",
+                "parts": [{ "text": self.system_text }]
+            },
+            "generationConfig": generation_config,
+            "sessionId": session_id
+        });
+        if let Some(safety_settings) = &self.safety_settings {
+            request["safetySettings"] = safety_settings.clone();
+        }
+        if self.validated_tool_calling {
+            request["toolConfig"] = json!({ "functionCallingConfig": { "mode": "VALIDATED" } });
+        }
+
+        json!({
+            "project": project_id,
+            "requestId": Uuid::new_v4().to_string(),
+            "model": self.upstream_model,
+            "userAgent": "antigravity",
+            "request": request
+        })
+    }
+}
+
+/// Token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// 协议无关的上游流式分片（只取第一个候选的第一个 part）
+#[derive(Debug, Clone, Default)]
+pub struct GenerateChunk {
+    pub response_id: Option<String>,
+    pub text: String,
+    /// 是否为思维链内容
+    pub is_thought: bool,
+    pub thought_signature: Option<String>,
+    /// 工具调用，原样保留 Gemini 的 functionCall 对象
+    pub function_call: Option<Value>,
+    /// Gemini 原始结束原因，例如 STOP / MAX_TOKENS / SAFETY
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+    /// 请求被拦截时上游返回的 promptFeedback
+    pub prompt_feedback: Option<Value>,
+}
+
+impl GenerateChunk {
+    /// 解析 Gemini 流式分片，兼容包在 response 字段下的格式
+    pub fn from_gemini(json: &Value) -> Self {
+        let root = json.get("response").unwrap_or(json);
+        let candidate = root.get("candidates").and_then(|c| c.get(0));
+        let part = candidate
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.get(0));
+        let usage = root.get("usageMetadata").map(|u| Usage {
+            input_tokens: u.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0),
+            output_tokens: u.get("candidatesTokenCount").and_then(|v| v.as_u64()).unwrap_or(0),
+        });
+
+        Self {
+            response_id: root.get("responseId").and_then(|s| s.as_str()).map(str::to_string),
+            text: part.and_then(|p| p.get("text")).and_then(|t| t.as_str()).unwrap_or("").to_string(),
+            is_thought: part.and_then(|p| p.get("thought")).and_then(|t| t.as_bool()).unwrap_or(false),
+            thought_signature: part
+                .and_then(|p| p.get("thoughtSignature"))
+                .and_then(|s| s.as_str())
+                .map(str::to_string),
+            function_call: part.and_then(|p| p.get("functionCall")).cloned(),
+            finish_reason: candidate
+                .and_then(|c| c.get("finishReason"))
+                .and_then(|f| f.as_str())
+                .map(str::to_string),
+            usage,
+            prompt_feedback: root.get("promptFeedback").cloned(),
+        }
+    }
+
+    /// 是否包含文本、思维链、签名或工具调用
+    pub fn has_content(&self) -> bool {
+        !self.text.is_empty() || self.is_thought || self.thought_signature.is_some() || self.function_call.is_some()
+    }
+
+    /// OpenAI 格式的结束原因
+    pub fn openai_finish_reason(&self) -> Option<&'static str> {
+        match self.finish_reason.as_deref() {
+            Some("STOP") => Some("stop"),
+            Some("MAX_TOKENS") => Some("length"),
+            Some("SAFETY") | Some("RECITATION") => Some("content_filter"),
+            _ => None,
+        }
+    }
+
+    /// Anthropic 格式的结束原因，任何 Gemini 结束原因都表示消息结束
+    pub fn anthropic_stop_reason(&self) -> Option<&'static str> {
+        self.finish_reason.as_deref().map(|reason| match reason {
+            "MAX_TOKENS" => "max_tokens",
+            _ => "end_turn",
+        })
+    }
+
+    /// 转换为 OpenAI chat.completion.chunk
+    pub fn to_openai_chunk(&self, model: &str) -> Value {
+        let mut chunk = json!({
+            "id": self.response_id.as_deref().unwrap_or("chatcmpl-stream"),
+            "object": "chat.completion.chunk",
+            "created": chrono::Utc::now().timestamp(),
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "delta": { "content": self.text },
+                "finish_reason": self.openai_finish_reason()
+            }]
+        });
+        if let Some(usage) = self.usage.filter(|_| self.finish_reason.is_some()) {
+            chunk["usage"] = json!({
+                "prompt_tokens": usage.input_tokens,
+                "completion_tokens": usage.output_tokens,
+                "total_tokens": usage.input_tokens + usage.output_tokens
+            });
+        }
+        chunk
+    }
+}
+
+/// 预读第一个分片
+///
+/// 上游在输出任何内容前失败或直接结束时返回错误，交由重试策略换账号；
+/// 成功时返回包含该分片的完整流
+pub async fn peek_first_chunk(mut stream: ChunkStream) -> Result<ChunkStream, UpstreamError> {
+    match stream.next().await {
+        Some(Ok(first)) => Ok(Box::pin(futures::stream::once(async move { Ok(first) }).chain(stream))),
+        Some(Err(e)) => Err(e),
+        None => Err(UpstreamError::empty_response("EMPTY_STREAM")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::upstream_error::UpstreamErrorKind;
+
+    fn openai_request(model: &str, extra: Option<HashMap<String, Value>>) -> OpenAIChatRequest {
+        OpenAIChatRequest {
+            model: model.to_string(),
+            messages: vec![],
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            stream: Some(true),
+            size: None,
+            quality: None,
+            extra,
+        }
+    }
+
+    #[test]
+    fn test_openai_request_body() {
+        let extra = HashMap::from([("stop".to_string(), json!(["END"]))]);
+        let request = GenerateRequest::from_openai(&openai_request("gemini-3-pro-image-16x9-4k", Some(extra)));
+        assert_eq!(request.upstream_model, "gemini-3-pro-image");
+
+        let body = request.to_body("project-1", "session-1");
+        let config = &body["request"]["generationConfig"];
+        assert_eq!(config["imageConfig"]["aspectRatio"], "16:9");
+        assert_eq!(config["imageConfig"]["imageSize"], "4K");
+        assert_eq!(config["stopSequences"], json!(["END"]));
+        assert_eq!(body["request"]["toolConfig"]["functionCallingConfig"]["mode"], "VALIDATED");
+        assert!(body["request"].get("safetySettings").is_none());
+        assert_eq!(body["project"], "project-1");
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_request_body() {
+        let request: AnthropicChatRequest = serde_json::from_value(json!({
+            "model": "claude-custom",
+            "system": "be brief",
+            "messages": [{ "role": "user", "content": "hello" }],
+            "stop_sequences": ["END"],
+            "stream": true
+        }))
+        .unwrap();
+        let mapping = HashMap::from([("claude-custom".to_string(), "gemini-3-pro-preview".to_string())]);
+        let request = GenerateRequest::from_anthropic(&request, &mapping, Arc::default()).await;
+        assert_eq!(request.model, "claude-custom");
+        assert_eq!(request.upstream_model, "gemini-3-pro-preview");
+
+        let body = request.to_body("project-1", "session-1");
+        assert_eq!(body["model"], "gemini-3-pro-preview");
+        assert_eq!(body["request"]["systemInstruction"]["parts"][0]["text"], "be brief");
+        assert_eq!(body["request"]["contents"][0]["role"], "user");
+        assert_eq!(body["request"]["contents"][0]["parts"][0]["text"], "hello");
+        assert_eq!(body["request"]["generationConfig"]["stopSequences"], json!(["END"]));
+        assert_eq!(body["request"]["sessionId"], "session-1");
+        assert!(body["request"]["safetySettings"].is_array());
+        assert!(body["request"].get("toolConfig").is_none());
+    }
+
+    #[test]
+    fn test_chunk_from_gemini() {
+        let json = json!({
+            "response": {
+                "responseId": "resp-1",
+                "candidates": [{
+                    "content": { "parts": [{ "text": "hi", "thought": true, "thoughtSignature": "sig" }] },
+                    "finishReason": "MAX_TOKENS"
+                }],
+                "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 5 }
+            }
+        });
+        let chunk = GenerateChunk::from_gemini(&json);
+        assert!(chunk.is_thought);
+        assert_eq!(chunk.thought_signature.as_deref(), Some("sig"));
+        assert_eq!(chunk.usage, Some(Usage { input_tokens: 3, output_tokens: 5 }));
+        assert_eq!(chunk.anthropic_stop_reason(), Some("max_tokens"));
+        let malformed = GenerateChunk { finish_reason: Some("MALFORMED_FUNCTION_CALL".to_string()), ..Default::default() };
+        assert_eq!(malformed.anthropic_stop_reason(), Some("end_turn"));
+        assert_eq!(GenerateChunk::default().anthropic_stop_reason(), None);
+
+        let openai = chunk.to_openai_chunk("gemini-3-pro-preview");
+        assert_eq!(openai["id"], "resp-1");
+        assert_eq!(openai["choices"][0]["finish_reason"], "length");
+        assert_eq!(openai["usage"]["total_tokens"], 8);
+    }
+
+    #[tokio::test]
+    async fn test_peek_first_chunk() {
+        let empty: ChunkStream = Box::pin(futures::stream::empty());
+        let error = peek_first_chunk(empty).await.err().unwrap();
+        assert_eq!(error.kind, UpstreamErrorKind::EmptyResponse);
+
+        let chunk = GenerateChunk { text: "a".to_string(), ..Default::default() };
+        let stream: ChunkStream = Box::pin(futures::stream::iter(vec![Ok(chunk.clone()), Ok(chunk)]));
+        let stream = peek_first_chunk(stream).await.unwrap();
+        assert_eq!(stream.count().await, 2);
+    }
+}
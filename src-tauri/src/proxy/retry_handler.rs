@@ -1,7 +1,12 @@
 // 重试处理模块 - 解析 429 错误响应中的 retryDelay 并决定重试策略
 
+use rand::Rng;
 use regex::Regex;
 use serde_json::Value;
+use std::time::{Duration, Instant};
+
+use crate::proxy::config::RetryPolicy;
+use crate::proxy::upstream_error::{UpstreamError, UpstreamErrorKind};
 
 /// 重试策略枚举
 #[derive(Debug, Clone, PartialEq)]
@@ -67,27 +72,13 @@ impl RetryDelayParser {
         Some(total_ms.round() as u64)
     }
     
-    /// 解析 429 错误响应中的重试延迟
+    /// 解析错误对象 (error 字段) 中的重试延迟
     /// 支持两种格式：
     /// 1. RetryInfo.retryDelay (如 "1.203608125s")
     /// 2. metadata.quotaResetDelay (如 "331.167174ms" 或 "1h16m0.667923083s")
-    pub fn parse_retry_delay_ms(error_text: &str) -> Option<u64> {
-        // 尝试解析 JSON
-        let err_obj: Value = match serde_json::from_str(error_text) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        
+    pub fn parse_retry_delay_from_error(error: &Value) -> Option<u64> {
         // 获取 error.details 数组
-        let details = err_obj
-            .get("error")
-            .and_then(|e| e.get("details"))
-            .and_then(|d| d.as_array());
-        
-        let details = match details {
-            Some(d) => d,
-            None => return None,
-        };
+        let details = error.get("details")?.as_array()?;
         
         // 1. 查找 RetryInfo.retryDelay
         for detail in details {
@@ -115,29 +106,26 @@ impl RetryDelayParser {
         None
     }
     
-    /// 决定重试策略
+    /// 根据上游错误类别决定重试策略
     /// 
     /// 规则：
-    /// - 429 且 retryDelay <= 5000ms：等待后重试同一账号
-    /// - 429 且 retryDelay > 5000ms 或无法解析：轮换账号
-    /// - 404 或 403：轮换账号
+    /// - 限流且 retryDelay <= short_delay_threshold_ms：等待后重试同一账号
+    /// - 限流且 retryDelay 超过阈值或无法解析：轮换账号
+    /// - 模型不存在、无权限、连接失败、空响应：轮换账号
     /// - 其他：不重试
-    pub fn decide_retry_action(status: u16, error_text: &str) -> RetryAction {
-        if status == 429 {
-            if let Some(delay_ms) = Self::parse_retry_delay_ms(error_text) {
-                if delay_ms <= 5000 {
-                    // 加 200ms 缓冲
-                    return RetryAction::WaitAndRetry(delay_ms + 200);
-                }
-            }
-            return RetryAction::RotateAccount;
+    pub fn decide(error: &UpstreamError, short_delay_threshold_ms: u64) -> RetryAction {
+        match error.kind {
+            UpstreamErrorKind::RateLimited => match error.retry_delay_ms {
+                // 加 200ms 缓冲
+                Some(delay_ms) if delay_ms <= short_delay_threshold_ms => RetryAction::WaitAndRetry(delay_ms + 200),
+                _ => RetryAction::RotateAccount,
+            },
+            UpstreamErrorKind::NotFound
+            | UpstreamErrorKind::PermissionDenied
+            | UpstreamErrorKind::Transport
+            | UpstreamErrorKind::EmptyResponse => RetryAction::RotateAccount,
+            _ => RetryAction::NoRetry,
         }
-        
-        if status == 404 || status == 403 {
-            return RetryAction::RotateAccount;
-        }
-        
-        RetryAction::NoRetry
     }
     
     /// 检查是否应该因为空响应而重试
@@ -155,10 +143,150 @@ impl RetryDelayParser {
     }
 }
 
+/// 按重试策略限制后的下一步
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryDecision {
+    /// 等待指定毫秒后重试同一账号
+    WaitSameAccount(u64),
+    /// 退避指定毫秒后轮换到下一个账号
+    Rotate(u64),
+    /// 错误可重试，但尝试次数或总时限已用尽
+    Exhausted,
+    /// 错误不可重试
+    Abort,
+}
+
+/// 单个请求的重试进度
+pub struct RetryBudget {
+    policy: RetryPolicy,
+    max_attempts: usize,
+    /// 已使用的账号次数
+    attempts: usize,
+    /// 当前账号上已等待的次数
+    same_account_waits: u32,
+    /// 已轮换账号的次数，用于计算指数退避
+    rotations: u32,
+    started: Instant,
+}
+
+impl RetryBudget {
+    /// # 参数
+    /// - `policy`: 重试策略
+    /// - `account_count`: 可用账号数，max_attempts 为 0 时作为尝试上限
+    pub fn new(policy: RetryPolicy, account_count: usize) -> Self {
+        let max_attempts = match policy.max_attempts {
+            0 => account_count.max(1),
+            n => n,
+        };
+        Self {
+            policy,
+            max_attempts,
+            attempts: 0,
+            same_account_waits: 0,
+            rotations: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// 开始使用一个新账号，返回这是第几个账号
+    pub fn next_attempt(&mut self) -> usize {
+        self.attempts += 1;
+        self.same_account_waits = 0;
+        self.attempts
+    }
+
+    /// 根据上游错误与剩余预算决定下一步
+    pub fn decide(&mut self, error: &UpstreamError) -> RetryDecision {
+        let decision = match RetryDelayParser::decide(error, self.policy.short_delay_threshold_ms) {
+            RetryAction::NoRetry => return RetryDecision::Abort,
+            RetryAction::WaitAndRetry(delay_ms) if self.same_account_waits < self.policy.max_same_account_waits => {
+                RetryDecision::WaitSameAccount(delay_ms)
+            }
+            // 同一账号等待次数用尽后同样轮换账号
+            RetryAction::WaitAndRetry(_) | RetryAction::RotateAccount => {
+                if self.attempts >= self.max_attempts {
+                    return RetryDecision::Exhausted;
+                }
+                RetryDecision::Rotate(self.backoff_ms())
+            }
+        };
+
+        let delay_ms = match decision {
+            RetryDecision::WaitSameAccount(ms) | RetryDecision::Rotate(ms) => ms,
+            _ => 0,
+        };
+        if !self.within_deadline(delay_ms) {
+            return RetryDecision::Exhausted;
+        }
+
+        match decision {
+            RetryDecision::WaitSameAccount(_) => self.same_account_waits += 1,
+            RetryDecision::Rotate(_) => self.rotations += 1,
+            _ => {}
+        }
+        decision
+    }
+
+    /// 第 n 次轮换的退避时间：base * 2^(n-1)，按比例随机抖动，不超过上限
+    fn backoff_ms(&self) -> u64 {
+        if self.policy.backoff_base_ms == 0 {
+            return 0;
+        }
+        let exponential = self
+            .policy
+            .backoff_base_ms
+            .saturating_mul(1u64.checked_shl(self.rotations).unwrap_or(u64::MAX))
+            .min(self.policy.backoff_max_ms);
+        let jitter = self.policy.backoff_jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return exponential;
+        }
+        let factor = rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter);
+        ((exponential as f64 * factor).round() as u64).min(self.policy.backoff_max_ms)
+    }
+
+    /// 在剩余总时限内执行一次上游调用，超时返回 None，应按次数或时限用尽处理
+    pub async fn run_attempt<F: std::future::Future>(&self, attempt: F) -> Option<F::Output> {
+        match self.remaining() {
+            Some(remaining) => tokio::time::timeout(remaining, attempt).await.ok(),
+            None => Some(attempt.await),
+        }
+    }
+
+    /// 距总时限的剩余时间，未设置时限时为 None
+    fn remaining(&self) -> Option<Duration> {
+        if self.policy.deadline_secs == 0 {
+            return None;
+        }
+        Some(Duration::from_secs(self.policy.deadline_secs).saturating_sub(self.started.elapsed()))
+    }
+
+    /// 等待 delay_ms 后是否仍在总时限内
+    fn within_deadline(&self, delay_ms: u64) -> bool {
+        self.remaining()
+            .map_or(true, |remaining| Duration::from_millis(delay_ms) < remaining)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    fn parse_retry_delay_ms(error_text: &str) -> Option<u64> {
+        let err_obj: Value = serde_json::from_str(error_text).ok()?;
+        err_obj.get("error").and_then(RetryDelayParser::parse_retry_delay_from_error)
+    }
+    
+    /// 按默认阈值决定重试策略，属性测试共用
+    pub(super) fn decide_retry_action(status: u16, error_text: &str) -> RetryAction {
+        let threshold = RetryPolicy::default().short_delay_threshold_ms;
+        RetryDelayParser::decide(&UpstreamError::from_response(status, error_text.to_string()), threshold)
+    }
+    
     #[test]
     fn test_parse_duration_ms_seconds() {
         assert_eq!(RetryDelayParser::parse_duration_ms("1.203608125s"), Some(1204));
@@ -203,7 +331,7 @@ mod tests {
                 ]
             }
         }"#;
-        assert_eq!(RetryDelayParser::parse_retry_delay_ms(error_json), Some(1500));
+        assert_eq!(parse_retry_delay_ms(error_json), Some(1500));
     }
     
     #[test]
@@ -222,13 +350,13 @@ mod tests {
                 ]
             }
         }"#;
-        assert_eq!(RetryDelayParser::parse_retry_delay_ms(error_json), Some(331));
+        assert_eq!(parse_retry_delay_ms(error_json), Some(331));
     }
     
     #[test]
     fn test_parse_retry_delay_ms_no_details() {
         let error_json = r#"{"error": {"code": 429, "message": "Rate limited"}}"#;
-        assert_eq!(RetryDelayParser::parse_retry_delay_ms(error_json), None);
+        assert_eq!(parse_retry_delay_ms(error_json), None);
     }
     
     #[test]
@@ -245,7 +373,7 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            RetryDelayParser::decide_retry_action(429, error_json),
+            decide_retry_action(429, error_json),
             RetryAction::WaitAndRetry(1700) // 1500 + 200
         );
     }
@@ -264,7 +392,7 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            RetryDelayParser::decide_retry_action(429, error_json),
+            decide_retry_action(429, error_json),
             RetryAction::RotateAccount
         );
     }
@@ -273,7 +401,7 @@ mod tests {
     fn test_decide_retry_action_429_no_delay() {
         let error_json = r#"{"error": {"code": 429, "message": "Rate limited"}}"#;
         assert_eq!(
-            RetryDelayParser::decide_retry_action(429, error_json),
+            decide_retry_action(429, error_json),
             RetryAction::RotateAccount
         );
     }
@@ -281,7 +409,7 @@ mod tests {
     #[test]
     fn test_decide_retry_action_404() {
         assert_eq!(
-            RetryDelayParser::decide_retry_action(404, "Not found"),
+            decide_retry_action(404, "Not found"),
             RetryAction::RotateAccount
         );
     }
@@ -289,7 +417,7 @@ mod tests {
     #[test]
     fn test_decide_retry_action_403() {
         assert_eq!(
-            RetryDelayParser::decide_retry_action(403, "Permission denied"),
+            decide_retry_action(403, "Permission denied"),
             RetryAction::RotateAccount
         );
     }
@@ -297,11 +425,121 @@ mod tests {
     #[test]
     fn test_decide_retry_action_500() {
         assert_eq!(
-            RetryDelayParser::decide_retry_action(500, "Internal error"),
+            decide_retry_action(500, "Internal error"),
+            RetryAction::NoRetry
+        );
+    }
+    
+    #[test]
+    fn test_decide_transport_and_overloaded() {
+        assert_eq!(
+            RetryDelayParser::decide(&UpstreamError::transport("请求失败: timed out"), 5000),
+            RetryAction::RotateAccount
+        );
+        assert_eq!(
+            RetryDelayParser::decide(&UpstreamError::from_response(503, "UNAVAILABLE".to_string()), 5000),
             RetryAction::NoRetry
         );
     }
     
+    fn rate_limited(delay: &str) -> UpstreamError {
+        let body = format!(
+            r#"{{"error": {{"code": 429, "details": [{{"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "{}"}}]}}}}"#,
+            delay
+        );
+        UpstreamError::from_response(429, body)
+    }
+    
+    #[test]
+    fn test_budget_limits_same_account_waits() {
+        let policy = RetryPolicy { max_same_account_waits: 2, ..RetryPolicy::default() };
+        let mut budget = RetryBudget::new(policy, 2);
+        budget.next_attempt();
+        let error = rate_limited("1s");
+        assert_eq!(budget.decide(&error), RetryDecision::WaitSameAccount(1200));
+        assert_eq!(budget.decide(&error), RetryDecision::WaitSameAccount(1200));
+        // 等待次数用尽后轮换账号
+        assert_eq!(budget.decide(&error), RetryDecision::Rotate(0));
+        budget.next_attempt();
+        assert_eq!(budget.decide(&error), RetryDecision::WaitSameAccount(1200));
+        assert_eq!(budget.decide(&error), RetryDecision::WaitSameAccount(1200));
+        assert_eq!(budget.decide(&error), RetryDecision::Exhausted);
+    }
+    
+    #[test]
+    fn test_budget_backoff_and_limits() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            short_delay_threshold_ms: 500,
+            backoff_base_ms: 100,
+            backoff_max_ms: 250,
+            backoff_jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let mut budget = RetryBudget::new(policy, 1);
+        assert_eq!(budget.max_attempts(), 4);
+        // 阈值可配置：1s 超过 500ms，直接轮换
+        let error = rate_limited("1s");
+        budget.next_attempt();
+        assert_eq!(budget.decide(&error), RetryDecision::Rotate(100));
+        budget.next_attempt();
+        assert_eq!(budget.decide(&error), RetryDecision::Rotate(200));
+        budget.next_attempt();
+        assert_eq!(budget.decide(&error), RetryDecision::Rotate(250));
+        budget.next_attempt();
+        assert_eq!(budget.decide(&error), RetryDecision::Exhausted);
+        
+        let invalid = UpstreamError::from_response(400, "bad".to_string());
+        assert_eq!(budget.decide(&invalid), RetryDecision::Abort);
+    }
+    
+    #[test]
+    fn test_budget_backoff_jitter_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 20,
+            short_delay_threshold_ms: 500,
+            backoff_base_ms: 100,
+            backoff_max_ms: 250,
+            backoff_jitter: 1.0,
+            ..RetryPolicy::default()
+        };
+        let mut budget = RetryBudget::new(policy, 1);
+        let error = rate_limited("1s");
+        for _ in 0..10 {
+            budget.next_attempt();
+            match budget.decide(&error) {
+                RetryDecision::Rotate(ms) => assert!(ms <= 250, "退避 {}ms 超过上限", ms),
+                other => panic!("应轮换账号，实际为 {:?}", other),
+            }
+        }
+    }
+    
+    #[test]
+    fn test_budget_deadline() {
+        let policy = RetryPolicy { deadline_secs: 1, ..RetryPolicy::default() };
+        let mut budget = RetryBudget::new(policy, 3);
+        budget.next_attempt();
+        // 等待会超出总时限
+        assert_eq!(budget.decide(&rate_limited("4s")), RetryDecision::Exhausted);
+        assert_eq!(budget.decide(&UpstreamError::transport("timed out")), RetryDecision::Rotate(0));
+    }
+    
+    #[tokio::test]
+    async fn test_budget_deadline_during_attempt() {
+        let policy = RetryPolicy { deadline_secs: 1, ..RetryPolicy::default() };
+        let budget = RetryBudget::new(policy, 3);
+        assert_eq!(budget.run_attempt(async { 1 }).await, Some(1));
+        
+        // 单次调用超出总时限时被中断，不等到 request_timeout
+        let started = Instant::now();
+        assert_eq!(budget.run_attempt(tokio::time::sleep(Duration::from_secs(30))).await, None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        
+        // 未设置时限时不限制
+        let budget = RetryBudget::new(RetryPolicy { deadline_secs: 0, ..RetryPolicy::default() }, 3);
+        assert_eq!(budget.run_attempt(tokio::time::sleep(Duration::from_millis(10))).await, Some(()));
+    }
+    
     #[test]
     fn test_should_retry_empty_response() {
         // 空内容 + MAX_TOKENS -> 重试
@@ -328,6 +566,7 @@ mod tests {
 #[cfg(test)]
 mod property_tests {
     use super::*;
+    use super::tests::decide_retry_action;
     use proptest::prelude::*;
     
     /// 生成有效的持续时间字符串
@@ -381,7 +620,7 @@ mod property_tests {
         fn prop_retry_action_429_short_delay(delay_ms in 1u64..5000) {
             // Feature: anthropic-api-enhancement, Property 9: 重试策略决策正确性
             let error_json = error_json_with_delay_strategy(delay_ms);
-            let action = RetryDelayParser::decide_retry_action(429, &error_json);
+            let action = decide_retry_action(429, &error_json);
             
             // 429 + 短延迟 (<=5000ms) 应该返回 WaitAndRetry
             match action {
@@ -403,7 +642,7 @@ mod property_tests {
         fn prop_retry_action_429_long_delay(delay_ms in 5001u64..100000) {
             // Feature: anthropic-api-enhancement, Property 9: 重试策略决策正确性
             let error_json = error_json_with_delay_strategy(delay_ms);
-            let action = RetryDelayParser::decide_retry_action(429, &error_json);
+            let action = decide_retry_action(429, &error_json);
             
             // 429 + 长延迟 (>5000ms) 应该返回 RotateAccount
             prop_assert_eq!(
@@ -416,7 +655,7 @@ mod property_tests {
         #[test]
         fn prop_retry_action_404_403(status in prop_oneof![Just(404u16), Just(403u16)]) {
             // Feature: anthropic-api-enhancement, Property 9: 重试策略决策正确性
-            let action = RetryDelayParser::decide_retry_action(status, "any error text");
+            let action = decide_retry_action(status, "any error text");
             
             // 404/403 应该返回 RotateAccount
             prop_assert_eq!(
@@ -432,7 +671,7 @@ mod property_tests {
             // 排除 429, 404, 403
             prop_assume!(status != 429 && status != 404 && status != 403);
             
-            let action = RetryDelayParser::decide_retry_action(status, "any error text");
+            let action = decide_retry_action(status, "any error text");
             
             // 其他状态码应该返回 NoRetry
             prop_assert_eq!(
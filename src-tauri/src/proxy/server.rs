@@ -1,15 +1,21 @@
 use axum::{
     Router,
     routing::{get, post},
-    extract::State,
+    extract::{Extension, Request, State},
+    middleware::{self, Next},
     response::{IntoResponse, Response, sse::{Event, Sse}},
-    http::StatusCode,
+    http::{HeaderMap, HeaderValue, StatusCode},
     Json,
 };
 use std::sync::Arc;
+use tracing::Instrument;
+use crate::modules::logger;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use futures::stream::StreamExt;
-use crate::proxy::{TokenManager, TokenRefresher, SignatureManager, converter, client::GeminiClient, retry_handler::{RetryDelayParser, RetryAction}};
+use crate::proxy::events::{self, ProxyEventKind};
+use crate::proxy::token_manager::ProxyToken;
+use crate::proxy::{admin, auth::{self, ApiKeyScope}, metrics, stats::RequestStats, TokenManager, TokenRefresher, AccountWatcher, SignatureManager, converter, client::GeminiClient, pipeline::{self, ChunkStream, GenerateChunk, GenerateRequest, Usage}, retry_handler::{RetryBudget, RetryDecision, RetryDelayParser}, upstream_error::{ClientProtocol, UpstreamError, UpstreamErrorKind}, RetryPolicy};
 
 /// Axum 应用状态
 #[derive(Clone)]
@@ -20,15 +26,33 @@ pub struct AppState {
     pub thought_signature_map: Arc<tokio::sync::Mutex<std::collections::HashMap<String, String>>>, // 思维链签名映射 (ID -> Signature) - 保留以兼容现有代码
     pub signature_manager: Arc<SignatureManager>, // 新的签名管理器
     pub upstream_proxy: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
+    pub api_key: Arc<tokio::sync::RwLock<String>>, // /v1/* 主密钥
+    pub api_key_groups: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>, // API 密钥 -> 账号分组
+    pub admin_key: Arc<tokio::sync::RwLock<String>>, // 管理接口密钥，为空时不开放
+    pub retry_policy: Arc<tokio::sync::RwLock<RetryPolicy>>, // 重试策略
+    pub stats: Arc<RequestStats>, // 请求统计
+    pub shutdown: CancellationToken, // 服务停止信号，用于结束长连接 (如 /admin/events)
 }
 
+/// 请求 ID 请求头
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
 /// Axum 服务器实例
 pub struct AxumServer {
     shutdown_tx: Option<oneshot::Sender<()>>,
     mapping_state: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
     proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
+    api_key_state: Arc<tokio::sync::RwLock<String>>,
+    api_key_groups_state: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    admin_key_state: Arc<tokio::sync::RwLock<String>>,
+    retry_policy_state: Arc<tokio::sync::RwLock<RetryPolicy>>,
+    stats: Arc<RequestStats>,
+    /// 服务停止信号
+    shutdown: CancellationToken,
     /// Token 自动刷新器
     token_refresher: Option<TokenRefresher>,
+    /// 账号目录监听器（热更新账号）
+    account_watcher: Option<AccountWatcher>,
 }
 
 impl AxumServer {
@@ -45,16 +69,53 @@ impl AxumServer {
         *proxy = new_config;
         tracing::info!("上游代理配置已热更新");
     }
+
+    /// 更新 API 密钥
+    pub async fn update_api_key(&self, new_key: String) {
+        let mut key = self.api_key_state.write().await;
+        *key = new_key;
+        tracing::info!("API 密钥已热更新");
+    }
+
+    /// 更新 API 密钥分组限制
+    pub async fn update_api_key_groups(&self, new_groups: std::collections::HashMap<String, String>) {
+        let mut groups = self.api_key_groups_state.write().await;
+        *groups = new_groups;
+        tracing::info!("API 密钥分组配置已热更新");
+    }
+
+    /// 更新管理接口密钥
+    pub async fn update_admin_key(&self, new_key: String) {
+        let mut key = self.admin_key_state.write().await;
+        *key = new_key;
+        tracing::info!("管理接口密钥已热更新");
+    }
+
+    /// 更新重试策略，只影响之后收到的请求
+    pub async fn update_retry_policy(&self, new_policy: RetryPolicy) {
+        let mut policy = self.retry_policy_state.write().await;
+        *policy = new_policy;
+        tracing::info!("重试策略已热更新");
+    }
+
+    /// 获取请求统计
+    pub fn stats(&self) -> crate::proxy::stats::ProxyStats {
+        self.stats.snapshot()
+    }
+
     /// 启动 Axum 服务器
     pub async fn start(
-        port: u16,
+        config: &crate::proxy::ProxyConfig,
         token_manager: Arc<TokenManager>,
-        anthropic_mapping: std::collections::HashMap<String, String>,
-        request_timeout: u64,
-        upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
-        let mapping_state = Arc::new(tokio::sync::RwLock::new(anthropic_mapping));
-        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy));
+        let mapping_state = Arc::new(tokio::sync::RwLock::new(config.anthropic_mapping.clone()));
+        let proxy_state = Arc::new(tokio::sync::RwLock::new(config.upstream_proxy.clone()));
+        let api_key_state = Arc::new(tokio::sync::RwLock::new(config.api_key.clone()));
+        let api_key_groups_state = Arc::new(tokio::sync::RwLock::new(config.api_key_groups.clone()));
+        let admin_key_state = Arc::new(tokio::sync::RwLock::new(config.admin_key.clone()));
+        let retry_policy_state = Arc::new(tokio::sync::RwLock::new(config.retry.clone()));
+        let stats = Arc::new(RequestStats::default());
+        let shutdown = CancellationToken::new();
 
         // 创建签名管理器
         let signature_manager = Arc::new(SignatureManager::with_defaults());
@@ -66,28 +127,51 @@ impl AxumServer {
             Some(Arc::clone(&signature_manager)),
         );
 
+        // 启动账号目录监听，增量同步新增/更新/删除的账号
+        let account_watcher = AccountWatcher::with_defaults();
+        account_watcher.start(token_manager.clone());
+
         let state = AppState {
             token_manager,
             anthropic_mapping: mapping_state.clone(),
-            request_timeout,
+            request_timeout: config.request_timeout,
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
             signature_manager,
             upstream_proxy: proxy_state.clone(),
+            api_key: api_key_state.clone(),
+            api_key_groups: api_key_groups_state.clone(),
+            admin_key: admin_key_state.clone(),
+            retry_policy: retry_policy_state.clone(),
+            stats: stats.clone(),
+            shutdown: shutdown.clone(),
         };
         
         // 构建路由
-        let app = Router::new()
+        let api = Router::new()
             .route("/v1/chat/completions", post(chat_completions_handler))
             .route("/v1/messages", post(anthropic_messages_handler))
             .route("/v1/models", get(list_models_handler))
+            .route_layer(middleware::from_fn_with_state(state.clone(), track_request))
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_api_key));
+        // 指标包含账号信息，监听非本机地址时需要密钥
+        let mut metrics_router = Router::new().route("/metrics", get(metrics_handler));
+        if !auth::is_loopback_address(&config.bind_address) {
+            metrics_router = metrics_router
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_metrics_key));
+        }
+        let app = Router::new()
+            .merge(api)
+            .nest("/admin", admin::router(state.clone()))
             .route("/healthz", get(health_check_handler))
+            .merge(metrics_router)
+            .layer(middleware::from_fn(request_span))
             .with_state(state);
         
         // 绑定地址
-        let addr = format!("127.0.0.1:{}", port);
+        let addr = format!("{}:{}", config.bind_address, config.port);
         let listener = tokio::net::TcpListener::bind(&addr)
             .await
-            .map_err(|e| format!("端口 {} 绑定失败: {}", port, e))?;
+            .map_err(|e| format!("端口 {} 绑定失败: {}", config.port, e))?;
         
         tracing::info!("反代服务器启动在 http://{}", addr);
         
@@ -98,7 +182,14 @@ impl AxumServer {
             shutdown_tx: Some(shutdown_tx),
             mapping_state,
             proxy_state,
+            api_key_state,
+            api_key_groups_state,
+            admin_key_state,
+            retry_policy_state,
+            stats,
+            shutdown,
             token_refresher: Some(token_refresher),
+            account_watcher: Some(account_watcher),
         };
         
         // 在新任务中启动服务器
@@ -124,6 +215,14 @@ impl AxumServer {
             refresher.stop();
         }
         
+        // 停止账号目录监听
+        if let Some(watcher) = self.account_watcher.take() {
+            watcher.stop();
+        }
+        
+        // 结束事件流等长连接，否则优雅关闭会一直等待
+        self.shutdown.cancel();
+
         // 停止 HTTP 服务器
         if let Some(tx) = self.shutdown_tx.take() {
             let _ = tx.send(());
@@ -133,346 +232,442 @@ impl AxumServer {
 
 // ===== API 处理器 =====
 
+/// 统计 /v1/* 请求的结果
+async fn track_request(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    state.stats.record(response.status().is_success());
+    response
+}
+
+/// 当前请求的 ID，由 request_span 写入请求扩展
+#[derive(Debug, Clone)]
+struct RequestId(String);
+
+/// 为每个请求分配 request_id 并在对应的 tracing span 中处理
+/// 客户端传入合法的 x-request-id 时沿用，响应中回写该请求头便于对照日志
+async fn request_span(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let mut request = request;
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
 /// 请求处理结果
 enum RequestResult {
     Success(Response),
-    Retry(String), // 包含重试原因
+    /// 上游调用失败，由重试策略决定是否重试
+    Upstream(UpstreamError),
     Error(Response),
 }
 
+/// 上游错误按重试策略处理后的下一步
+enum RetryStep {
+    /// 继续使用同一账号重试
+    SameAccount,
+    /// 换下一个账号重试
+    NextAccount,
+    /// 不再重试，返回该响应
+    Stop(Response),
+}
+
+/// 客户端在响应完成前断开连接时事件中使用的状态码
+const CLIENT_CLOSED_REQUEST: u16 = 499;
+
+/// 单个请求的事件发布上下文
+///
+/// 流式响应在处理器返回时只发出了响应头，结束事件由流包装在流结束时通过它发布。
+/// 所有副本释放时仍未发布结束事件（客户端断开导致处理器或响应流被丢弃），补发 Failed
+#[derive(Clone)]
+struct RequestEvents(Arc<RequestEventsInner>);
+
+struct RequestEventsInner {
+    request_id: String,
+    started: std::time::Instant,
+    /// 是否已发布 Completed / Failed
+    finished: std::sync::atomic::AtomicBool,
+}
+
+impl RequestEvents {
+    fn new(request_id: String) -> Self {
+        Self(Arc::new(RequestEventsInner {
+            request_id,
+            started: std::time::Instant::now(),
+            finished: std::sync::atomic::AtomicBool::new(false),
+        }))
+    }
+
+    fn request_id(&self) -> &str {
+        &self.0.request_id
+    }
+
+    fn completed(&self, status: u16) {
+        self.0.finish(|duration_ms| ProxyEventKind::Completed { status, duration_ms });
+    }
+
+    fn failed(&self, status: u16, error: String) {
+        self.0.finish(|duration_ms| ProxyEventKind::Failed { status, error, duration_ms });
+    }
+
+    /// 流式响应中途出错：响应头已按 200 发出，事件中使用上游状态码
+    fn stream_failed(&self, error: &UpstreamError) {
+        self.failed(error.status.unwrap_or(StatusCode::BAD_GATEWAY.as_u16()), error.to_string());
+    }
+}
+
+impl RequestEventsInner {
+    /// 发布结束事件，每个请求只发布一次
+    fn finish(&self, kind: impl FnOnce(u64) -> ProxyEventKind) {
+        if self.finished.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        events::publish(&self.request_id, kind(duration_ms));
+    }
+}
+
+impl Drop for RequestEventsInner {
+    fn drop(&mut self) {
+        self.finish(|duration_ms| ProxyEventKind::Failed {
+            status: CLIENT_CLOSED_REQUEST,
+            error: "client closed".to_string(),
+            duration_ms,
+        });
+    }
+}
+
+/// 记录请求指标并发布请求开始与结束事件
+/// 成功的 SSE 响应由流包装在流结束时发布结束事件
+async fn observe_request(
+    protocol: &'static str,
+    model: String,
+    request_events: &RequestEvents,
+    handler: impl std::future::Future<Output = Response>,
+) -> Response {
+    events::publish(request_events.request_id(), ProxyEventKind::Started { protocol, model: model.clone() });
+
+    let response = handler.await;
+    let status = response.status().as_u16();
+    metrics::record_request(protocol, &model, status);
+
+    if response.status().is_success() {
+        let is_stream = response
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !is_stream {
+            request_events.completed(status);
+        }
+        return response;
+    }
+
+    // 错误响应体是很小的 JSON，读出错误信息后原样放回
+    let (parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, 64 * 1024).await.unwrap_or_default();
+    let error = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned());
+    request_events.failed(status, error);
+    Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
+/// 发布账号选定事件
+fn publish_account_chosen(request_id: &str, token: &ProxyToken, attempt: usize) {
+    events::publish(request_id, ProxyEventKind::AccountChosen {
+        account_id: token.account_id.clone(),
+        email: token.email.clone(),
+        attempt,
+    });
+}
+
+/// 发布重试事件，delay_ms 为 None 时表示轮换到下一个账号
+fn publish_retry(request_id: &str, token: &ProxyToken, reason: &str, delay_ms: Option<u64>) {
+    events::publish(request_id, ProxyEventKind::Retry {
+        email: token.email.clone(),
+        reason: reason.to_string(),
+        delay_ms,
+    });
+}
+
+/// 按重试策略处理一次失败的上游调用
+/// 限流短延迟在同一账号等待，可轮换的错误退避后换下一个账号，次数或时限用尽及不可重试的错误按客户端协议返回
+async fn handle_upstream_error(
+    request_id: &str,
+    protocol: ClientProtocol,
+    token: &ProxyToken,
+    error: UpstreamError,
+    budget: &mut RetryBudget,
+) -> RetryStep {
+    if error.kind == UpstreamErrorKind::RateLimited {
+        metrics::record_account_cooldown(&token.account_id, error.retry_delay_ms);
+    }
+    match budget.decide(&error) {
+        RetryDecision::WaitSameAccount(delay_ms) => {
+            tracing::info!("账号 {} 遇到限流，等待 {}ms 后重试: {}", token.email, delay_ms, error);
+            publish_retry(request_id, token, &error.to_string(), Some(delay_ms));
+            tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+            metrics::record_retry(protocol.label());
+            RetryStep::SameAccount
+        }
+        RetryDecision::Rotate(backoff_ms) => {
+            tracing::warn!("账号 {} 请求失败，轮换账号: {}", token.email, error);
+            metrics::record_rotation(protocol.label());
+            publish_retry(request_id, token, &error.to_string(), None);
+            if backoff_ms > 0 {
+                tracing::debug!("退避 {}ms 后轮换账号", backoff_ms);
+                tokio::time::sleep(tokio::time::Duration::from_millis(backoff_ms)).await;
+            }
+            RetryStep::NextAccount
+        }
+        RetryDecision::Exhausted => {
+            tracing::warn!("账号 {} 请求失败，重试次数或时限已用尽: {}", token.email, error);
+            RetryStep::Stop(error.exhausted_response(protocol))
+        }
+        RetryDecision::Abort => {
+            tracing::error!("账号 {} 请求失败，不再重试: {}", token.email, error);
+            if !error.body.is_empty() {
+                tracing::debug!("上游错误正文: {}", logger::content_preview(&error.body, 500));
+            }
+            RetryStep::Stop(error.client_response(protocol))
+        }
+    }
+}
+
 /// 聊天补全处理器
 async fn chat_completions_handler(
     State(state): State<AppState>,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    Extension(scope): Extension<ApiKeyScope>,
+    headers: HeaderMap,
     Json(request): Json<converter::OpenAIChatRequest>,
 ) -> Response {
-    let max_retries = state.token_manager.len().max(1);
-    let mut attempts = 0;
-    
-    // 克隆请求以支持重试
+    let model = request.model.clone();
+    let group = scope.account_group(&headers);
+    let request_events = RequestEvents::new(request_id);
+    observe_request(
+        metrics::PROTOCOL_OPENAI,
+        model,
+        &request_events,
+        serve_chat_completions(state, request_events.clone(), group, request),
+    )
+    .await
+}
+
+async fn serve_chat_completions(
+    state: AppState,
+    request_events: RequestEvents,
+    group: Option<String>,
+    request: converter::OpenAIChatRequest,
+) -> Response {
+    let upstream = Arc::new(GenerateRequest::from_openai(&request));
     let request = Arc::new(request);
 
+    run_with_retries(&state, request_events.request_id(), ClientProtocol::OpenAI, group, |token| {
+        process_request(state.clone(), request_events.clone(), Arc::clone(&request), Arc::clone(&upstream), token)
+    })
+    .await
+}
+
+/// 在分组内轮换账号执行请求，失败时按 ProxyConfig.retry 等待、换号或返回错误
+///
+/// # 参数
+/// - `protocol`: 客户端协议，决定错误响应格式
+/// - `group`: 限定使用的账号分组，None 时不限制
+/// - `attempt`: 使用给定账号完成一次上游调用
+async fn run_with_retries<F, Fut>(
+    state: &AppState,
+    request_id: &str,
+    protocol: ClientProtocol,
+    group: Option<String>,
+    attempt: F,
+) -> Response
+where
+    F: Fn(ProxyToken) -> Fut,
+    Fut: std::future::Future<Output = RequestResult>,
+{
+    let policy = state.retry_policy.read().await.clone();
+    let mut budget = RetryBudget::new(policy, state.token_manager.len_in_group(group.as_deref()));
+    // 限流短延迟等待后继续使用的账号
+    let mut pinned: Option<ProxyToken> = None;
+
     loop {
-        attempts += 1;
-        
         // 1. 获取 Token
-        let token = match state.token_manager.get_token().await {
-            Some(t) => t,
+        let token = match pinned.take() {
+            Some(token) => token,
             None => {
-                return (
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": "没有可用账号",
-                            "type": "no_accounts"
-                        }
-                    }))
-                ).into_response();
+                let token = match state.token_manager.get_token_in_group(group.as_deref()).await {
+                    Some(t) => t,
+                    None => {
+                        let message = match &group {
+                            Some(g) => format!("分组 {} 中没有可用账号", g),
+                            None => "没有可用账号".to_string(),
+                        };
+                        return protocol.error_response(StatusCode::SERVICE_UNAVAILABLE, "no_accounts", &message);
+                    }
+                };
+                let attempt_no = budget.next_attempt();
+                tracing::info!("尝试使用账号: {} (第 {}/{} 次尝试)", token.email, attempt_no, budget.max_attempts());
+                publish_account_chosen(request_id, &token, attempt_no);
+                token
             }
         };
-        
-        tracing::info!("尝试使用账号: {} (第 {}/{} 次尝试)", token.email, attempts, max_retries);
 
         // 2. 处理请求
-        let result = process_request(state.clone(), request.clone(), token.clone()).await;
-        
+        let upstream_started = std::time::Instant::now();
+        let result = budget.run_attempt(attempt(token.clone())).await;
+        metrics::observe_upstream_latency(protocol.label(), upstream_started.elapsed());
+        let Some(result) = result else {
+            let error = UpstreamError::transport("请求超出重试总时限");
+            tracing::warn!("账号 {} 请求失败，重试次数或时限已用尽: {}", token.email, error);
+            return error.exhausted_response(protocol);
+        };
+
         match result {
-            RequestResult::Success(response) => return response,
-            RequestResult::Retry(reason) => {
-                tracing::warn!("账号 {} 请求失败，准备重试: {}", token.email, reason);
-                if attempts >= max_retries {
-                    return (
-                        StatusCode::TOO_MANY_REQUESTS,
-                        Json(serde_json::json!({
-                            "error": {
-                                "message": format!("所有账号配额已耗尽或请求失败。最后错误: {}", reason),
-                                "type": "all_accounts_exhausted"
-                            }
-                        }))
-                    ).into_response();
+            RequestResult::Success(response) | RequestResult::Error(response) => return response,
+            RequestResult::Upstream(error) => {
+                match handle_upstream_error(request_id, protocol, &token, error, &mut budget).await {
+                    RetryStep::SameAccount => pinned = Some(token),
+                    RetryStep::NextAccount => {}
+                    RetryStep::Stop(response) => return response,
                 }
-                // 继续下一次循环，token_manager.get_token() 会自动轮换
-                continue;
-            },
-            RequestResult::Error(response) => return response,
+            }
         }
     }
 }
 
+/// 创建使用当前上游代理配置的客户端
+async fn upstream_client(state: &AppState) -> GeminiClient {
+    let proxy_config = state.upstream_proxy.read().await.clone();
+    GeminiClient::new(state.request_timeout, Some(proxy_config))
+}
+
 /// 统一请求分发入口
 async fn process_request(
     state: AppState,
+    request_events: RequestEvents,
     request: Arc<converter::OpenAIChatRequest>,
-    token: crate::proxy::token_manager::ProxyToken,
+    upstream: Arc<GenerateRequest>,
+    token: ProxyToken,
 ) -> RequestResult {
+    let project_id = match get_project_id(&token, ClientProtocol::OpenAI) {
+        Ok(id) => id,
+        Err(e) => return RequestResult::Error(e),
+    };
+    let client = upstream_client(&state).await;
     let is_stream = request.stream.unwrap_or(false);
     let is_image_model = request.model.contains("gemini-3-pro-image");
-    
-    if is_stream {
-        if is_image_model {
-            handle_image_stream_request(state, request, token).await
+
+    if !is_stream || is_image_model {
+        let response = match client.generate(&upstream, &token.access_token, project_id, &token.session_id).await {
+            Ok(response) => process_inline_data(response),
+            Err(e) => return RequestResult::Upstream(e),
+        };
+        if is_stream {
+            RequestResult::Success(image_stream_response(request_events, &request.model, &response))
         } else {
-            handle_stream_request(state, request, token).await
+            RequestResult::Success(Json(response).into_response())
         }
     } else {
-        handle_non_stream_request(state, request, token).await
-    }
-}
-
-/// 处理画图模型的流式请求（模拟流式）
-async fn handle_image_stream_request(
-    state: AppState,
-    request: Arc<converter::OpenAIChatRequest>,
-    token: crate::proxy::token_manager::ProxyToken,
-) -> RequestResult {
-    let proxy_config = state.upstream_proxy.read().await.clone();
-    let client = GeminiClient::new(state.request_timeout, Some(proxy_config));
-    let model = request.model.clone();
-    
-    let project_id = match get_project_id(&token) {
-        Ok(id) => id,
-        Err(e) => return RequestResult::Error(e),
-    };
-    
-    let response_result = client.generate(
-        &request,
-        &token.access_token,
-        project_id,
-        &token.session_id,
-    ).await;
-    
-    match response_result {
-        Ok(response) => {
-            // 2. 处理图片转 Markdown
-            let processed_json = process_inline_data(response);
-            
-            // 3. 提取 Markdown 文本
-            // 移除详细调试日志以免刷屏
-            // tracing::info!("Processed Image Response: {}", serde_json::to_string_pretty(&processed_json).unwrap_or_default());
-            tracing::info!("Image generation successful, processing response...");
-
-            let content = processed_json["response"]["candidates"][0]["content"]["parts"][0]["text"]
-                .as_str()
-                .or_else(|| {
-                    // 尝试备用路径：有时候 structure 可能略有不同
-                    tracing::warn!("Standard path for image content failed. Checking response structure...");
-                    processed_json["candidates"][0]["content"]["parts"][0]["text"].as_str()
-                })
-                .unwrap_or("生成图片失败或格式错误")
-                .to_string();
-                
-            // 4. 构造 SSE 流
-            let stream = async_stream::stream! {
-                let chunk = serde_json::json!({
-                    "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                    "object": "chat.completion.chunk",
-                    "created": chrono::Utc::now().timestamp(),
-                    "model": model,
-                    "choices": [
-                        {
-                            "index": 0,
-                            "delta": { "content": content },
-                            "finish_reason": null
-                        }
-                    ]
-                });
-                yield Ok::<_, axum::Error>(Event::default().data(chunk.to_string()));
-                
-                let end_chunk = serde_json::json!({
-                    "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                    "object": "chat.completion.chunk",
-                    "created": chrono::Utc::now().timestamp(),
-                    "model": model,
-                    "choices": [
-                        {
-                            "index": 0,
-                            "delta": {},
-                            "finish_reason": "stop"
-                        }
-                    ]
-                });
-                yield Ok(Event::default().data(end_chunk.to_string()));
-                yield Ok(Event::default().data("[DONE]"));
-            };
-            
-            RequestResult::Success(Sse::new(stream).into_response())
-        },
-        Err(e) => check_retry_error(&e),
+        let stream = match client.stream(&upstream, &token.access_token, project_id, &token.session_id).await {
+            Ok(stream) => stream,
+            Err(e) => return RequestResult::Upstream(e),
+        };
+        match pipeline::peek_first_chunk(stream).await {
+            Ok(stream) => RequestResult::Success(openai_stream_response(request_events, request.model.clone(), stream)),
+            Err(e) => RequestResult::Upstream(e),
+        }
     }
 }
 
-/// 处理流式请求
-async fn handle_stream_request(
-    state: AppState,
-    request: Arc<converter::OpenAIChatRequest>,
-    token: crate::proxy::token_manager::ProxyToken,
-) -> RequestResult {
-    let proxy_config = state.upstream_proxy.read().await.clone();
-    let client = GeminiClient::new(state.request_timeout, Some(proxy_config));
-    
-    let project_id = match get_project_id(&token) {
-        Ok(id) => id,
-        Err(e) => return RequestResult::Error(e),
-    };
-    
-    let stream_result = client.stream_generate(
-        &request,
-        &token.access_token,
-        project_id,
-        &token.session_id,
-    ).await;
-    
-    match stream_result {
-        Ok(stream) => {
-            let sse_stream = stream.map(move |chunk| {
-                match chunk {
-                    Ok(data) => Ok(Event::default().data(data)),
-                    Err(e) => {
-                        tracing::error!("Stream error: {}", e);
-                        Err(axum::Error::new(e))
-                    }
+/// 将上游分片流转换为 OpenAI SSE 响应
+fn openai_stream_response(request_events: RequestEvents, model: String, mut stream: ChunkStream) -> Response {
+    let sse_stream = async_stream::stream! {
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => yield Ok(Event::default().data(chunk.to_openai_chunk(&model).to_string())),
+                Err(e) => {
+                    // 响应头已发出，只能中断连接
+                    tracing::error!("Stream error: {}", e);
+                    request_events.stream_failed(&e);
+                    yield Err(axum::Error::new(e));
+                    return;
                 }
-            });
-            RequestResult::Success(Sse::new(sse_stream).into_response())
-        },
-        Err(e) => check_retry_error(&e),
-    }
-}
-
-/// 处理非流式请求
-async fn handle_non_stream_request(
-    state: AppState,
-    request: Arc<converter::OpenAIChatRequest>,
-    token: crate::proxy::token_manager::ProxyToken,
-) -> RequestResult {
-    let proxy_config = state.upstream_proxy.read().await.clone();
-    let client = GeminiClient::new(state.request_timeout, Some(proxy_config));
-    
-    let project_id = match get_project_id(&token) {
-        Ok(id) => id,
-        Err(e) => return RequestResult::Error(e),
+            }
+        }
+        yield Ok(Event::default().data("[DONE]"));
+        request_events.completed(StatusCode::OK.as_u16());
     };
-    
-    let response_result = client.generate(
-        &request,
-        &token.access_token,
-        project_id,
-        &token.session_id,
-    ).await;
-    
-    match response_result {
-        Ok(response) => {
-            let processed_response = process_inline_data(response);
-            RequestResult::Success(Json(processed_response).into_response())
-        },
-        Err(e) => check_retry_error(&e),
-    }
+    Sse::new(sse_stream).into_response()
 }
 
-/// 辅助函数：获取 Project ID
-fn get_project_id(token: &crate::proxy::token_manager::ProxyToken) -> Result<&str, Response> {
-    token.project_id.as_ref()
-        .map(|s| s.as_str())
-        .ok_or_else(|| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": {
-                        "message": "没有 project_id",
-                        "type": "config_error"
-                    }
-                }))
-            ).into_response()
+/// 画图模型不支持流式，将完整结果包装为 SSE 响应（模拟流式）
+fn image_stream_response(request_events: RequestEvents, model: &str, processed_json: &serde_json::Value) -> Response {
+    tracing::info!("Image generation successful, processing response...");
+
+    let content = processed_json["response"]["candidates"][0]["content"]["parts"][0]["text"]
+        .as_str()
+        .or_else(|| {
+            // 尝试备用路径：有时候 structure 可能略有不同
+            tracing::warn!("Standard path for image content failed. Checking response structure...");
+            processed_json["candidates"][0]["content"]["parts"][0]["text"].as_str()
         })
-}
+        .unwrap_or("生成图片失败或格式错误")
+        .to_string();
 
-/// 辅助函数：检查错误是否需要重试
-fn check_retry_error(error_msg: &str) -> RequestResult {
-    // 检查 404/403 - 跳过当前账号，尝试下一个
-    // 参考 CLIProxyAPI 的做法：遇到 404/403 时继续尝试其他账号
-    if error_msg.contains("404") || error_msg.contains("NOT_FOUND") ||
-       error_msg.contains("403") || error_msg.contains("PERMISSION_DENIED") {
-        return RequestResult::Retry(format!("账号不支持此模型或无权限，跳过: {}", error_msg));
-    }
-    
-    // 检查 429 或者 配额耗尽 关键字
-    if error_msg.contains("429") || 
-       error_msg.contains("RESOURCE_EXHAUSTED") || 
-       error_msg.contains("QUOTA_EXHAUSTED") ||
-       error_msg.contains("The request has been rate limited") ||
-       error_msg.contains("closed connection") ||
-       error_msg.contains("error sending request") ||
-       error_msg.contains("operation timed out") ||
-       error_msg.contains("RATE_LIMIT_EXCEEDED") {
-        return RequestResult::Retry(error_msg.to_string());
-    }
-    
-    // 其他错误直接返回
-    RequestResult::Error((
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(serde_json::json!({
-            "error": {
-                "message": format!("Antigravity API 错误: {}", error_msg),
-                "type": "api_error"
-            }
-        }))
-    ).into_response())
-}
+    let chunk = |delta: serde_json::Value, finish_reason: Option<&str>| {
+        serde_json::json!({
+            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
+            "object": "chat.completion.chunk",
+            "created": chrono::Utc::now().timestamp(),
+            "model": model,
+            "choices": [
+                {
+                    "index": 0,
+                    "delta": delta,
+                    "finish_reason": finish_reason
+                }
+            ]
+        })
+        .to_string()
+    };
+    let events = vec![
+        chunk(serde_json::json!({ "content": content }), None),
+        chunk(serde_json::json!({}), Some("stop")),
+        "[DONE]".to_string(),
+    ];
 
-/// 增强版重试检查：支持解析 429 响应中的 retryDelay
-/// 返回 (RequestResult, Option<u64>) - 第二个值是需要等待的毫秒数（如果是短延迟重试）
-fn check_retry_error_enhanced(status: u16, error_msg: &str) -> (RequestResult, Option<u64>) {
-    // 使用 RetryDelayParser 决定重试策略
-    let action = RetryDelayParser::decide_retry_action(status, error_msg);
-    
-    match action {
-        RetryAction::WaitAndRetry(delay_ms) => {
-            tracing::info!("429 短延迟重试: 等待 {}ms 后重试同一账号", delay_ms);
-            (RequestResult::Retry(format!("429 短延迟重试 ({}ms)", delay_ms)), Some(delay_ms))
-        },
-        RetryAction::RotateAccount => {
-            // 404/403/429(长延迟) 都轮换账号
-            let reason = if status == 404 {
-                format!("账号不支持此模型 (404): {}", error_msg)
-            } else if status == 403 {
-                format!("账号无权限 (403): {}", error_msg)
-            } else {
-                format!("配额耗尽或限流 ({}): {}", status, error_msg)
-            };
-            (RequestResult::Retry(reason), None)
-        },
-        RetryAction::NoRetry => {
-            // 其他错误直接返回
-            (RequestResult::Error((
-                StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                Json(serde_json::json!({
-                    "error": {
-                        "message": format!("Antigravity API 错误: {}", error_msg),
-                        "type": "api_error"
-                    }
-                }))
-            ).into_response()), None)
+    let stream = async_stream::stream! {
+        for data in events {
+            yield Ok::<_, axum::Error>(Event::default().data(data));
         }
-    }
+        request_events.completed(StatusCode::OK.as_u16());
+    };
+    Sse::new(stream).into_response()
 }
 
-/// 从错误消息中提取 HTTP 状态码
-fn extract_status_from_error(error_msg: &str) -> u16 {
-    // 尝试从 "上游服务错误 (429):" 格式中提取
-    if let Some(start) = error_msg.find('(') {
-        if let Some(end) = error_msg[start..].find(')') {
-            if let Ok(status) = error_msg[start+1..start+end].parse::<u16>() {
-                return status;
-            }
-        }
-    }
-    
-    // 回退到关键字检测
-    if error_msg.contains("429") { return 429; }
-    if error_msg.contains("404") { return 404; }
-    if error_msg.contains("403") { return 403; }
-    if error_msg.contains("500") { return 500; }
-    
-    500 // 默认
+/// 辅助函数：获取 Project ID
+fn get_project_id(token: &ProxyToken, protocol: ClientProtocol) -> Result<&str, Response> {
+    token.project_id.as_deref().ok_or_else(|| {
+        protocol.error_response(StatusCode::INTERNAL_SERVER_ERROR, "config_error", "没有 project_id")
+    })
 }
 
 /// 模型列表处理器
@@ -480,37 +675,28 @@ async fn list_models_handler(
     State(_state): State<AppState>,
 ) -> Response {
     // 返回 Antigravity 实际可用的模型列表
+    let data: Vec<serde_json::Value> = crate::proxy::model_mapper::LISTED_MODELS
+        .iter()
+        .map(|(id, owned_by, created)| serde_json::json!({
+            "id": id, "object": "model", "created": created, "owned_by": owned_by, "permission": []
+        }))
+        .collect();
     let models = serde_json::json!({
         "object": "list",
-        "data": [
-            // Gemini Native (from Log)
-            { "id": "gemini-2.5-flash-thinking", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-2.5-flash", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-2.5-flash-lite", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-2.5-pro", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-low", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-high", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-flash", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-
-            // Claude Native (from Log)
-            { "id": "claude-sonnet-4-5", "object": "model", "created": 1734336000, "owned_by": "anthropic", "permission": [] },
-            { "id": "claude-sonnet-4-5-thinking", "object": "model", "created": 1734336000, "owned_by": "anthropic", "permission": [] },
-            { "id": "claude-opus-4-5-thinking", "object": "model", "created": 1734336000, "owned_by": "anthropic", "permission": [] },
-
-            // Internal Image Models
-            { "id": "gemini-3-pro-image", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-image-16x9", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-image-9x16", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-image-4k", "object": "model", "created": 1734336000, "owned_by": "google", "permission": [] },
-            { "id": "gemini-2.5-flash-image", "object": "model", "created": 1759363200, "owned_by": "google", "permission": [] },
-            { "id": "gemini-2.5-flash-image-preview", "object": "model", "created": 1756166400, "owned_by": "google", "permission": [] },
-            { "id": "gemini-3-pro-image-preview", "object": "model", "created": 1737158400, "owned_by": "google", "permission": [] }
-        ]
+        "data": data
     });
     
     Json(models).into_response()
 }
 
+/// Prometheus 指标
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        metrics::render(&state.token_manager, &state.signature_manager),
+    ).into_response()
+}
+
 /// 健康检查处理器
 async fn health_check_handler() -> Response {
     Json(serde_json::json!({
@@ -580,7 +766,28 @@ fn process_inline_data(mut response: serde_json::Value) -> serde_json::Value {
 /// Anthropic Messages 处理器
 async fn anthropic_messages_handler(
     State(state): State<AppState>,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+    Extension(scope): Extension<ApiKeyScope>,
+    headers: HeaderMap,
     Json(request): Json<converter::AnthropicChatRequest>,
+) -> Response {
+    let model = request.model.clone();
+    let group = scope.account_group(&headers);
+    let request_events = RequestEvents::new(request_id);
+    observe_request(
+        metrics::PROTOCOL_ANTHROPIC,
+        model,
+        &request_events,
+        serve_anthropic_messages(state, request_events.clone(), group, request),
+    )
+    .await
+}
+
+async fn serve_anthropic_messages(
+    state: AppState,
+    request_events: RequestEvents,
+    group: Option<String>,
+    request: converter::AnthropicChatRequest,
 ) -> Response {
     // 记录请求信息
     let stream_mode = request.stream.unwrap_or(true);
@@ -590,11 +797,7 @@ async fn anthropic_messages_handler(
         if let Some(first_content) = first_msg.content.first() {
             match first_content {
                 converter::AnthropicContent::Text { text } => {
-                    if text.len() > 50 {
-                        format!("{}...", &text[..50])
-                    } else {
-                        text.clone()
-                    }
+                    logger::content_preview(text, 50)
                 },
                 converter::AnthropicContent::Image { .. } => {
                     "[图片]".to_string()
@@ -615,385 +818,239 @@ async fn anthropic_messages_handler(
     } else {
         "无消息".to_string()
     };
-    
-    // 预处理：解析映射后的模型名（仅用于日志显示，实际逻辑在 client 中也会再次处理，或者我们可以这里处理完传进去）
-    // 为了保持一致性，我们复用简单的查找逻辑用于日志
-    let mapped_model = {
+
+    let upstream = {
         let mapping_guard = state.anthropic_mapping.read().await;
-            // 鲁棒模糊模型映射 (参考 CLIProxyAPI 经验，与 client.rs 同步)
-            let initial_m = {
-                let mut tmp = request.model.clone();
-                for (k, v) in mapping_guard.iter() {
-                    if request.model.contains(k) {
-                        tmp = v.clone();
-                        break;
-                    }
-                }
-                tmp
-            };
-            
-            let lower_name = initial_m.to_lowercase();
-            // 最终 API 型号转换：将内部型号转换为 Antigravity Daily API 实际支持的名称
-            if lower_name.contains("sonnet") || lower_name.contains("thinking") {
-                "gemini-3-pro-preview".to_string()
-            } else if lower_name.contains("haiku") {
-                "gemini-2.0-flash-exp".to_string()
-            } else if lower_name.contains("opus") {
-                "gemini-3-pro-preview".to_string()
-            } else if lower_name.contains("claude") {
-                "gemini-2.5-flash-thinking".to_string()
-            } else if lower_name == "gemini-3-pro-high" || lower_name == "gemini-3-pro-low" {
-                "gemini-3-pro-preview".to_string()
-            } else if lower_name == "gemini-3-flash" {
-                "gemini-3-flash-preview".to_string()
-            } else {
-                initial_m
-            }
+        GenerateRequest::from_anthropic(&request, &mapping_guard, state.thought_signature_map.clone()).await
     };
 
-    // 截断过长的消息预览
-    let truncated_preview = if first_msg_preview.len() > 50 {
-        format!("{}...", &first_msg_preview[..50])
-    } else {
-        first_msg_preview.clone()
-    };
-    
     tracing::info!(
         "(Anthropic) 请求 {} → {} | 消息数:{} | 流式:{} | 预览:{}",
         request.model,
-        mapped_model,
+        upstream.upstream_model,
         msg_count,
         if stream_mode { "是" } else { "否" },
-        truncated_preview
+        first_msg_preview
     );
-    let max_retries = state.token_manager.len().max(1);
-    let mut attempts = 0;
-    
+
     // Check if stream is requested. Default to false? Anthropic usually true for interactive.
     let is_stream = request.stream.unwrap_or(false);
-    
-    // Clone request for retries
-    let request = Arc::new(request);
+    let upstream = Arc::new(upstream);
 
-    loop {
-        attempts += 1;
-        
-        // 1. 获取 Token
-        let token = match state.token_manager.get_token().await {
-            Some(t) => t,
-            None => {
-                 return (
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    Json(serde_json::json!({
-                        "type": "error",
-                        "error": {
-                            "type": "overloaded_error",
-                            "message": "No available accounts"
-                        }
-                    }))
-                ).into_response();
-            }
-        };
-        
-        tracing::info!("(Anthropic) 尝试使用账号: {} (第 {}/{} 次尝试)", token.email, attempts, max_retries);
+    run_with_retries(&state, request_events.request_id(), ClientProtocol::Anthropic, group, |token| {
+        let state = state.clone();
+        let request_events = request_events.clone();
+        let upstream = Arc::clone(&upstream);
+        async move {
+            let project_id = match get_project_id(&token, ClientProtocol::Anthropic) {
+                Ok(id) => id,
+                Err(e) => return RequestResult::Error(e),
+            };
+            let stream = match upstream_client(&state)
+                .await
+                .stream(&upstream, &token.access_token, project_id, &token.session_id)
+                .await
+            {
+                Ok(stream) => stream,
+                Err(e) => return RequestResult::Upstream(e),
+            };
 
-        // 2. 发起请求
-        // Helper logic inline to support retries
-        let proxy_config = state.upstream_proxy.read().await.clone();
-        let client = GeminiClient::new(state.request_timeout, Some(proxy_config));
-        let project_id_result = get_project_id(&token);
-        
-        if let Err(e) = project_id_result {
-             // If config error, don't retry, just fail
-             return e; // e is Response
+            if is_stream {
+                match pipeline::peek_first_chunk(stream).await {
+                    Ok(stream) => RequestResult::Success(anthropic_stream_response(state, request_events, upstream.model.clone(), token, stream)),
+                    Err(e) => RequestResult::Upstream(e),
+                }
+            } else {
+                collect_anthropic_response(&state, &upstream.model, &token, stream).await
+            }
         }
-        let project_id = project_id_result.unwrap();
+    })
+    .await
+}
 
-        let mapping_guard = state.anthropic_mapping.read().await;
-        
-        if is_stream {
-             let stream_result = client.stream_generate_anthropic(
-                &request,
-                &token.access_token,
-                project_id,
-                &token.session_id,
-                &mapping_guard,
-                state.thought_signature_map.clone(),
-                Some(Arc::clone(&state.signature_manager)), // 新增：SignatureManager
-            ).await;
-            
-            match stream_result {
-                Ok(stream) => {
-                    let mut stream = stream;
-                    
-                    // ⚠️ 预检：如果第一个分片就是错误（例如我们刚加的空响应错误），则触发重试
-                    let first_chunk = match futures::StreamExt::next(&mut stream).await {
-                        Some(Ok(chunk)) => chunk,
-                        Some(Err(e)) => {
-                            let check = check_retry_error(&e);
-                            match check {
-                                RequestResult::Retry(reason) => {
-                                    tracing::warn!("(Anthropic) 账号 {} 请求失败，重试: {}", token.email, reason);
-                                    if attempts >= max_retries {
-                                        return (
-                                            StatusCode::TOO_MANY_REQUESTS,
-                                            Json(serde_json::json!({
-                                                "type": "error",
-                                                "error": { "type": "rate_limit_error", "message": format!("Max retries exceeded. Last error: {}", reason) }
-                                            }))
-                                        ).into_response();
-                                    }
-                                    continue;
-                                },
-                                RequestResult::Error(resp) => return resp,
-                                RequestResult::Success(resp) => return resp,
-                            }
-                        },
-                        None => continue,
-                    };
-
-                    // Success! Convert stream to Anthropic SSE
-                    let msg_id = format!("msg_{}", uuid::Uuid::new_v4());
-                    let token_clone = token.clone();
-                    let _request_clone = Arc::clone(&request);
-                    let mut _total_content_length = 0;
-                    let mut total_content = String::new(); 
-                    let model_name = request.model.clone();
-
-                    // 将拿出的第一个分片重新包装回流中
-                    let combined_stream = futures::stream::once(futures::future::ready(Ok(first_chunk))).chain(stream);
-                    
-                     let sse_stream = async_stream::stream! {
-                        // 1. send message_start
-                        let start_event = crate::proxy::claude_converter::ClaudeStreamConverter::create_message_start(&msg_id, &model_name);
-                        yield Ok::<_, axum::Error>(Event::default().event(start_event.event).data(start_event.data));
-
-                        // 状态机
-                        let mut converter = crate::proxy::claude_converter::ClaudeStreamConverter::new();
-                         
-                        // 2. Loop over combined stream
-                        for await chunk_result in combined_stream {
-                            match chunk_result {
-                                Ok(chunk_str) => {
-                                    if chunk_str == "[DONE]" { continue; }
-                                    
-                                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&chunk_str) {
-                                        // 记录请求详情以便调试 promptFeedback
-                                        if json.get("candidates").is_none() && json.get("choices").is_none() {
-                                             if let Some(feedback) = json.get("promptFeedback") {
-                                                tracing::warn!("(Anthropic) 收到 promptFeedback (可能被拦截): {}", feedback);
-                                             }
-                                        }
-
-                                        let events = converter.process_chunk(&json);
-                                        for event in events {
-                                            if event.event == "content_block_delta" {
-                                                if let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.data) {
-                                                    if let Some(delta) = data.get("delta") {
-                                                        if let Some(text) = delta.get("text").and_then(|t| t.as_str()) {
-                                                             _total_content_length += text.len();
-                                                             total_content.push_str(text);
-                                                        } else if let Some(thinking) = delta.get("thinking").and_then(|t| t.as_str()) {
-                                                             // 同时也记录 thinking 内容
-                                                             _total_content_length += thinking.len();
-                                                             total_content.push_str(thinking);
-                                                        }
-                                                    }
-                                                }
-                                            } else if event.event == "message_stop" {
-                                                if total_content.is_empty() {
-                                                    tracing::warn!(
-                                                        "(Anthropic) ✓ {} | 回答为空 (可能是 Gemini 返回了非文本数据)",
-                                                        token_clone.email
-                                                    );
-                                                } else {
-                                                    let response_preview: String = total_content.chars().take(100).collect();
-                                                    let suffix = if total_content.chars().count() > 100 { "..." } else { "" };
-                                                    
-                                                    tracing::info!(
-                                                        "(Anthropic) ✓ {} | 回答: {}{}",
-                                                        token_clone.email,
-                                                        response_preview,
-                                                        suffix
-                                                    );
-                                                }
-                                            }
-                                            yield Ok(Event::default().event(event.event).data(event.data));
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::error!("Stream error: {}", e);
-                                    let err_check = check_retry_error(&e);
-                                    if let RequestResult::Retry(reason) = err_check {
-                                         tracing::warn!("Stream interrupted (retryable): {}", reason);
-                                    }
-                                }
+/// 保存上游返回的思维链签名，供后续请求回填 thinking 块
+async fn capture_thought_signature(state: &AppState, chunk: &GenerateChunk) {
+    let Some(signature) = &chunk.thought_signature else {
+        return;
+    };
+    let key = chunk.response_id.as_deref().unwrap_or("latest");
+    state.signature_manager.store_signature(key, signature);
+    // 同时存入兼容的 map（保持向后兼容）
+    state.thought_signature_map.lock().await.insert(key.to_string(), signature.clone());
+    tracing::debug!("(Anthropic) 捕获到 thoughtSignature 并已存入 SignatureManager");
+}
+
+/// 将上游分片流转换为 Anthropic SSE 响应
+fn anthropic_stream_response(
+    state: AppState,
+    request_events: RequestEvents,
+    model_name: String,
+    token: ProxyToken,
+    mut stream: ChunkStream,
+) -> Response {
+    let msg_id = format!("msg_{}", uuid::Uuid::new_v4());
+
+    let sse_stream = async_stream::stream! {
+        // 1. send message_start
+        let start_event = crate::proxy::claude_converter::ClaudeStreamConverter::create_message_start(&msg_id, &model_name);
+        yield Ok::<_, axum::Error>(Event::default().event(start_event.event).data(start_event.data));
+
+        // 状态机
+        let mut converter = crate::proxy::claude_converter::ClaudeStreamConverter::new();
+        let mut total_content = String::new();
+
+        // 2. Loop over stream
+        while let Some(chunk_result) = stream.next().await {
+            match chunk_result {
+                Ok(chunk) => {
+                    if let Some(feedback) = &chunk.prompt_feedback {
+                        tracing::warn!("(Anthropic) 收到 promptFeedback (可能被拦截): {}", feedback);
+                    }
+                    capture_thought_signature(&state, &chunk).await;
+                    total_content.push_str(&chunk.text);
+
+                    for event in converter.process(&chunk) {
+                        if event.event == "message_stop" {
+                            if total_content.is_empty() {
+                                tracing::warn!(
+                                    "(Anthropic) ✓ {} | 回答为空 (可能是 Gemini 返回了非文本数据)",
+                                    token.email
+                                );
+                            } else {
+                                tracing::info!(
+                                    "(Anthropic) ✓ {} | 回答: {}",
+                                    token.email,
+                                    logger::content_preview(&total_content, 100)
+                                );
                             }
                         }
-                    };
-                    
-                    return Sse::new(sse_stream).into_response();
-                },
-                Err(e_msg) => {
-                    // 使用增强版重试检查，支持 429 短延迟重试
-                    let status = extract_status_from_error(&e_msg);
-                    let (check, wait_ms) = check_retry_error_enhanced(status, &e_msg);
-                    
-                    match check {
-                        RequestResult::Retry(reason) => {
-                            // 如果是短延迟重试，先等待
-                            if let Some(delay) = wait_ms {
-                                tracing::info!("(Anthropic) 账号 {} 遇到 429，等待 {}ms 后重试同一账号", token.email, delay);
-                                tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-                                // 短延迟重试不增加 attempts 计数，直接重试同一账号
-                                continue;
-                            }
-                            
-                            tracing::warn!("(Anthropic) 账号 {} 请求失败，轮换账号: {}", token.email, reason);
-                            if attempts >= max_retries {
-                                return (
-                                    StatusCode::TOO_MANY_REQUESTS,
-                                    Json(serde_json::json!({
-                                        "type": "error",
-                                        "error": { "type": "rate_limit_error", "message": format!("Max retries exceeded. Last error: {}", reason) }
-                                    }))
-                                ).into_response();
-                            }
-                            continue;
-                        },
-                        RequestResult::Error(resp) => return resp,
-                        RequestResult::Success(resp) => return resp,
+                        yield Ok(Event::default().event(event.event).data(event.data));
                     }
                 }
+                Err(e) => {
+                    // 响应头已发出，无法再换账号，按 Anthropic 协议发送 error 事件后结束
+                    tracing::error!("(Anthropic) 流中断: {}", e);
+                    request_events.stream_failed(&e);
+                    let body = e.anthropic_body(&e.to_string());
+                    yield Ok(Event::default().event("error").data(body.to_string()));
+                    return;
+                }
             }
+        }
+        request_events.completed(StatusCode::OK.as_u16());
+    };
 
-        } else {
-            // Non-stream: collect streaming response and convert to non-streaming format
-            let mapping_guard = state.anthropic_mapping.read().await;
-            
-            let stream_result = client.stream_generate_anthropic(
-                &request,
-                &token.access_token,
-                project_id,
-                &token.session_id,
-                &mapping_guard,
-                state.thought_signature_map.clone(),
-                Some(Arc::clone(&state.signature_manager)), // 新增：SignatureManager
-            ).await;
-            
-            match stream_result {
-                Ok(mut stream) => {
-                    let mut full_text = String::new();
-                    let mut stop_reason = "end_turn";
-                    
-                    // Collect all chunks
-                    while let Some(chunk_result) = stream.next().await {
-                        match chunk_result {
-                            Ok(chunk_str) => {
-                                if chunk_str == "[DONE]" { continue; }
-                                
-                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&chunk_str) {
-                                    if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                        full_text.push_str(content);
-                                    }
-                                    if let Some(reason) = json["choices"][0]["finish_reason"].as_str() {
-                                        stop_reason = match reason {
-                                            "stop" => "end_turn",
-                                            "length" => "max_tokens",
-                                            _ => "end_turn"
-                                        };
-                                    }
-                                }
-                            }
-                            Err(_) => {}
-                        }
-                    }
-                    
-                    // 收集完后检查是否为空且为 MAX_TOKENS 或 STOP
-                    // 使用 RetryDelayParser 统一检查逻辑
-                    let gemini_finish_reason = match stop_reason {
-                        "max_tokens" => Some("MAX_TOKENS"),
-                        "end_turn" => Some("STOP"),
-                        _ => None,
-                    };
-                    if RetryDelayParser::should_retry_empty_response(&full_text, gemini_finish_reason) {
-                        tracing::warn!("(Anthropic) 非流式：检测到空响应且原因为 {:?}，触发重试...", gemini_finish_reason);
-                        if attempts >= max_retries {
-                            // 同上错误返回
-                             return (StatusCode::TOO_MANY_REQUESTS, Json(serde_json::json!({ "error": { "message": "Max retries exceeded due to empty MAX_TOKENS responses" } }))).into_response();
-                        }
-                        continue;
-                    }
-                    
-                    // Build Anthropic non-streaming response
-                    let response = serde_json::json!({
-                        "id": format!("msg_{}", uuid::Uuid::new_v4()),
-                        "type": "message",
-                        "role": "assistant",
-                        "model": request.model,
-                        "content": [{
-                            "type": "text",
-                            "text": full_text
-                        }],
-                        "stop_reason": stop_reason,
-                        "stop_sequence": null,
-                        "usage": {
-                            "input_tokens": 0,
-                            "output_tokens": 0
-                        }
-                    });
-                    
-                    // 记录响应(截取前60字符)
-                    let answer_text = response["content"].as_array()
-                        .and_then(|arr| arr.first())
-                        .and_then(|c| c["text"].as_str())
-                        .unwrap_or("");
-                    let response_preview: String = answer_text.chars().take(60).collect();
-                    let suffix = if answer_text.chars().count() > 60 { "..." } else { "" };
-                    
-                    tracing::info!(
-                        "(Anthropic) ✓ {} | 回答: {}{}",
-                        token.email, response_preview, suffix
-                    );
-                    
-                    return (StatusCode::OK, Json(response)).into_response();
-                },
-                Err(e_msg) => {
-                    // 使用增强版重试检查，支持 429 短延迟重试
-                    let status = extract_status_from_error(&e_msg);
-                    let (check, wait_ms) = check_retry_error_enhanced(status, &e_msg);
-                    
-                    match check {
-                        RequestResult::Retry(reason) => {
-                            // 如果是短延迟重试，先等待
-                            if let Some(delay) = wait_ms {
-                                tracing::info!("(Anthropic) 账号 {} 遇到 429，等待 {}ms 后重试同一账号", token.email, delay);
-                                tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-                                // 短延迟重试不增加 attempts 计数，直接重试同一账号
-                                continue;
-                            }
-                            
-                            tracing::warn!("(Anthropic) 账号 {} 请求失败，轮换账号: {}", token.email, reason);
-                            if attempts >= max_retries {
-                                return (
-                                    StatusCode::TOO_MANY_REQUESTS,
-                                    Json(serde_json::json!({
-                                        "type": "error",
-                                        "error": {
-                                            "type": "rate_limit_error",
-                                            "message": format!("Max retries exceeded. Last error: {}", reason)
-                                        }
-                                    }))
-                                ).into_response();
-                            }
-                            continue;
-                        },
-                        RequestResult::Error(resp) => return resp,
-                        RequestResult::Success(resp) => return resp,
-                    }
+    Sse::new(sse_stream).into_response()
+}
+
+/// 收集上游分片并构建 Anthropic 非流式响应
+async fn collect_anthropic_response(
+    state: &AppState,
+    model_name: &str,
+    token: &ProxyToken,
+    mut stream: ChunkStream,
+) -> RequestResult {
+    let mut full_text = String::new();
+    let mut stop_reason = "end_turn";
+    let mut finish_reason: Option<String> = None;
+    let mut usage = Usage::default();
+
+    // Collect all chunks
+    while let Some(chunk_result) = stream.next().await {
+        match chunk_result {
+            Ok(chunk) => {
+                capture_thought_signature(state, &chunk).await;
+                full_text.push_str(&chunk.text);
+                if let Some(reason) = chunk.anthropic_stop_reason() {
+                    stop_reason = reason;
+                    finish_reason = chunk.finish_reason.clone();
+                }
+                if let Some(chunk_usage) = chunk.usage {
+                    usage = chunk_usage;
                 }
             }
+            // 还没有收到内容时交由重试策略处理，否则保留已有结果
+            Err(e) if full_text.is_empty() => return RequestResult::Upstream(e),
+            Err(e) => {
+                tracing::warn!("(Anthropic) 非流式：读取中断，返回已收到的内容: {}", e);
+                break;
+            }
+        }
+    }
+
+    // 收集完后检查是否为空且为 MAX_TOKENS 或 STOP，未收到结束原因时按 STOP 处理
+    let gemini_finish_reason = finish_reason.as_deref().unwrap_or("STOP");
+    if RetryDelayParser::should_retry_empty_response(&full_text, Some(gemini_finish_reason)) {
+        return RequestResult::Upstream(UpstreamError::empty_response(gemini_finish_reason));
+    }
+
+    // Build Anthropic non-streaming response
+    let response = serde_json::json!({
+        "id": format!("msg_{}", uuid::Uuid::new_v4()),
+        "type": "message",
+        "role": "assistant",
+        "model": model_name,
+        "content": [{
+            "type": "text",
+            "text": full_text
+        }],
+        "stop_reason": stop_reason,
+        "stop_sequence": null,
+        "usage": {
+            "input_tokens": usage.input_tokens,
+            "output_tokens": usage.output_tokens
+        }
+    });
+
+    // 记录响应(按日志内容记录级别截取前60字符)
+    tracing::info!(
+        "(Anthropic) ✓ {} | 回答: {}",
+        token.email, logger::content_preview(&full_text, 60)
+    );
+
+    RequestResult::Success((StatusCode::OK, Json(response)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 读取指定请求的下一条事件，忽略其他测试同时发布的事件
+    async fn next_event(rx: &mut tokio::sync::broadcast::Receiver<events::ProxyEvent>, request_id: &str) -> ProxyEventKind {
+        loop {
+            let event = rx.recv().await.unwrap();
+            if event.request_id == request_id {
+                return event.kind;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_events_publish_once() {
+        let mut rx = events::subscribe();
+
+        // 流被丢弃时所有副本释放，补发客户端断开
+        let request_events = RequestEvents::new("req-dropped".to_string());
+        let stream_copy = request_events.clone();
+        drop(request_events);
+        drop(stream_copy);
+        match next_event(&mut rx, "req-dropped").await {
+            ProxyEventKind::Failed { status, .. } => assert_eq!(status, CLIENT_CLOSED_REQUEST),
+            other => panic!("应为 failed 事件，实际为 {:?}", other),
+        }
+
+        // 已完成的请求释放时不再补发
+        let request_events = RequestEvents::new("req-done".to_string());
+        request_events.completed(200);
+        drop(request_events);
+        RequestEvents::new("req-marker".to_string()).completed(200);
+        assert!(matches!(next_event(&mut rx, "req-done").await, ProxyEventKind::Completed { status: 200, .. }));
+        loop {
+            let event = rx.recv().await.unwrap();
+            assert_ne!(event.request_id, "req-done", "结束事件只应发布一次");
+            if event.request_id == "req-marker" {
+                break;
+            }
         }
     }
 }
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 反代服务统计
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProxyStats {
+    pub total_requests: u64,
+    pub success_count: u64,
+    pub error_count: u64,
+}
+
+/// 反代请求计数器（服务启动后累计）
+#[derive(Debug, Default)]
+pub struct RequestStats {
+    success: AtomicU64,
+    error: AtomicU64,
+}
+
+impl RequestStats {
+    /// 记录一次已完成的请求
+    pub fn record(&self, success: bool) {
+        let counter = if success { &self.success } else { &self.error };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 读取当前统计
+    pub fn snapshot(&self) -> ProxyStats {
+        let success_count = self.success.load(Ordering::Relaxed);
+        let error_count = self.error.load(Ordering::Relaxed);
+        ProxyStats {
+            total_requests: success_count + error_count,
+            success_count,
+            error_count,
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use dashmap::DashMap;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use crate::proxy::token_refresher::TokenRefresher;
+use crate::proxy::metrics;
 
 #[derive(Debug, Clone)]
 pub struct ProxyToken {
@@ -15,14 +17,44 @@ pub struct ProxyToken {
     pub account_path: PathBuf,  // 账号文件路径，用于更新
     pub project_id: Option<String>,
     pub session_id: String,  // sessionId
+    pub groups: Vec<String>,  // 账号分组，用于按分组路由
+    pub quota_exhausted: bool,  // 最近一次配额刷新显示已无可用配额
+}
+
+impl ProxyToken {
+    /// 账号是否属于指定分组（不区分大小写）
+    pub fn in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|g| g.eq_ignore_ascii_case(group))
+    }
+
+    /// 是否可作为候选账号
+    fn is_candidate(&self, group: Option<&str>, skip_exhausted: bool) -> bool {
+        group.map_or(true, |g| self.in_group(g)) && !(skip_exhausted && self.quota_exhausted)
+    }
+}
+
+/// 根据账号文件中的配额判断是否已耗尽：被禁止访问，或所有模型剩余配额均为 0
+fn is_quota_exhausted(quota: &serde_json::Value) -> bool {
+    if quota.get("is_forbidden").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return true;
+    }
+    match quota.get("models").and_then(|v| v.as_array()) {
+        Some(models) if !models.is_empty() => models.iter()
+            .all(|m| m.get("percentage").and_then(|v| v.as_i64()).unwrap_or(0) <= 0),
+        _ => false,
+    }
 }
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
+    /// 每个账号一把刷新锁，保证同一账号同一时刻只有一个刷新与写文件操作
+    refresh_locks: Arc<DashMap<String, Arc<tokio::sync::Mutex<()>>>>,
     current_index: Arc<AtomicUsize>,
     data_dir: PathBuf,
     /// Token 自动刷新器
     refresher: Option<TokenRefresher>,
+    /// 用于向前端发送事件（如账号需要重新授权）
+    app_handle: Option<tauri::AppHandle>,
 }
 
 impl TokenManager {
@@ -30,13 +62,29 @@ impl TokenManager {
     pub fn new(data_dir: PathBuf) -> Self {
         Self {
             tokens: Arc::new(DashMap::new()),
+            refresh_locks: Arc::new(DashMap::new()),
             current_index: Arc::new(AtomicUsize::new(0)),
             data_dir,
             refresher: None,
+            app_handle: None,
         }
     }
+
+    /// 设置用于发送前端事件的 AppHandle
+    pub fn with_app_handle(mut self, app_handle: tauri::AppHandle) -> Self {
+        self.app_handle = Some(app_handle);
+        self
+    }
+    
+    /// 用于发送前端事件的 AppHandle，无界面模式下为 None
+    pub fn app_handle(&self) -> Option<&tauri::AppHandle> {
+        self.app_handle.as_ref()
+    }
     
     /// 从主应用账号目录加载所有账号
+    /// 
+    /// 与目录内容做全量同步：新增和更新的账号写入内存，
+    /// 已删除、已禁用或退出反代池的账号从轮换中移除
     pub async fn load_accounts(&self) -> Result<usize, String> {
         let accounts_dir = self.data_dir.join("accounts");
         
@@ -48,6 +96,7 @@ impl TokenManager {
             .map_err(|e| format!("读取账号目录失败: {}", e))?;
         
         let mut count = 0;
+        let mut seen = HashSet::new();
         
         for entry in entries {
             let entry = entry.map_err(|e| format!("读取目录项失败: {}", e))?;
@@ -60,8 +109,8 @@ impl TokenManager {
             // 尝试加载账号
             match self.load_single_account(&path).await {
                 Ok(Some(token)) => {
-                    let account_id = token.account_id.clone();
-                    self.tokens.insert(account_id, token);
+                    seen.insert(token.account_id.clone());
+                    self.upsert_token(token);
                     count += 1;
                 },
                 Ok(None) => {
@@ -69,20 +118,48 @@ impl TokenManager {
                 },
                 Err(e) => {
                     tracing::warn!("加载账号失败 {:?}: {}", path, e);
+                    // 文件可能正在被写入，保留内存中已有的账号，等待下次同步
+                    for entry in self.tokens.iter() {
+                        if entry.account_path == path {
+                            seen.insert(entry.key().clone());
+                        }
+                    }
                 }
             }
         }
         
+        let before = self.tokens.len();
+        self.tokens.retain(|account_id, _| seen.contains(account_id));
+        let removed = before - self.tokens.len();
+        if removed > 0 {
+            tracing::info!("已从轮换中移除 {} 个账号", removed);
+        }
+        
         Ok(count)
     }
+
+    /// 写入或更新内存中的账号
+    /// 已存在的账号保留原有 sessionId，避免热更新打断上游会话
+    fn upsert_token(&self, mut token: ProxyToken) {
+        if let Some(existing) = self.tokens.get(&token.account_id) {
+            token.session_id = existing.session_id.clone();
+        }
+        self.tokens.insert(token.account_id.clone(), token);
+    }
     
     /// 加载单个账号
-    async fn load_single_account(&self, path: &PathBuf) -> Result<Option<ProxyToken>, String> {
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| format!("读取文件失败: {}", e))?;
+    async fn load_single_account(&self, path: &Path) -> Result<Option<ProxyToken>, String> {
+        let mut account: serde_json::Value = crate::utils::fs::read_json(path)?;
+        crate::modules::crypto::open_account_value(&mut account)
+            .map_err(|e| format!("解密 Token 失败: {}", e))?;
         
-        let account: serde_json::Value = serde_json::from_str(&content)
-            .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        // 已禁用（如 refresh_token 已失效）或退出反代池的账号不参与轮换
+        if account.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false) {
+            return Ok(None);
+        }
+        if !account.get("proxy_enabled").and_then(|v| v.as_bool()).unwrap_or(true) {
+            return Ok(None);
+        }
         
         let account_id = account["id"].as_str()
             .ok_or("缺少 id 字段")?
@@ -118,6 +195,14 @@ impl TokenManager {
             .map(|s| s.to_string())
             .unwrap_or_else(|| generate_session_id());
         
+        let groups = account.get("groups")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|g| g.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        let quota_exhausted = account.get("quota")
+            .map(is_quota_exhausted)
+            .unwrap_or(false);
+        
         Ok(Some(ProxyToken {
             account_id,
             access_token,
@@ -125,99 +210,122 @@ impl TokenManager {
             expires_in,
             timestamp,
             email,
-            account_path: path.clone(),
+            account_path: path.to_path_buf(),
             project_id,
             session_id,
+            groups,
+            quota_exhausted,
         }))
     }
     
+    /// 重新加载单个账号（热更新）
+    /// 账号文件不存在、已禁用或退出反代池时从轮换中移除
+    /// 
+    /// # 返回
+    /// - `Ok(true)`: 账号在轮换中
+    /// - `Ok(false)`: 账号已移出轮换
+    pub async fn reload_account(&self, account_id: &str) -> Result<bool, String> {
+        let path = self.data_dir.join("accounts").join(format!("{}.json", account_id));
+
+        if !path.exists() {
+            self.tokens.remove(account_id);
+            return Ok(false);
+        }
+
+        match self.load_single_account(&path).await? {
+            Some(token) => {
+                self.upsert_token(token);
+                Ok(true)
+            },
+            None => {
+                self.tokens.remove(account_id);
+                Ok(false)
+            }
+        }
+    }
+
     /// 获取当前可用的 Token（轮换机制）
+    /// 优先选择配额未耗尽的账号，全部耗尽时退回到全部账号
     /// 如果 project_id 缺失，会尝试动态获取
     /// 如果 token 过期，会自动刷新
-    pub async fn get_token(&self) -> Option<ProxyToken> {
-        let total = self.tokens.len();
-        if total == 0 {
-            return None;
-        }
+    /// 
+    /// # 参数
+    /// - `group`: 账号分组，为 None 时从全部账号中选择
+    pub async fn get_token_in_group(&self, group: Option<&str>) -> Option<ProxyToken> {
+        // refresh_token 被撤销的账号会在刷新时移出轮换，此时继续尝试下一个账号
+        loop {
+            let available = self.tokens.iter().filter(|entry| entry.is_candidate(group, true)).count();
+            let skip_exhausted = available > 0;
+            let total = if skip_exhausted { available } else { self.len_in_group(group) };
+            if total == 0 {
+                return None;
+            }
         
-        let idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-        let mut token = self.tokens.iter().nth(idx).map(|entry| entry.value().clone())?;
+            let idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
+            let mut token = self.tokens.iter()
+                .filter(|entry| entry.is_candidate(group, skip_exhausted))
+                .nth(idx)
+                .map(|entry| entry.value().clone())?;
         
-        // 检查 token 是否过期（提前5分钟刷新）
-        let now = chrono::Utc::now().timestamp();
-        if now >= token.timestamp - 300 {
-            tracing::info!("账号 {} 的 token 即将过期，正在刷新...", token.email);
-            
-            // 调用 OAuth 刷新 token
-            match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
-                Ok(token_response) => {
-                    tracing::info!("Token 刷新成功！有效期: {} 秒", token_response.expires_in);
-                    
-                    // 更新 token 信息
-                    token.access_token = token_response.access_token.clone();
-                    token.expires_in = token_response.expires_in;
-                    token.timestamp = now + token_response.expires_in;
-                    
-                    // 保存到文件
-                    if let Err(e) = self.save_refreshed_token(&token.account_id, &token_response).await {
-                        tracing::warn!("保存刷新后的 token 失败: {}", e);
-                    }
-                    
-                    // 更新 DashMap 中的值
-                    if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
-                        entry.access_token = token.access_token.clone();
-                        entry.expires_in = token.expires_in;
-                        entry.timestamp = token.timestamp;
+            // 检查 token 是否即将过期，需要时刷新（同一账号的并发请求共享一次刷新）
+            if crate::modules::oauth::needs_refresh(token.timestamp, crate::modules::oauth::REFRESH_AHEAD_SECS) {
+                match self.refresh_if_needed(&token.account_id, crate::modules::oauth::REFRESH_AHEAD_SECS).await {
+                    Ok(refreshed) => {
+                        token.access_token = refreshed.access_token;
+                        token.expires_in = refreshed.expires_in;
+                        token.timestamp = refreshed.timestamp;
+                    },
+                    Err(e) if e.needs_reauth() => {
+                        // 账号已被移出轮换，换下一个账号
+                        tracing::warn!("账号 {} 需要重新授权，跳过: {}", token.email, e);
+                        continue;
+                    },
+                    Err(e) => {
+                        tracing::error!("刷新 token 失败: {}", e);
+                        // 继续使用过期的 token，让 API 返回 401
                     }
-                },
-                Err(e) => {
-                    tracing::error!("刷新 token 失败: {}", e);
-                    // 继续使用过期的 token，让 API 返回 401
                 }
             }
-        }
         
-        // 如果没有 project_id，尝试获取
-        if token.project_id.is_none() {
-            tracing::info!("账号 {} 缺少 project_id，尝试获取...", token.email);
+            // 如果没有 project_id，尝试获取
+            if token.project_id.is_none() {
+                tracing::info!("账号 {} 缺少 project_id，尝试获取...", token.email);
             
-            match crate::proxy::project_resolver::fetch_project_id(&token.access_token).await {
-                Ok(project_id) => {
-                    tracing::info!("成功获取 project_id: {}", project_id);
-                    
-                    // 更新到内存
-                    token.project_id = Some(project_id.clone());
+                match crate::proxy::project_resolver::fetch_project_id(&token.access_token).await {
+                    Ok(project_id) => {
+                        tracing::info!("成功获取 project_id: {}", project_id);
                     
-                    // 保存到文件
-                    if let Err(e) = self.save_project_id(&token.account_id, &project_id).await {
-                        tracing::warn!("保存 project_id 失败: {}", e);
-                    }
+                        // 更新到内存
+                        token.project_id = Some(project_id.clone());
                     
-                    // 更新 DashMap 中的值
-                    if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
-                        entry.project_id = Some(project_id);
-                    }
-                },
-                Err(e) => {
-                    tracing::warn!("获取 project_id 失败: {}, 使用占位符", e);
-                    // 使用占位符 ID
-                    let mock_id = crate::proxy::project_resolver::generate_mock_project_id();
-                    token.project_id = Some(mock_id.clone());
+                        // 保存到文件
+                        if let Err(e) = self.save_project_id(&token.account_id, &project_id).await {
+                            tracing::warn!("保存 project_id 失败: {}", e);
+                        }
                     
-                    // 保存占位符
-                    if let Err(e) = self.save_project_id(&token.account_id, &mock_id).await {
-                        tracing::warn!("保存占位符 project_id 失败: {}", e);
-                    }
+                        // 更新 DashMap 中的值
+                        if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
+                            entry.project_id = Some(project_id);
+                        }
+                    },
+                    Err(e) => {
+                        tracing::warn!("获取 project_id 失败: {}, 使用占位符", e);
+                        // 使用占位符 ID
+                        let mock_id = crate::proxy::project_resolver::generate_mock_project_id();
+                        token.project_id = Some(mock_id.clone());
                     
-                    // 更新内存
-                    if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
-                        entry.project_id = Some(mock_id);
+                        // 占位符只保存在内存中：写入账号文件后配额刷新会把它当作真实 project_id 缓存，
+                        // 不再调用 loadCodeAssist；重新加载账号时会再次尝试获取
+                        if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
+                            entry.project_id = Some(mock_id);
+                        }
                     }
                 }
             }
+            
+            crate::modules::quota_history::record_proxy_request(&token.account_id);
+            return Some(token);
         }
-        
-        Some(token)
     }
     
     /// 保存 project_id 到账号文件
@@ -225,16 +333,14 @@ impl TokenManager {
         let entry = self.tokens.get(account_id)
             .ok_or("账号不存在")?;
         
-        let path = &entry.account_path;
-        
-        let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
-        ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        let path = entry.account_path.clone();
+        drop(entry);
         
-        content["token"]["project_id"] = serde_json::Value::String(project_id.to_string());
-        
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        crate::utils::fs::update_json(&path, |content: &mut serde_json::Value| {
+            crate::modules::crypto::open_account_value(content)?;
+            content["token"]["project_id"] = serde_json::Value::String(project_id.to_string());
+            crate::modules::crypto::seal_account_value(content)
+        })?;
         
         tracing::info!("已保存 project_id 到账号 {}", account_id);
         Ok(())
@@ -245,20 +351,18 @@ impl TokenManager {
         let entry = self.tokens.get(account_id)
             .ok_or("账号不存在")?;
         
-        let path = &entry.account_path;
-        
-        let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
-        ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        let path = entry.account_path.clone();
+        drop(entry);
         
         let now = chrono::Utc::now().timestamp();
         
-        content["token"]["access_token"] = serde_json::Value::String(token_response.access_token.clone());
-        content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
-        content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
-        
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        crate::utils::fs::update_json(&path, |content: &mut serde_json::Value| {
+            crate::modules::crypto::open_account_value(content)?;
+            content["token"]["access_token"] = serde_json::Value::String(token_response.access_token.clone());
+            content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
+            content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
+            crate::modules::crypto::seal_account_value(content)
+        })?;
         
         tracing::info!("已保存刷新后的 token 到账号 {}", account_id);
         Ok(())
@@ -269,6 +373,14 @@ impl TokenManager {
         self.tokens.len()
     }
     
+    /// 获取指定分组内的账号数量，group 为 None 时返回全部账号数量
+    pub fn len_in_group(&self, group: Option<&str>) -> usize {
+        match group {
+            Some(g) => self.tokens.iter().filter(|entry| entry.in_group(g)).count(),
+            None => self.tokens.len(),
+        }
+    }
+    
     /// 获取所有 Token 的克隆列表
     /// 用于 TokenRefresher 遍历检查
     pub fn get_all_tokens(&self) -> Vec<ProxyToken> {
@@ -300,6 +412,90 @@ impl TokenManager {
         self.save_refreshed_token(account_id, token_response).await
     }
 
+    /// 在需要时刷新指定账号的 Token（单飞）
+    /// 
+    /// 同一账号的并发调用会在刷新锁上排队，拿到锁后重新检查内存中的最新状态，
+    /// 已被其他调用者刷新过的直接返回，因此每次过期只会触发一次刷新和一次写文件
+    /// 
+    /// # 参数
+    /// - `account_id`: 账号 ID
+    /// - `ahead_secs`: 提前刷新时间（秒）
+    pub async fn refresh_if_needed(
+        &self,
+        account_id: &str,
+        ahead_secs: i64,
+    ) -> Result<ProxyToken, crate::modules::oauth::OAuthError> {
+        use crate::modules::oauth::OAuthError;
+
+        let lock = self.refresh_locks
+            .entry(account_id.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+            .clone();
+        let _guard = lock.lock().await;
+
+        let token = self.tokens.get(account_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| OAuthError::Other(format!("账号 {} 不存在", account_id)))?;
+
+        if !crate::modules::oauth::needs_refresh(token.timestamp, ahead_secs) {
+            return Ok(token);
+        }
+
+        tracing::info!("账号 {} 的 token 即将过期，正在刷新...", token.email);
+        let token_response = match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
+            Ok(token_response) => {
+                metrics::record_token_refresh(metrics::REFRESH_SUCCESS);
+                token_response
+            }
+            Err(e) => {
+                if e.needs_reauth() {
+                    metrics::record_token_refresh(metrics::REFRESH_REVOKED);
+                    self.disable_account(&token, &e.to_string());
+                } else {
+                    metrics::record_token_refresh(metrics::REFRESH_FAILURE);
+                }
+                return Err(e);
+            }
+        };
+        tracing::info!("Token 刷新成功！有效期: {} 秒", token_response.expires_in);
+
+        if let Err(e) = self.update_token(account_id, &token_response).await {
+            tracing::warn!("保存刷新后的 token 失败: {}", e);
+        }
+
+        self.tokens.get(account_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| OAuthError::Other(format!("账号 {} 不存在", account_id)))
+    }
+
+    /// 禁用 refresh_token 已失效的账号
+    /// 从轮换中移除，在账号文件中标记需要重新授权，并通知前端
+    fn disable_account(&self, token: &ProxyToken, error: &str) {
+        self.tokens.remove(&token.account_id);
+        tracing::warn!("账号 {} 的 refresh_token 已失效，已移出轮换", token.email);
+
+        if let Err(e) = Self::mark_needs_reauth_in_file(&token.account_path) {
+            tracing::warn!("标记账号 {} 需要重新授权失败: {}", token.email, e);
+        }
+
+        if let Some(app_handle) = &self.app_handle {
+            crate::modules::account::emit_needs_reauth(app_handle, &token.account_id, &token.email, error);
+        }
+    }
+
+    /// 在账号文件中写入禁用标记
+    fn mark_needs_reauth_in_file(path: &Path) -> Result<(), String> {
+        crate::utils::fs::update_json(path, |content: &mut serde_json::Value| {
+            content["disabled"] = serde_json::Value::Bool(true);
+            content["disabled_reason"] = serde_json::Value::String(
+                crate::modules::account::DISABLED_REASON_NEEDS_REAUTH.to_string()
+            );
+            content["disabled_at"] = serde_json::Value::Number(chrono::Utc::now().timestamp().into());
+            Ok(())
+        })?;
+        Ok(())
+    }
+
     /// 启动 Token 自动刷新任务
     /// 
     /// # 参数
@@ -335,3 +531,117 @@ fn generate_session_id() -> String {
     let num: i64 = -rng.gen_range(1_000_000_000_000_000_000..9_000_000_000_000_000_000);
     num.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::TempDir;
+
+    fn temp_data_dir() -> TempDir {
+        let dir = TempDir::new("tm");
+        std::fs::create_dir_all(dir.join("accounts")).unwrap();
+        dir
+    }
+
+    fn write_account(data_dir: &std::path::Path, id: &str, extra: serde_json::Value) {
+        let mut account = serde_json::json!({
+            "id": id,
+            "email": format!("{}@example.com", id),
+            "token": {
+                "access_token": "access",
+                "refresh_token": "refresh",
+                "expires_in": 3600,
+                "expiry_timestamp": chrono::Utc::now().timestamp() + 3600,
+                "project_id": "project"
+            }
+        });
+        if let (Some(obj), Some(extra)) = (account.as_object_mut(), extra.as_object()) {
+            for (k, v) in extra {
+                obj.insert(k.clone(), v.clone());
+            }
+        }
+        let path = data_dir.join("accounts").join(format!("{}.json", id));
+        std::fs::write(path, serde_json::to_string_pretty(&account).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_accounts_removes_deleted_and_excluded() {
+        let data_dir = temp_data_dir();
+        write_account(&data_dir, "a", serde_json::json!({}));
+        write_account(&data_dir, "b", serde_json::json!({}));
+        write_account(&data_dir, "c", serde_json::json!({ "proxy_enabled": false }));
+
+        let manager = TokenManager::new(data_dir.to_path_buf());
+        assert_eq!(manager.load_accounts().await.unwrap(), 2);
+        let session_a = manager.tokens.get("a").unwrap().session_id.clone();
+
+        std::fs::remove_file(data_dir.join("accounts").join("b.json")).unwrap();
+        write_account(&data_dir, "a", serde_json::json!({ "name": "renamed" }));
+        assert_eq!(manager.load_accounts().await.unwrap(), 1);
+        assert!(manager.tokens.get("b").is_none());
+        // 热更新保留已有 sessionId
+        assert_eq!(manager.tokens.get("a").unwrap().session_id, session_a);
+
+    }
+
+    #[tokio::test]
+    async fn test_reload_account_applies_disabled_flag() {
+        let data_dir = temp_data_dir();
+        write_account(&data_dir, "a", serde_json::json!({}));
+
+        let manager = TokenManager::new(data_dir.to_path_buf());
+        manager.load_accounts().await.unwrap();
+        assert_eq!(manager.len(), 1);
+
+        write_account(&data_dir, "a", serde_json::json!({ "disabled": true }));
+        assert!(!manager.reload_account("a").await.unwrap());
+        assert_eq!(manager.len(), 0);
+
+        write_account(&data_dir, "a", serde_json::json!({ "disabled": false }));
+        assert!(manager.reload_account("a").await.unwrap());
+        assert_eq!(manager.len(), 1);
+
+    }
+
+    #[tokio::test]
+    async fn test_get_token_in_group_only_picks_group_members() {
+        let data_dir = temp_data_dir();
+        write_account(&data_dir, "a", serde_json::json!({ "groups": ["pro"] }));
+        write_account(&data_dir, "b", serde_json::json!({ "groups": ["free", "team-a"] }));
+        write_account(&data_dir, "c", serde_json::json!({}));
+
+        let manager = TokenManager::new(data_dir.to_path_buf());
+        manager.load_accounts().await.unwrap();
+        assert_eq!(manager.len_in_group(None), 3);
+        assert_eq!(manager.len_in_group(Some("PRO")), 1);
+
+        for _ in 0..4 {
+            let token = manager.get_token_in_group(Some("team-a")).await.unwrap();
+            assert_eq!(token.account_id, "b");
+        }
+        assert!(manager.get_token_in_group(Some("missing")).await.is_none());
+
+    }
+
+    #[tokio::test]
+    async fn test_get_token_prefers_accounts_with_quota() {
+        let data_dir = temp_data_dir();
+        let quota = |percentage: i32| serde_json::json!({
+            "quota": { "models": [{ "name": "m", "percentage": percentage, "reset_time": "" }], "last_updated": 0 }
+        });
+        write_account(&data_dir, "a", quota(0));
+        write_account(&data_dir, "b", quota(50));
+
+        let manager = TokenManager::new(data_dir.to_path_buf());
+        manager.load_accounts().await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(manager.get_token_in_group(None).await.unwrap().account_id, "b");
+        }
+
+        // 全部耗尽时仍然返回账号，由上游决定是否可用
+        write_account(&data_dir, "b", quota(0));
+        manager.reload_account("b").await.unwrap();
+        assert!(manager.get_token_in_group(None).await.is_some());
+
+    }
+}
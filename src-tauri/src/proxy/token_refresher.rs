@@ -9,7 +9,7 @@ use crate::proxy::signature_manager::SignatureManager;
 pub struct TokenRefresher {
     /// 刷新间隔（毫秒）- 默认 5 分钟
     refresh_interval_ms: u64,
-    /// 提前刷新时间（秒）- 默认 10 分钟
+    /// 提前刷新时间（秒）- 默认 oauth::REFRESH_AHEAD_SECS
     refresh_ahead_secs: i64,
     /// 取消信号
     cancel_token: CancellationToken,
@@ -20,7 +20,7 @@ impl TokenRefresher {
     /// 
     /// # 参数
     /// - `refresh_interval_ms`: 刷新检查间隔（毫秒），默认 300000 (5分钟)
-    /// - `refresh_ahead_secs`: 提前刷新时间（秒），默认 600 (10分钟)
+    /// - `refresh_ahead_secs`: 提前刷新时间（秒），默认 oauth::REFRESH_AHEAD_SECS
     pub fn new(refresh_interval_ms: u64, refresh_ahead_secs: i64) -> Self {
         Self {
             refresh_interval_ms,
@@ -31,9 +31,9 @@ impl TokenRefresher {
 
     /// 使用默认配置创建刷新器
     /// - 刷新间隔: 5 分钟
-    /// - 提前刷新: 10 分钟
+    /// - 提前刷新: oauth::REFRESH_AHEAD_SECS (与 TokenManager 保持一致)
     pub fn with_defaults() -> Self {
-        Self::new(5 * 60 * 1000, 10 * 60)
+        Self::new(5 * 60 * 1000, crate::modules::oauth::REFRESH_AHEAD_SECS)
     }
 
     /// 启动后台刷新任务
@@ -100,9 +100,7 @@ impl TokenRefresher {
     /// - `true`: 需要刷新
     /// - `false`: 不需要刷新
     pub fn should_refresh(token: &ProxyToken, ahead_secs: i64) -> bool {
-        let now = chrono::Utc::now().timestamp();
-        // 如果当前时间 + 提前时间 >= 过期时间，则需要刷新
-        now + ahead_secs >= token.timestamp
+        crate::modules::oauth::needs_refresh(token.timestamp, ahead_secs)
     }
 
     /// 刷新所有账号的 Token
@@ -123,25 +121,14 @@ impl TokenRefresher {
                     token.email
                 );
 
-                match Self::refresh_account(&token).await {
-                    Ok(new_token_response) => {
-                        // 更新 TokenManager 中的 Token
-                        if let Err(e) = token_manager
-                            .update_token(&token.account_id, &new_token_response)
-                            .await
-                        {
-                            tracing::error!(
-                                "更新账号 {} 的 Token 失败: {}",
-                                token.email,
-                                e
-                            );
-                        } else {
-                            tracing::info!(
-                                "账号 {} 的 Token 刷新成功，有效期: {} 秒",
-                                token.email,
-                                new_token_response.expires_in
-                            );
-                        }
+                // 通过 TokenManager 的单飞刷新，避免与请求路径上的刷新重复
+                match token_manager.refresh_if_needed(&token.account_id, ahead_secs).await {
+                    Ok(refreshed) => {
+                        tracing::info!(
+                            "账号 {} 的 Token 刷新成功，有效期: {} 秒",
+                            token.email,
+                            refreshed.expires_in
+                        );
                     }
                     Err(e) => {
                         tracing::error!(
@@ -154,20 +141,6 @@ impl TokenRefresher {
             }
         }
     }
-
-    /// 刷新单个账号的 Token
-    /// 
-    /// # 参数
-    /// - `token`: 要刷新的 Token
-    /// 
-    /// # 返回
-    /// - `Ok(TokenResponse)`: 刷新成功，返回新的 Token 响应
-    /// - `Err(String)`: 刷新失败，返回错误信息
-    async fn refresh_account(
-        token: &ProxyToken,
-    ) -> Result<crate::modules::oauth::TokenResponse, String> {
-        crate::modules::oauth::refresh_access_token(&token.refresh_token).await
-    }
 }
 
 impl Default for TokenRefresher {
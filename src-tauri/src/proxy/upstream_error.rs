@@ -0,0 +1,385 @@
+//! 上游 (Antigravity / Gemini) 错误分类
+//! GeminiClient 将 HTTP 状态码、Google 错误状态与 ErrorInfo.reason 解析为 UpstreamError，
+//! 由 RetryDelayParser 决定重试策略，再映射为 OpenAI / Anthropic 各自的客户端错误格式
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::proxy::metrics;
+use crate::proxy::retry_handler::RetryDelayParser;
+
+/// 客户端协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientProtocol {
+    /// OpenAI 兼容接口 (/v1/chat/completions)
+    OpenAI,
+    /// Anthropic 兼容接口 (/v1/messages)
+    Anthropic,
+}
+
+impl ClientProtocol {
+    /// 指标与事件中使用的协议名
+    pub fn label(self) -> &'static str {
+        match self {
+            ClientProtocol::OpenAI => metrics::PROTOCOL_OPENAI,
+            ClientProtocol::Anthropic => metrics::PROTOCOL_ANTHROPIC,
+        }
+    }
+
+    /// 按协议格式构造代理自身产生的错误响应（如没有可用账号）
+    ///
+    /// # 参数
+    /// - `status`: HTTP 状态码，Anthropic 的 error.type 由状态码决定
+    /// - `openai_type`: OpenAI 格式的 error.type
+    /// - `message`: 错误描述
+    pub fn error_response(self, status: StatusCode, openai_type: &str, message: &str) -> Response {
+        let body = match self {
+            ClientProtocol::OpenAI => json!({
+                "error": {
+                    "message": message,
+                    "type": openai_type
+                }
+            }),
+            ClientProtocol::Anthropic => {
+                let error_type = match status.as_u16() {
+                    400 => "invalid_request_error",
+                    401 => "authentication_error",
+                    403 => "permission_error",
+                    404 => "not_found_error",
+                    429 => "rate_limit_error",
+                    503 | 529 => "overloaded_error",
+                    _ => "api_error",
+                };
+                json!({
+                    "type": "error",
+                    "error": {
+                        "type": error_type,
+                        "message": message
+                    }
+                })
+            }
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+/// 上游错误类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamErrorKind {
+    /// 请求未到达上游或连接中断（连接失败、超时、流中断）
+    Transport,
+    /// 429 / RESOURCE_EXHAUSTED：限流或配额耗尽
+    RateLimited,
+    /// 401 / UNAUTHENTICATED：access_token 无效
+    Unauthenticated,
+    /// 403 / PERMISSION_DENIED：账号无权限
+    PermissionDenied,
+    /// 404 / NOT_FOUND：账号不支持该模型
+    NotFound,
+    /// 400 / INVALID_ARGUMENT：请求内容不合法
+    InvalidRequest,
+    /// 503 / UNAVAILABLE：上游过载
+    Overloaded,
+    /// 其他 5xx
+    ServerError,
+    /// 上游正常结束但没有返回任何内容
+    EmptyResponse,
+    /// 响应无法解析或状态码不符合预期
+    BadResponse,
+}
+
+/// 一次上游调用失败的详细信息
+#[derive(Debug, Clone)]
+pub struct UpstreamError {
+    pub kind: UpstreamErrorKind,
+    /// HTTP 状态码，未收到响应时为 None
+    pub status: Option<u16>,
+    /// Google 错误状态，例如 RESOURCE_EXHAUSTED
+    pub code: Option<String>,
+    /// google.rpc.ErrorInfo.reason，例如 RATE_LIMIT_EXCEEDED / QUOTA_EXHAUSTED
+    pub reason: Option<String>,
+    /// 上游建议的重试等待（毫秒）
+    pub retry_delay_ms: Option<u64>,
+    /// 错误描述
+    pub message: String,
+    /// 原始响应正文
+    pub body: String,
+}
+
+impl UpstreamError {
+    fn new(kind: UpstreamErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            status: None,
+            code: None,
+            reason: None,
+            retry_delay_ms: None,
+            message,
+            body: String::new(),
+        }
+    }
+
+    /// 解析上游返回的错误响应
+    ///
+    /// # 参数
+    /// - `status`: HTTP 状态码
+    /// - `body`: 响应正文，通常为 `{"error": {...}}`，流式接口可能包在数组中
+    pub fn from_response(status: u16, body: String) -> Self {
+        let parsed: Option<Value> = serde_json::from_str(&body).ok();
+        let error = parsed.as_ref().and_then(|v| match v {
+            Value::Array(items) => items.first().and_then(|item| item.get("error")),
+            other => other.get("error"),
+        });
+
+        let code = error
+            .and_then(|e| e.get("status"))
+            .and_then(|s| s.as_str())
+            .map(str::to_string);
+        let reason = error
+            .and_then(|e| e.get("details"))
+            .and_then(|d| d.as_array())
+            .and_then(|details| {
+                details
+                    .iter()
+                    .find(|d| d.get("@type").and_then(|t| t.as_str()).is_some_and(|t| t.contains("ErrorInfo")))
+                    .and_then(|d| d.get("reason"))
+                    .and_then(|r| r.as_str())
+            })
+            .map(str::to_string);
+        let message = error
+            .and_then(|e| e.get("message"))
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| body.trim().to_string());
+        let retry_delay_ms = error.and_then(RetryDelayParser::parse_retry_delay_from_error);
+
+        Self {
+            kind: classify(status, code.as_deref()),
+            status: Some(status),
+            code,
+            reason,
+            retry_delay_ms,
+            message,
+            body,
+        }
+    }
+
+    /// 请求发送失败或连接中断
+    pub fn transport(message: impl Into<String>) -> Self {
+        Self::new(UpstreamErrorKind::Transport, message.into())
+    }
+
+    /// 上游没有返回内容就结束
+    ///
+    /// # 参数
+    /// - `finish_reason`: Gemini 的结束原因，例如 MAX_TOKENS / STOP
+    pub fn empty_response(finish_reason: &str) -> Self {
+        Self::new(
+            UpstreamErrorKind::EmptyResponse,
+            format!("Gemini 返回空内容 ({})", finish_reason),
+        )
+    }
+
+    /// 响应无法解析
+    pub fn bad_response(message: impl Into<String>, body: String) -> Self {
+        Self {
+            body,
+            ..Self::new(UpstreamErrorKind::BadResponse, message.into())
+        }
+    }
+
+    /// 限流是否由配额耗尽导致（需要等待配额重置，而非短暂限流）
+    pub fn is_quota_exhausted(&self) -> bool {
+        self.reason.as_deref() == Some("QUOTA_EXHAUSTED")
+    }
+
+    /// OpenAI 格式的 (状态码, error.type, error.code)
+    fn openai_error(&self) -> (StatusCode, &'static str, &'static str) {
+        match self.kind {
+            UpstreamErrorKind::RateLimited if self.is_quota_exhausted() => {
+                (StatusCode::TOO_MANY_REQUESTS, "insufficient_quota", "insufficient_quota")
+            }
+            UpstreamErrorKind::RateLimited => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error", "rate_limit_exceeded"),
+            UpstreamErrorKind::Unauthenticated => {
+                (StatusCode::UNAUTHORIZED, "authentication_error", "upstream_unauthenticated")
+            }
+            UpstreamErrorKind::PermissionDenied => (StatusCode::FORBIDDEN, "permission_error", "permission_denied"),
+            UpstreamErrorKind::NotFound => (StatusCode::NOT_FOUND, "invalid_request_error", "model_not_found"),
+            UpstreamErrorKind::InvalidRequest => (StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_request"),
+            UpstreamErrorKind::Overloaded => (StatusCode::SERVICE_UNAVAILABLE, "server_error", "overloaded"),
+            UpstreamErrorKind::ServerError => (self.upstream_status(), "server_error", "server_error"),
+            UpstreamErrorKind::Transport => (StatusCode::BAD_GATEWAY, "server_error", "upstream_unavailable"),
+            UpstreamErrorKind::EmptyResponse => (StatusCode::BAD_GATEWAY, "server_error", "empty_response"),
+            UpstreamErrorKind::BadResponse => (StatusCode::BAD_GATEWAY, "server_error", "bad_upstream_response"),
+        }
+    }
+
+    /// Anthropic 格式的 (状态码, error.type)
+    fn anthropic_error(&self) -> (StatusCode, &'static str) {
+        match self.kind {
+            UpstreamErrorKind::RateLimited => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error"),
+            UpstreamErrorKind::Unauthenticated => (StatusCode::UNAUTHORIZED, "authentication_error"),
+            UpstreamErrorKind::PermissionDenied => (StatusCode::FORBIDDEN, "permission_error"),
+            UpstreamErrorKind::NotFound => (StatusCode::NOT_FOUND, "not_found_error"),
+            UpstreamErrorKind::InvalidRequest => (StatusCode::BAD_REQUEST, "invalid_request_error"),
+            // Anthropic 使用 529 表示过载
+            UpstreamErrorKind::Overloaded => (
+                StatusCode::from_u16(529).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                "overloaded_error",
+            ),
+            UpstreamErrorKind::ServerError => (self.upstream_status(), "api_error"),
+            UpstreamErrorKind::Transport | UpstreamErrorKind::EmptyResponse | UpstreamErrorKind::BadResponse => {
+                (StatusCode::BAD_GATEWAY, "api_error")
+            }
+        }
+    }
+
+    fn upstream_status(&self) -> StatusCode {
+        self.status
+            .and_then(|s| StatusCode::from_u16(s).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Anthropic 错误对象，也用作流式响应中的 error 事件数据
+    pub fn anthropic_body(&self, message: &str) -> Value {
+        let (_, error_type) = self.anthropic_error();
+        json!({
+            "type": "error",
+            "error": {
+                "type": error_type,
+                "message": message
+            }
+        })
+    }
+
+    /// 按客户端协议生成错误响应
+    ///
+    /// # 参数
+    /// - `protocol`: 客户端协议
+    /// - `message`: 返回给客户端的错误描述
+    pub fn to_response(&self, protocol: ClientProtocol, message: &str) -> Response {
+        match protocol {
+            ClientProtocol::OpenAI => {
+                let (status, error_type, code) = self.openai_error();
+                (
+                    status,
+                    Json(json!({
+                        "error": {
+                            "message": message,
+                            "type": error_type,
+                            "code": code
+                        }
+                    })),
+                )
+                    .into_response()
+            }
+            ClientProtocol::Anthropic => {
+                let (status, _) = self.anthropic_error();
+                (status, Json(self.anthropic_body(message))).into_response()
+            }
+        }
+    }
+
+    /// 不重试时返回给客户端的错误
+    pub fn client_response(&self, protocol: ClientProtocol) -> Response {
+        self.to_response(protocol, &format!("Antigravity API 错误: {}", self))
+    }
+
+    /// 所有账号都尝试失败后返回给客户端的错误
+    pub fn exhausted_response(&self, protocol: ClientProtocol) -> Response {
+        self.to_response(protocol, &format!("所有账号配额已耗尽或请求失败。最后错误: {}", self))
+    }
+}
+
+/// 按 HTTP 状态码与 Google 错误状态分类，错误状态优先
+fn classify(status: u16, code: Option<&str>) -> UpstreamErrorKind {
+    match code {
+        Some("RESOURCE_EXHAUSTED") => return UpstreamErrorKind::RateLimited,
+        Some("UNAUTHENTICATED") => return UpstreamErrorKind::Unauthenticated,
+        Some("PERMISSION_DENIED") => return UpstreamErrorKind::PermissionDenied,
+        Some("NOT_FOUND") => return UpstreamErrorKind::NotFound,
+        Some("INVALID_ARGUMENT") | Some("FAILED_PRECONDITION") => return UpstreamErrorKind::InvalidRequest,
+        Some("UNAVAILABLE") => return UpstreamErrorKind::Overloaded,
+        _ => {}
+    }
+    match status {
+        429 => UpstreamErrorKind::RateLimited,
+        401 => UpstreamErrorKind::Unauthenticated,
+        403 => UpstreamErrorKind::PermissionDenied,
+        404 => UpstreamErrorKind::NotFound,
+        400 => UpstreamErrorKind::InvalidRequest,
+        503 | 529 => UpstreamErrorKind::Overloaded,
+        500..=599 => UpstreamErrorKind::ServerError,
+        _ => UpstreamErrorKind::BadResponse,
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.status, &self.code) {
+            (Some(status), Some(code)) => write!(f, "上游服务错误 ({} {}): {}", status, code, self.message),
+            (Some(status), None) => write!(f, "上游服务错误 ({}): {}", status, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for UpstreamError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_response_parses_google_error() {
+        let body = r#"[{
+            "error": {
+                "code": 429,
+                "message": "Resource has been exhausted",
+                "status": "RESOURCE_EXHAUSTED",
+                "details": [
+                    { "@type": "type.googleapis.com/google.rpc.ErrorInfo", "reason": "QUOTA_EXHAUSTED" },
+                    { "@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "2s" }
+                ]
+            }
+        }]"#;
+        let error = UpstreamError::from_response(429, body.to_string());
+        assert_eq!(error.kind, UpstreamErrorKind::RateLimited);
+        assert_eq!(error.code.as_deref(), Some("RESOURCE_EXHAUSTED"));
+        assert_eq!(error.reason.as_deref(), Some("QUOTA_EXHAUSTED"));
+        assert_eq!(error.retry_delay_ms, Some(2000));
+        assert_eq!(error.message, "Resource has been exhausted");
+        assert_eq!(error.to_string(), "上游服务错误 (429 RESOURCE_EXHAUSTED): Resource has been exhausted");
+    }
+
+    #[test]
+    fn test_classify_prefers_google_status() {
+        let body = r#"{"error": {"code": 400, "message": "quota", "status": "RESOURCE_EXHAUSTED"}}"#;
+        assert_eq!(UpstreamError::from_response(400, body.to_string()).kind, UpstreamErrorKind::RateLimited);
+        assert_eq!(UpstreamError::from_response(503, "busy".to_string()).kind, UpstreamErrorKind::Overloaded);
+        assert_eq!(UpstreamError::from_response(502, String::new()).kind, UpstreamErrorKind::ServerError);
+        assert_eq!(UpstreamError::from_response(200, String::new()).kind, UpstreamErrorKind::BadResponse);
+    }
+
+    #[test]
+    fn test_protocol_error_mapping() {
+        let overloaded = UpstreamError::from_response(503, "busy".to_string());
+        let (status, error_type) = overloaded.anthropic_error();
+        assert_eq!(status.as_u16(), 529);
+        assert_eq!(error_type, "overloaded_error");
+        assert_eq!(overloaded.openai_error().2, "overloaded");
+
+        let invalid = UpstreamError::from_response(400, "bad".to_string());
+        assert_eq!(invalid.anthropic_error().1, "invalid_request_error");
+        assert_eq!(invalid.anthropic_body("x")["error"]["type"], "invalid_request_error");
+
+        let limited = UpstreamError::from_response(429, String::new());
+        assert_eq!(limited.openai_error(), (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error", "rate_limit_exceeded"));
+        assert_eq!(UpstreamError::transport("timeout").anthropic_error().1, "api_error");
+    }
+}
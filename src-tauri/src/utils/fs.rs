@@ -0,0 +1,243 @@
+use dashmap::DashMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// 每个文件一把锁，GUI 与反代服务的所有写入方共享
+/// (两者运行在同一进程内，进程内锁即可保证读-改-写不互相覆盖)
+static FILE_LOCKS: OnceLock<DashMap<PathBuf, Arc<Mutex<()>>>> = OnceLock::new();
+
+/// 获取指定文件的锁
+pub fn file_lock(path: &Path) -> Arc<Mutex<()>> {
+    FILE_LOCKS
+        .get_or_init(DashMap::new)
+        .entry(path.to_path_buf())
+        .or_insert_with(|| Arc::new(Mutex::new(())))
+        .clone()
+}
+
+/// 最近一次可正常解析的备份文件路径 (xxx.json -> xxx.json.bak)
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// 原子写入文件（调用方负责持有文件锁）
+/// 先写入同目录临时文件并 fsync，再 rename 覆盖目标文件，
+/// 崩溃时目标文件要么是旧内容，要么是完整的新内容
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let dir = path.parent().ok_or_else(|| format!("无效的文件路径: {:?}", path))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4().simple()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)
+            .map_err(|e| format!("创建临时文件失败: {}", e))?;
+        file.write_all(content)
+            .map_err(|e| format!("写入临时文件失败: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("同步临时文件失败: {}", e))?;
+        fs::rename(&tmp_path, path)
+            .map_err(|e| format!("替换文件失败: {}", e))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // 同步目录项，保证 rename 本身落盘
+    #[cfg(unix)]
+    if let Ok(dir_file) = fs::File::open(dir) {
+        let _ = dir_file.sync_all();
+    }
+
+    Ok(())
+}
+
+/// 读取 JSON 文件，解析失败时从最近一次的备份恢复
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let lock = file_lock(path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    read_json_unlocked(path)
+}
+
+/// 原子写入 JSON 文件
+/// 覆盖前会把当前可解析的内容保存为备份，供损坏时恢复
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let lock = file_lock(path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    write_json_unlocked(path, value)
+}
+
+/// 在文件锁内完成读-改-写，避免并发写入互相覆盖
+pub fn update_json<T, F>(path: &Path, update: F) -> Result<T, String>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce(&mut T) -> Result<(), String>,
+{
+    let lock = file_lock(path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut value: T = read_json_unlocked(path)?;
+    update(&mut value)?;
+    write_json_unlocked(path, &value)?;
+    Ok(value)
+}
+
+/// 与 update_json 相同，文件不存在时从 `init` 返回的初始值开始修改
+pub fn update_json_or<T, I, F>(path: &Path, init: I, update: F) -> Result<T, String>
+where
+    T: Serialize + DeserializeOwned,
+    I: FnOnce() -> T,
+    F: FnOnce(&mut T) -> Result<(), String>,
+{
+    let lock = file_lock(path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut value: T = if path.exists() { read_json_unlocked(path)? } else { init() };
+    update(&mut value)?;
+    write_json_unlocked(path, &value)?;
+    Ok(value)
+}
+
+/// 在文件锁内完成读-改-写，且不保留旧内容
+/// 备份同样被替换为新内容，用于旧内容不应继续留在磁盘上的场景 (如明文 Token 加密迁移)
+pub fn update_json_discarding_backup<T, F>(path: &Path, update: F) -> Result<T, String>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce(&mut T) -> Result<(), String>,
+{
+    let lock = file_lock(path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut value: T = read_json_unlocked(path)?;
+    update(&mut value)?;
+    let content = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("序列化失败: {}", e))?;
+    // 先覆盖备份：中途崩溃时目标文件仍是旧内容，下次迁移会重新处理
+    write_atomic(&backup_path(path), content.as_bytes())?;
+    write_atomic(path, content.as_bytes())?;
+    Ok(value)
+}
+
+fn read_json_unlocked<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+
+    match serde_json::from_str(&content) {
+        Ok(value) => Ok(value),
+        Err(parse_err) => {
+            let backup = backup_path(path);
+            let backup_content = fs::read_to_string(&backup)
+                .map_err(|_| format!("解析 JSON 失败: {} (无可用备份)", parse_err))?;
+            let value = serde_json::from_str(&backup_content)
+                .map_err(|_| format!("解析 JSON 失败: {} (备份同样损坏)", parse_err))?;
+
+            tracing::warn!("文件 {:?} 已损坏 ({})，已从备份恢复", path, parse_err);
+            write_atomic(path, backup_content.as_bytes())?;
+            Ok(value)
+        }
+    }
+}
+
+fn write_json_unlocked<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("序列化失败: {}", e))?;
+
+    // 只备份可正常解析的旧内容，避免用损坏的文件覆盖有效备份
+    if let Ok(current) = fs::read_to_string(path) {
+        if serde_json::from_str::<serde_json::Value>(&current).is_ok() {
+            if let Err(e) = write_atomic(&backup_path(path), current.as_bytes()) {
+                tracing::warn!("备份文件 {:?} 失败: {}", path, e);
+            }
+        }
+    }
+
+    write_atomic(path, content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::TempDir;
+
+    #[test]
+    fn test_write_then_read_roundtrip() {
+        let dir = TempDir::new("fs");
+        let path = dir.join("a.json");
+        write_json(&path, &serde_json::json!({ "v": 1 })).unwrap();
+        let value: serde_json::Value = read_json(&path).unwrap();
+        assert_eq!(value["v"], 1);
+        // 首次写入没有旧内容，不产生备份
+        assert!(!backup_path(&path).exists());
+    }
+
+    #[test]
+    fn test_corrupted_file_restored_from_backup() {
+        let dir = TempDir::new("fs");
+        let path = dir.join("a.json");
+        write_json(&path, &serde_json::json!({ "v": 1 })).unwrap();
+        write_json(&path, &serde_json::json!({ "v": 2 })).unwrap();
+
+        // 模拟写入中途崩溃导致的截断
+        fs::write(&path, "{\"v\": ").unwrap();
+
+        let value: serde_json::Value = read_json(&path).unwrap();
+        assert_eq!(value["v"], 1);
+        // 恢复后原文件重新可解析
+        let restored: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(restored["v"], 1);
+    }
+
+    #[test]
+    fn test_update_json_or_starts_from_init_and_skips_failed_update() {
+        let dir = TempDir::new("fs");
+        let path = dir.join("index.json");
+
+        let failed = update_json_or(&path, || serde_json::json!({ "n": 0 }), |_: &mut serde_json::Value| {
+            Err("rejected".to_string())
+        });
+        assert_eq!(failed.unwrap_err(), "rejected");
+        assert!(!path.exists());
+
+        update_json_or(&path, || serde_json::json!({ "n": 0 }), |v: &mut serde_json::Value| {
+            v["n"] = serde_json::json!(1);
+            Ok(())
+        })
+        .unwrap();
+        let value: serde_json::Value = read_json(&path).unwrap();
+        assert_eq!(value["n"], 1);
+    }
+
+    #[test]
+    fn test_update_json_concurrent_increments() {
+        let dir = TempDir::new("fs");
+        let path = dir.join("counter.json");
+        write_json(&path, &serde_json::json!({ "n": 0 })).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        update_json::<serde_json::Value, _>(&path, |v| {
+                            v["n"] = serde_json::json!(v["n"].as_i64().unwrap() + 1);
+                            Ok(())
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let value: serde_json::Value = read_json(&path).unwrap();
+        assert_eq!(value["n"], 80);
+    }
+}
@@ -1,2 +1,6 @@
 pub mod http;
 pub mod protobuf;
+pub mod fs;
+pub mod redact;
+#[cfg(test)]
+pub mod testing;
@@ -46,7 +46,7 @@ pub fn skip_field(data: &[u8], offset: usize, wire_type: u8) -> Result<usize, St
         2 => {
             // Length-delimited
             let (length, content_offset) = read_varint(data, offset)?;
-            Ok(content_offset + length as usize)
+            Ok(content_offset.saturating_add(length as usize))
         }
         5 => {
             // 32-bit
@@ -56,6 +56,25 @@ pub fn skip_field(data: &[u8], offset: usize, wire_type: u8) -> Result<usize, St
     }
 }
 
+/// 校验数据能否完整解析为 Protobuf 字段序列
+/// 字段越界、wire_type 未知或末尾有残留字节时返回错误
+pub fn validate(data: &[u8]) -> Result<(), String> {
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let (tag, new_offset) = read_varint(data, offset)?;
+        if tag >> 3 == 0 {
+            return Err(format!("无效的字段编号 (偏移 {})", offset));
+        }
+        offset = skip_field(data, new_offset, (tag & 7) as u8)?;
+        if offset > data.len() {
+            return Err(format!("字段长度越界 (偏移 {})", new_offset));
+        }
+    }
+
+    Ok(())
+}
+
 /// 移除指定的 Protobuf 字段
 pub fn remove_field(data: &[u8], field_num: u32) -> Result<Vec<u8>, String> {
     let mut result = Vec::new();
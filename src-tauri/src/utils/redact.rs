@@ -0,0 +1,84 @@
+//! 日志脱敏
+//! 屏蔽日志中的 Token、密钥与邮箱，避免日志文件泄露凭据
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// 凭据匹配规则与替换内容，按顺序应用
+fn secret_rules() -> &'static [(Regex, &'static str)] {
+    static RULES: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            // JSON / 表单 / 查询参数中的凭据字段
+            (
+                r#"(?i)("?(?:access_token|refresh_token|id_token|api_key|admin_key|client_secret|password)"?\s*[:=]\s*"?)[^"\s,&}]+"#,
+                "${1}***",
+            ),
+            (r"(?i)(bearer\s+)[A-Za-z0-9._~+/=\-]+", "${1}***"),
+            // Google OAuth access_token / refresh_token
+            (r"ya29\.[A-Za-z0-9._\-]+", "ya29.***"),
+            (r"\b1//[A-Za-z0-9_\-]{10,}", "1//***"),
+            // 反代 API 密钥
+            (r"\bsk-[A-Za-z0-9_\-]{8,}", "sk-***"),
+            // JWT (id_token 等)
+            (r"\beyJ[A-Za-z0-9_\-]+\.[A-Za-z0-9_\-]+\.[A-Za-z0-9_\-]+", "eyJ***"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("无效的脱敏规则"), replacement))
+        .collect()
+    })
+}
+
+fn email_pattern() -> &'static Regex {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    EMAIL.get_or_init(|| {
+        Regex::new(r"\b([A-Za-z0-9])[A-Za-z0-9._%+\-]*@([A-Za-z0-9\-]+(?:\.[A-Za-z0-9\-]+)*\.[A-Za-z]{2,})\b")
+            .expect("无效的邮箱规则")
+    })
+}
+
+/// 屏蔽文本中的 Token 与密钥
+pub fn mask_secrets(text: &str) -> String {
+    secret_rules()
+        .iter()
+        .fold(text.to_string(), |acc, (re, replacement)| re.replace_all(&acc, *replacement).into_owned())
+}
+
+/// 屏蔽邮箱用户名，只保留首字符与域名，例如 `a***@gmail.com`
+pub fn mask_emails(text: &str) -> String {
+    email_pattern().replace_all(text, "${1}***@${2}").into_owned()
+}
+
+/// 按字符截断，超出部分以 `...` 结尾（不会截断在多字节字符中间）
+pub fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_secrets() {
+        let line = r#"refresh failed: {"access_token": "ya29.a0AfB_byC-123", "refresh_token":"1//0gAbCdEfGhIjKlMn"} Authorization: Bearer sk-abcdef123456"#;
+        let masked = mask_secrets(line);
+        assert!(!masked.contains("a0AfB_byC"));
+        assert!(!masked.contains("0gAbCdEfGh"));
+        assert!(!masked.contains("abcdef123456"));
+        assert!(masked.contains(r#""access_token": "***""#));
+        assert!(masked.contains("Bearer ***"));
+
+        assert_eq!(mask_secrets("key=sk-1234567890abc"), "key=sk-***");
+        assert_eq!(mask_secrets("模型 gemini-3-pro-preview 请求完成"), "模型 gemini-3-pro-preview 请求完成");
+    }
+
+    #[test]
+    fn test_mask_emails_and_truncate() {
+        assert_eq!(mask_emails("账号 alice.smith@gmail.com 刷新成功"), "账号 a***@gmail.com 刷新成功");
+        assert_eq!(truncate_chars("你好世界", 2), "你好...");
+        assert_eq!(truncate_chars("hello", 10), "hello");
+    }
+}
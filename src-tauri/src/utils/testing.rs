@@ -0,0 +1,42 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// 测试用临时目录，离开作用域时自动删除
+///
+/// 断言失败导致 panic 时同样会在展开过程中清理，不会在系统临时目录留下残留
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// 在系统临时目录下创建 `antigravity_<prefix>_test_<uuid>` 目录
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("antigravity_{}_test_{}", prefix, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,21 +1,23 @@
 import { useEffect, useRef } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import { useConfigStore } from '../../stores/useConfigStore';
 import { useAccountStore } from '../../stores/useAccountStore';
 
 function BackgroundTaskRunner() {
     const { config } = useConfigStore();
-    const { refreshAllQuotas, fetchCurrentAccount } = useAccountStore();
+    const { refreshAllQuotas, fetchCurrentAccount, fetchAccounts } = useAccountStore();
 
     // Use refs to track previous state to detect "off -> on" transitions
     const prevAutoRefreshRef = useRef(false);
     const prevAutoSyncRef = useRef(false);
 
     // Auto Refresh Quota Effect
+    // Periodic refresh is driven by the backend QuotaScheduler (auto_refresh / refresh_interval).
+    // Only refresh once when it gets turned on, so accounts are not refreshed twice per period.
     useEffect(() => {
         if (!config) return;
 
-        let intervalId: ReturnType<typeof setTimeout> | null = null;
-        const { auto_refresh, refresh_interval } = config;
+        const { auto_refresh } = config;
 
         // Check if we just turned it on
         if (auto_refresh && !prevAutoRefreshRef.current) {
@@ -23,22 +25,19 @@ function BackgroundTaskRunner() {
             refreshAllQuotas();
         }
         prevAutoRefreshRef.current = auto_refresh;
+    }, [config?.auto_refresh]);
 
-        if (auto_refresh && refresh_interval > 0) {
-            console.log(`[BackgroundTask] Starting auto-refresh quota timer: ${refresh_interval} mins`);
-            intervalId = setInterval(() => {
-                console.log('[BackgroundTask] Auto-refreshing all quotas...');
-                refreshAllQuotas();
-            }, refresh_interval * 60 * 1000);
-        }
+    // Reload the account list after a backend scheduled refresh
+    useEffect(() => {
+        const unlistenPromise = listen('quota://updated', () => {
+            console.log('[BackgroundTask] Quotas refreshed by scheduler, reloading accounts...');
+            fetchAccounts();
+        });
 
         return () => {
-            if (intervalId) {
-                console.log('[BackgroundTask] Clearing auto-refresh timer');
-                clearInterval(intervalId);
-            }
+            unlistenPromise.then(unlisten => unlisten());
         };
-    }, [config?.auto_refresh, config?.refresh_interval]);
+    }, [fetchAccounts]);
 
     // Auto Sync Current Account Effect
     useEffect(() => {
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 import { Account, QuotaData } from '../types/account';
+import { SwitchMode } from '../types/config';
 
 // 检查 Tauri 环境
 function ensureTauriEnvironment() {
@@ -26,10 +27,26 @@ export async function deleteAccount(accountId: string): Promise<void> {
     return await invoke('delete_account', { accountId });
 }
 
-export async function switchAccount(accountId: string): Promise<void> {
+export async function switchAccount(accountId: string): Promise<SwitchMode> {
     return await invoke('switch_account', { accountId });
 }
 
+export async function setAccountDisabled(accountId: string, disabled: boolean): Promise<Account> {
+    return await invoke('set_account_disabled', { accountId, disabled });
+}
+
+export async function setAccountProxyEnabled(accountId: string, enabled: boolean): Promise<Account> {
+    return await invoke('set_account_proxy_enabled', { accountId, enabled });
+}
+
+export async function setAccountGroups(accountId: string, groups: string[]): Promise<Account> {
+    return await invoke('set_account_groups', { accountId, groups });
+}
+
+export async function listAccountGroups(): Promise<string[]> {
+    return await invoke('list_account_groups');
+}
+
 export async function fetchAccountQuota(accountId: string): Promise<QuotaData> {
     return await invoke('fetch_account_quota', { accountId });
 }
@@ -41,8 +58,50 @@ export interface RefreshStats {
     details: string[];
 }
 
-export async function refreshAllQuotas(): Promise<RefreshStats> {
-    return await invoke('refresh_all_quotas');
+export async function refreshAllQuotas(group?: string): Promise<RefreshStats> {
+    return await invoke('refresh_all_quotas', { group: group ?? null });
+}
+
+// 配额历史与预测
+export interface QuotaHistoryPoint {
+    timestamp: number;
+    models: Record<string, number>;
+    proxy_requests: number;
+}
+
+export interface QuotaForecast {
+    account_id: string;
+    email: string;
+    model: string;
+    current_percentage: number;
+    burn_rate_per_hour: number | null;
+    requests_per_percent: number | null;
+    exhausts_at: number | null;
+    resets_at: number | null;
+    exhausts_before_reset: boolean;
+}
+
+export async function getQuotaHistory(accountId: string, model?: string, since?: number): Promise<QuotaHistoryPoint[]> {
+    return await invoke('get_quota_history', { accountId, model: model ?? null, since: since ?? null });
+}
+
+export async function getQuotaForecasts(accountId?: string): Promise<QuotaForecast[]> {
+    return await invoke('get_quota_forecasts', { accountId: accountId ?? null });
+}
+
+export interface DbBackupInfo {
+    id: string;
+    created_at: number;
+    reason: string;
+    size: number;
+}
+
+export async function listDbBackups(): Promise<DbBackupInfo[]> {
+    return await invoke('list_db_backups');
+}
+
+export async function restoreDbBackup(backupId: string): Promise<void> {
+    return await invoke('restore_db_backup', { backupId });
 }
 
 // OAuth
@@ -65,6 +124,11 @@ export async function startOAuthLogin(): Promise<Account> {
     }
 }
 
+export async function reauthorizeAccount(accountId: string): Promise<Account> {
+    ensureTauriEnvironment();
+    return await invoke('reauthorize_account', { accountId });
+}
+
 export async function cancelOAuthLogin(): Promise<void> {
     ensureTauriEnvironment();
     return await invoke('cancel_oauth_login');
@@ -78,3 +142,43 @@ export async function importV1Accounts(): Promise<Account[]> {
 export async function importFromDb(): Promise<Account> {
     return await invoke('import_from_db');
 }
+
+// 批量导入 refresh_token
+export interface BulkImportItem {
+    line: number;
+    success: boolean;
+    email: string | null;
+    account_id: string | null;
+    message: string;
+}
+
+export interface BulkImportReport {
+    total: number;
+    success: number;
+    failed: number;
+    items: BulkImportItem[];
+}
+
+export async function importRefreshTokens(content: string): Promise<BulkImportReport> {
+    return await invoke('import_refresh_tokens', { content });
+}
+
+// 加密账号包
+export type BundleConflictStrategy = 'skip' | 'overwrite' | 'merge';
+
+export interface BundleImportReport {
+    total: number;
+    added: number;
+    updated: number;
+    skipped: number;
+    failed: number;
+    details: string[];
+}
+
+export async function exportAccountBundle(path: string, passphrase: string): Promise<number> {
+    return await invoke('export_account_bundle', { path, passphrase });
+}
+
+export async function importAccountBundle(path: string, passphrase: string, conflict: BundleConflictStrategy): Promise<BundleImportReport> {
+    return await invoke('import_account_bundle', { path, passphrase, conflict });
+}
@@ -6,6 +6,11 @@ export interface Account {
     quota?: QuotaData;
     created_at: number;
     last_used: number;
+    disabled?: boolean;
+    disabled_reason?: string;
+    disabled_at?: number;
+    proxy_enabled?: boolean;
+    groups?: string[];
 }
 
 export interface TokenData {
@@ -6,13 +6,51 @@ export interface UpstreamProxyConfig {
 export interface ProxyConfig {
     enabled: boolean;
     port: number;
+    bind_address?: string;
     api_key: string;
+    admin_key?: string;
     auto_start: boolean;
     anthropic_mapping?: Record<string, string>;
     request_timeout: number;
     upstream_proxy: UpstreamProxyConfig;
+    api_key_groups?: Record<string, string>;
+    retry?: RetryPolicy;
 }
 
+export interface RetryPolicy {
+    max_attempts: number;
+    max_same_account_waits: number;
+    short_delay_threshold_ms: number;
+    backoff_base_ms: number;
+    backoff_max_ms: number;
+    backoff_jitter: number;
+    deadline_secs: number;
+}
+
+export interface AutoSwitchConfig {
+    enabled: boolean;
+    threshold: number;
+    models: string[];
+    check_interval_secs: number;
+    min_interval_minutes: number;
+    grace_period_secs: number;
+    busy_cpu_threshold: number;
+}
+
+export interface LogConfig {
+    level: string;
+    module_levels: Record<string, string>;
+    json: boolean;
+    max_file_size_mb: number;
+    max_files: number;
+    content_logging: ContentLogging;
+    privacy_mode: boolean;
+}
+
+export type ContentLogging = 'off' | 'preview' | 'full';
+
+export type SwitchMode = 'restart' | 'soft';
+
 export interface AppConfig {
     language: string;
     theme: string;
@@ -21,5 +59,10 @@ export interface AppConfig {
     auto_sync: boolean;
     sync_interval: number;
     default_export_path?: string;
+    quota_alert_threshold?: number;
+    auto_switch?: AutoSwitchConfig;
+    switch_mode?: SwitchMode;
+    db_backup_retention?: number;
+    log?: LogConfig;
     proxy: ProxyConfig;
 }
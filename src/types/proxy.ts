@@ -0,0 +1,12 @@
+/** 反代请求生命周期事件 (proxy://event, /admin/events) */
+export type ProxyEventKind =
+    | { type: 'started'; protocol: 'openai' | 'anthropic'; model: string }
+    | { type: 'account_chosen'; account_id: string; email: string; attempt: number }
+    | { type: 'retry'; email: string; reason: string; delay_ms: number | null }
+    | { type: 'completed'; status: number; duration_ms: number }
+    | { type: 'failed'; status: number; error: string; duration_ms: number };
+
+export type ProxyEvent = ProxyEventKind & {
+    request_id: string;
+    timestamp: number;
+};